@@ -7,6 +7,7 @@ use plonky2::{
         config::PoseidonGoldilocksConfig,
     },
 };
+use types::types::Address;
 
 fn main() {
     let wallet = Wallet::initialize_new_wallet();
@@ -21,6 +22,7 @@ fn main() {
 
     let mut partial_witness = PartialWitness::<F>::new();
 
+    let from = Address::from_public_key(&public_key);
     let plonky2_public_key = public_key.into_plonky2_public_key();
     let plonky2_message = message.into_plonky2_message();
     let plonky2_signature = signature.into_plonky2_signature();
@@ -29,6 +31,7 @@ fn main() {
         plonky2_message,
         plonky2_public_key,
         plonky2_signature,
+        from,
         ecdsa_signature_targets,
     );
 
@@ -1,11 +1,12 @@
 use libsecp256k1::{sign, verify};
 use rand::Rng;
-use types::types::{Message, PrivateKey, PublicKey, Signature};
+use types::types::{Message, PrivateKey, PublicKey, RecoverableSignature, Signature};
 
 pub trait ETHWallet {
     fn initialize_new_wallet() -> Self;
     fn initialize_from_private_key(private_key: PrivateKey) -> Self;
     fn sign_message(self, message: &Message) -> Signature;
+    fn sign_message_recoverable(self, message: &Message) -> RecoverableSignature;
     fn get_public_key(&self) -> PublicKey;
 }
 
@@ -45,6 +46,14 @@ impl ETHWallet for Wallet {
         );
         Signature::new(signature)
     }
+
+    fn sign_message_recoverable(self, message: &Message) -> RecoverableSignature {
+        let (signature, recovery_id) = sign(
+            message.as_libsecp256k1_message(),
+            &self.private_key.into_secret_key(),
+        );
+        RecoverableSignature::new(signature, recovery_id)
+    }
 }
 
 pub fn verify_signature(message: &Message, signature: &Signature, public_key: &PublicKey) -> bool {
@@ -58,8 +67,8 @@ pub fn verify_signature(message: &Message, signature: &Signature, public_key: &P
 pub fn generate_random_message() -> Message {
     let data = rand::thread_rng().gen::<[u8; 32]>();
     println!("data = {:?}", data);
-    let message = Message::new_message(data);
-    message
+
+    Message::new_message(data)
 }
 
 #[cfg(test)]
@@ -78,7 +87,7 @@ mod tests {
 
         let plonky2_message = message.into_plonky2_message();
         let plonky2_public_key = public_key.into_plonky2_public_key();
-        let plonky2_signature = signature.clone().into_plonky2_signature();
+        let plonky2_signature = signature.into_plonky2_signature();
 
         assert!(verify_message(
             plonky2_message,
@@ -87,6 +96,22 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn it_works_wallet_recoverable_signature() {
+        let wallet = Wallet::initialize_new_wallet();
+        let message = generate_random_message();
+        let public_key = wallet.get_public_key();
+
+        let signature = wallet.sign_message_recoverable(&message);
+
+        assert!(verify_signature(
+            &message,
+            &signature.to_signature(),
+            &public_key
+        ));
+        assert_eq!(signature.recover_public_key(&message).unwrap(), public_key);
+    }
+
     #[test]
     fn it_works_signature_scheme_libsecp256k1_plonky2_wallet() {
         let wallet = Wallet::initialize_new_wallet();
@@ -97,7 +122,7 @@ mod tests {
 
         let plonky2_message = message.into_plonky2_message();
         let plonky2_public_key = public_key.into_plonky2_public_key();
-        let plonky2_signature = signature.clone().into_plonky2_signature();
+        let plonky2_signature = signature.into_plonky2_signature();
 
         assert!(verify_message(
             plonky2_message,
@@ -0,0 +1,496 @@
+//! Aggregation of intent signature proofs, by a binary tree of recursive proofs.
+//!
+//! Each leaf is an intent signature proof exposing its intent commitment (see
+//! [`PublicInputsOptions::intent_commitment`]) as its last public inputs. Each node of the tree
+//! verifies two proofs of the level below, and exposes the Poseidon hash of their commitments
+//! with the number of intents below it, so that the root proof exposes the Merkle root of the
+//! batch's intent commitments, then the batch's size.
+//!
+//! The batch is padded to a power of two by repeating its last proof, flagged as padding: the
+//! padding leaves commit to a zero hash, and do not count as intents, so that a batch is not
+//! mistaken for the same batch with its last intent repeated. The nodes verify proofs of a
+//! fixed circuit, so that a verifier rebuilds the aggregation circuits from the signature
+//! circuit's verifier data (see [`verify_aggregated_proof`]).
+//!
+//! [`PublicInputsOptions::intent_commitment`]: crate::public_inputs::PublicInputsOptions
+use anyhow::anyhow;
+use plonky2::{
+    field::types::{Field, PrimeField64},
+    hash::{
+        hash_types::{HashOut, HashOutTarget},
+        poseidon::PoseidonHash,
+    },
+    iop::{
+        target::{BoolTarget, Target},
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::{
+            CircuitConfig, CircuitData, CommonCircuitData, VerifierCircuitData,
+            VerifierOnlyCircuitData,
+        },
+        config::{AlgebraicHasher, GenericConfig, Hasher},
+        proof::{ProofWithPublicInputs, ProofWithPublicInputsTarget},
+    },
+};
+
+use crate::{intent::SignatureProofData, D, F};
+
+/// Number of field elements of a commitment, a Poseidon hash
+const COMMITMENT_ELEMENTS: usize = 4;
+
+/// Proof that every intent signature proof of a batch is valid
+pub struct AggregatedProofData<C: GenericConfig<D, F = F>> {
+    pub proof_with_pis: ProofWithPublicInputs<F, C, D>,
+    pub common: CommonCircuitData<F, D>,
+    pub verifier_only: VerifierOnlyCircuitData<C, D>,
+}
+
+impl<C: GenericConfig<D, F = F>> AggregatedProofData<C> {
+    /// Merkle root of the batch's intent commitments, or `None` if the proof does not expose
+    /// the public inputs of an aggregated proof
+    pub fn batch_commitment(&self) -> Option<HashOut<F>> {
+        self.public_inputs()
+            .map(|public_inputs| HashOut::from_partial(&public_inputs[..COMMITMENT_ELEMENTS]))
+    }
+
+    /// Number of proofs in the batch, before padding, or `None` if the proof does not expose
+    /// the public inputs of an aggregated proof
+    pub fn num_proofs(&self) -> Option<usize> {
+        self.public_inputs()
+            .map(|public_inputs| public_inputs[COMMITMENT_ELEMENTS].to_canonical_u64() as usize)
+    }
+
+    /// Public inputs of the proof, if they are a commitment then a number of proofs
+    fn public_inputs(&self) -> Option<&[F]> {
+        let public_inputs = &self.proof_with_pis.public_inputs;
+        (public_inputs.len() == COMMITMENT_ELEMENTS + 1).then_some(public_inputs.as_slice())
+    }
+}
+
+/// Circuit of a level of the tree, verifying two proofs of the level below.
+///
+/// The circuit of the lowest level verifies signature proofs, flagging those which pad the
+/// batch, while the circuits of the levels above verify proofs of the level below, exposing
+/// their commitment then their number of intents.
+struct AggregationCircuit<C: GenericConfig<D, F = F>> {
+    data: CircuitData<F, C, D>,
+    left_proof_target: ProofWithPublicInputsTarget<D>,
+    right_proof_target: ProofWithPublicInputsTarget<D>,
+    /// whether the left and right signature proofs are padding, on the lowest level
+    padding_targets: Option<[BoolTarget; 2]>,
+}
+
+impl<C> AggregationCircuit<C>
+where
+    C: GenericConfig<D, F = F>,
+    C::Hasher: AlgebraicHasher<F>,
+{
+    fn new(
+        inner_common: &CommonCircuitData<F, D>,
+        inner_verifier_only: &VerifierOnlyCircuitData<C, D>,
+        leaves: bool,
+    ) -> Self {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut circuit_builder = CircuitBuilder::<F, D>::new(config);
+
+        // the inner circuit is fixed, so that the root proof also attests to the leaves' circuit
+        let inner_verifier_target = circuit_builder.constant_verifier_data(inner_verifier_only);
+        let left_proof_target = circuit_builder.add_virtual_proof_with_pis(inner_common);
+        let right_proof_target = circuit_builder.add_virtual_proof_with_pis(inner_common);
+        circuit_builder.verify_proof::<C>(&left_proof_target, &inner_verifier_target, inner_common);
+        circuit_builder.verify_proof::<C>(
+            &right_proof_target,
+            &inner_verifier_target,
+            inner_common,
+        );
+
+        let proof_targets = [&left_proof_target, &right_proof_target];
+        let (commitments, counts, padding_targets): (Vec<_>, Vec<_>, _) = if leaves {
+            // a padding leaf commits to a zero hash, and does not count as an intent
+            let padding_targets = [(); 2].map(|_| circuit_builder.add_virtual_bool_target_safe());
+            let mut commitments = vec![];
+            let mut counts = vec![];
+            for (proof_target, padding) in proof_targets.iter().zip(padding_targets) {
+                let intent = circuit_builder.not(padding);
+                commitments.extend(
+                    commitment_targets(&proof_target.public_inputs)
+                        .into_iter()
+                        .map(|element| circuit_builder.mul(element, intent.target)),
+                );
+                counts.push(intent.target);
+            }
+            (commitments, counts, Some(padding_targets))
+        } else {
+            let commitments = proof_targets
+                .iter()
+                .flat_map(|proof_target| proof_target.public_inputs[..COMMITMENT_ELEMENTS].to_vec())
+                .collect();
+            let counts = proof_targets
+                .iter()
+                .map(|proof_target| proof_target.public_inputs[COMMITMENT_ELEMENTS])
+                .collect();
+            (commitments, counts, None)
+        };
+        let node_commitment = circuit_builder.hash_n_to_hash_no_pad::<PoseidonHash>(commitments);
+        let node_count: Target = circuit_builder.add(counts[0], counts[1]);
+        circuit_builder.register_public_inputs(&node_commitment.elements);
+        circuit_builder.register_public_input(node_count);
+
+        Self {
+            data: circuit_builder.build::<C>(),
+            left_proof_target,
+            right_proof_target,
+            padding_targets,
+        }
+    }
+
+    /// Proves a node of `left` and `right`, flagged as padding by `padding` on the lowest level
+    fn prove(
+        &self,
+        left: &ProofWithPublicInputs<F, C, D>,
+        right: &ProofWithPublicInputs<F, C, D>,
+        padding: [bool; 2],
+    ) -> Result<ProofWithPublicInputs<F, C, D>, anyhow::Error> {
+        let mut partial_witness = PartialWitness::<F>::new();
+        partial_witness.set_proof_with_pis_target(&self.left_proof_target, left);
+        partial_witness.set_proof_with_pis_target(&self.right_proof_target, right);
+        if let Some(padding_targets) = self.padding_targets {
+            for (padding_target, padding) in padding_targets.into_iter().zip(padding) {
+                partial_witness.set_bool_target(padding_target, padding);
+            }
+        }
+        self.data.prove(partial_witness)
+    }
+}
+
+/// Circuits of each level of the tree aggregating `num_proofs` proofs of the circuit of
+/// `inner_verifier_data`, from the lowest level to the root
+fn aggregation_circuits<C>(
+    inner_verifier_data: &VerifierCircuitData<F, C, D>,
+    num_proofs: usize,
+) -> Vec<AggregationCircuit<C>>
+where
+    C: GenericConfig<D, F = F>,
+    C::Hasher: AlgebraicHasher<F>,
+{
+    let depth = padded_batch_size(num_proofs).trailing_zeros();
+    let mut circuits: Vec<AggregationCircuit<C>> = vec![];
+    for _ in 0..depth {
+        let circuit = match circuits.last() {
+            Some(inner) => {
+                AggregationCircuit::new(&inner.data.common, &inner.data.verifier_only, false)
+            }
+            None => AggregationCircuit::new(
+                &inner_verifier_data.common,
+                &inner_verifier_data.verifier_only,
+                true,
+            ),
+        };
+        circuits.push(circuit);
+    }
+    circuits
+}
+
+/// The last public inputs of a proof, holding its commitment
+fn commitment_targets<T: Copy>(public_inputs: &[T]) -> Vec<T> {
+    public_inputs[public_inputs.len() - COMMITMENT_ELEMENTS..].to_vec()
+}
+
+/// Number of leaves of the tree of a batch of `num_proofs`, with at least two leaves
+fn padded_batch_size(num_proofs: usize) -> usize {
+    num_proofs.next_power_of_two().max(2)
+}
+
+/// Merkle root of a batch's intent commitments, as exposed by its aggregated proof, padded
+/// with zero hashes
+pub fn intent_batch_commitment(intent_commitments: &[HashOut<F>]) -> HashOut<F> {
+    assert!(!intent_commitments.is_empty(), "batch should not be empty");
+    let mut level = intent_commitments.to_vec();
+    level.resize(padded_batch_size(level.len()), HashOut::ZERO);
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| PoseidonHash::hash_no_pad(&[pair[0].elements, pair[1].elements].concat()))
+            .collect();
+    }
+    level[0]
+}
+
+/// In-circuit [`intent_batch_commitment`]
+pub(crate) fn intent_batch_commitment_target(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    intent_commitments: &[HashOutTarget],
+) -> HashOutTarget {
+    assert!(!intent_commitments.is_empty(), "batch should not be empty");
+    let mut level = intent_commitments.to_vec();
+    let zero = HashOutTarget::from_vec(vec![circuit_builder.zero(); COMMITMENT_ELEMENTS]);
+    level.resize(padded_batch_size(level.len()), zero);
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| {
+                let elements = [pair[0].elements, pair[1].elements].concat();
+                circuit_builder.hash_n_to_hash_no_pad::<PoseidonHash>(elements)
+            })
+            .collect();
+    }
+    level[0]
+}
+
+/// Recursively aggregates intent signature proofs of the same circuit into a single proof,
+/// each of them should expose its intent commitment
+pub fn aggregate_intent_signature_proofs<C>(
+    signature_proofs: &[SignatureProofData<C>],
+) -> Result<AggregatedProofData<C>, anyhow::Error>
+where
+    C: GenericConfig<D, F = F>,
+    C::Hasher: AlgebraicHasher<F>,
+{
+    let first = signature_proofs
+        .first()
+        .ok_or_else(|| anyhow!("No signature proof to aggregate"))?;
+    for (index, signature_proof) in signature_proofs.iter().enumerate() {
+        if signature_proof.verifier_only.circuit_digest != first.verifier_only.circuit_digest {
+            return Err(anyhow!(
+                "Signature proof {} is not from the same circuit as the first one",
+                index
+            ));
+        }
+        if signature_proof.proof_with_pis.public_inputs.len() < COMMITMENT_ELEMENTS {
+            return Err(anyhow!(
+                "Signature proof {} does not expose an intent commitment",
+                index
+            ));
+        }
+    }
+
+    let num_proofs = signature_proofs.len();
+    let mut level = signature_proofs
+        .iter()
+        .map(|signature_proof| signature_proof.proof_with_pis.clone())
+        .collect::<Vec<_>>();
+    let last = level.last().unwrap().clone();
+    level.resize(padded_batch_size(num_proofs), last);
+
+    let verifier_data = VerifierCircuitData {
+        verifier_only: first.verifier_only.clone(),
+        common: first.common.clone(),
+    };
+    let circuits = aggregation_circuits(&verifier_data, num_proofs);
+    for (depth, circuit) in circuits.iter().enumerate() {
+        level = level
+            .chunks(2)
+            .enumerate()
+            .map(|(index, pair)| {
+                // the padding leaves are the ones after the batch's proofs
+                let padding =
+                    [2 * index, 2 * index + 1].map(|leaf| depth == 0 && leaf >= num_proofs);
+                circuit.prove(&pair[0], &pair[1], padding)
+            })
+            .collect::<Result<_, _>>()?;
+    }
+
+    let root = circuits.into_iter().last().unwrap().data;
+    Ok(AggregatedProofData {
+        proof_with_pis: level.remove(0),
+        common: root.common,
+        verifier_only: root.verifier_only,
+    })
+}
+
+/// Verifies an aggregated proof of signature proofs of the circuit of
+/// `signature_verifier_data`, and that it covers exactly the intents with the given
+/// commitments. The aggregation circuits are rebuilt from `signature_verifier_data`, which
+/// should be the signature circuit's (see
+/// [`IntentSignatureProver::verifier_data`](crate::prover::IntentSignatureProver::verifier_data)).
+pub fn verify_aggregated_proof<C>(
+    aggregated_proof: &AggregatedProofData<C>,
+    intent_commitments: &[HashOut<F>],
+    signature_verifier_data: &VerifierCircuitData<F, C, D>,
+) -> Result<(), anyhow::Error>
+where
+    C: GenericConfig<D, F = F>,
+    C::Hasher: AlgebraicHasher<F>,
+{
+    if intent_commitments.is_empty() {
+        return Err(anyhow!("Batch should not be empty"));
+    }
+    let expected_public_inputs = [
+        intent_batch_commitment(intent_commitments)
+            .elements
+            .to_vec(),
+        vec![F::from_canonical_usize(intent_commitments.len())],
+    ]
+    .concat();
+    if aggregated_proof.proof_with_pis.public_inputs != expected_public_inputs {
+        return Err(anyhow!(
+            "Aggregated proof does not cover the given intent commitments"
+        ));
+    }
+
+    let root = aggregation_circuits(signature_verifier_data, intent_commitments.len())
+        .into_iter()
+        .last()
+        .unwrap()
+        .data;
+    if aggregated_proof.verifier_only.circuit_digest != root.verifier_only.circuit_digest {
+        return Err(anyhow!(
+            "Aggregated proof is not an aggregation of the signature circuit's proofs"
+        ));
+    }
+    root.verifier_data()
+        .verify(aggregated_proof.proof_with_pis.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::{
+        field::types::{Field, Sample},
+        plonk::config::PoseidonGoldilocksConfig,
+    };
+
+    use crate::{
+        swap_settlement::swap_intent_commitments,
+        test_utils::{proven_intent_signatures, proven_swap_intents, signature_prover},
+    };
+
+    use super::*;
+
+    type C = PoseidonGoldilocksConfig;
+
+    /// Proofs of a small circuit standing in for the signature circuit, which exposes
+    /// another value before the commitment, as the signature circuit may
+    fn leaf_proofs(intent_commitments: &[HashOut<F>]) -> Vec<SignatureProofData<C>> {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut circuit_builder = CircuitBuilder::<F, D>::new(config);
+        let other_target = circuit_builder.add_virtual_public_input();
+        let commitment_target = circuit_builder.add_virtual_hash();
+        circuit_builder.register_public_inputs(&commitment_target.elements);
+        let data = circuit_builder.build::<C>();
+
+        intent_commitments
+            .iter()
+            .map(|intent_commitment| {
+                let mut partial_witness = PartialWitness::<F>::new();
+                partial_witness.set_target(other_target, F::ONE);
+                partial_witness.set_hash_target(commitment_target, *intent_commitment);
+                SignatureProofData {
+                    proof_with_pis: data.prove(partial_witness).unwrap(),
+                    common: data.common.clone(),
+                    verifier_only: data.verifier_only.clone(),
+                }
+            })
+            .collect()
+    }
+
+    fn verifier_data(signature_proof: &SignatureProofData<C>) -> VerifierCircuitData<F, C, D> {
+        VerifierCircuitData {
+            verifier_only: signature_proof.verifier_only.clone(),
+            common: signature_proof.common.clone(),
+        }
+    }
+
+    #[test]
+    fn it_works_aggregate_intent_signature_proofs() {
+        let intent_commitments = (0..3).map(|_| HashOut::rand()).collect::<Vec<_>>();
+        let signature_proofs = leaf_proofs(&intent_commitments);
+        let signature_verifier_data = verifier_data(&signature_proofs[0]);
+
+        let aggregated_proof = aggregate_intent_signature_proofs(&signature_proofs).unwrap();
+        assert_eq!(aggregated_proof.num_proofs(), Some(3));
+        assert_eq!(
+            aggregated_proof.batch_commitment(),
+            Some(intent_batch_commitment(&intent_commitments))
+        );
+        // which are not read from a proof exposing other public inputs
+        let mut truncated_proof = aggregated_proof.proof_with_pis.clone();
+        truncated_proof.public_inputs.pop();
+        let truncated_proof = AggregatedProofData {
+            proof_with_pis: truncated_proof,
+            common: aggregated_proof.common.clone(),
+            verifier_only: aggregated_proof.verifier_only.clone(),
+        };
+        assert_eq!(truncated_proof.num_proofs(), None);
+        assert_eq!(truncated_proof.batch_commitment(), None);
+        verify_aggregated_proof(
+            &aggregated_proof,
+            &intent_commitments,
+            &signature_verifier_data,
+        )
+        .unwrap();
+
+        // the proof does not cover another batch, nor the batch padded with its last intent
+        let mut other_commitments = intent_commitments.clone();
+        other_commitments.swap(0, 1);
+        let mut repeated_last = intent_commitments.clone();
+        repeated_last.push(intent_commitments[2]);
+        for commitments in [
+            &other_commitments[..],
+            &intent_commitments[..2],
+            &repeated_last[..],
+        ] {
+            assert!(verify_aggregated_proof(
+                &aggregated_proof,
+                commitments,
+                &signature_verifier_data
+            )
+            .is_err());
+        }
+    }
+
+    #[test]
+    fn it_works_aggregate_real_intent_signature_proofs() {
+        let proven_intents = proven_swap_intents();
+        let signature_proofs = proven_intent_signatures()
+            .into_iter()
+            .map(|intent_signature| intent_signature.signature_proof_data)
+            .collect::<Vec<_>>();
+        let intents = proven_intents
+            .iter()
+            .map(|proven_intent| proven_intent.intent.clone())
+            .collect::<Vec<_>>();
+        let intent_commitments = swap_intent_commitments(&intents);
+        let signature_verifier_data = signature_prover().verifier_data();
+
+        let aggregated_proof = aggregate_intent_signature_proofs(&signature_proofs).unwrap();
+        assert_eq!(aggregated_proof.num_proofs(), Some(2));
+        verify_aggregated_proof(
+            &aggregated_proof,
+            &intent_commitments,
+            &signature_verifier_data,
+        )
+        .unwrap();
+
+        // the aggregation of proofs of another circuit, exposing the same commitments
+        let other_proofs = leaf_proofs(&intent_commitments);
+        let other_aggregated_proof = aggregate_intent_signature_proofs(&other_proofs).unwrap();
+        assert_eq!(
+            other_aggregated_proof.proof_with_pis.public_inputs,
+            aggregated_proof.proof_with_pis.public_inputs
+        );
+        let error = verify_aggregated_proof(
+            &other_aggregated_proof,
+            &intent_commitments,
+            &signature_verifier_data,
+        )
+        .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("not an aggregation of the signature circuit's proofs"));
+    }
+
+    #[test]
+    fn it_works_intent_batch_commitment_padding() {
+        let intent_commitment = HashOut::rand();
+        let single = intent_batch_commitment(&[intent_commitment]);
+        assert_eq!(
+            single,
+            PoseidonHash::hash_no_pad(
+                &[intent_commitment.elements, HashOut::ZERO.elements].concat()
+            )
+        );
+        assert_ne!(intent_batch_commitment(&[intent_commitment; 2]), single);
+    }
+}
@@ -0,0 +1,648 @@
+//! Local model of constant-product AMM pools, as external liquidity for solvers.
+//!
+//! Pools follow Uniswap v2: a swap of `amount_in` tokens, of which a fee of `fee_bps` basis
+//! points is kept by the pool, gives the amount of tokens keeping the product of both
+//! reserves constant, rounded down. All amounts are integers, so that swaps are exact and
+//! deterministic. Tokens without a direct pool are swapped through several pools, along the
+//! route giving the most tokens after fees, and a swap may be split across several routes.
+//! Pools are loaded from a json snapshot of their reserves:
+//!
+//! ```json
+//! {
+//!   "pools": [
+//!     {
+//!       "token0": "0x0101010101010101010101010101010101010101",
+//!       "token1": "0x0202020202020202020202020202020202020202",
+//!       "reserve0": "1000000",
+//!       "reserve1": "2000000",
+//!       "fee_bps": 30
+//!     }
+//!   ]
+//! }
+//! ```
+use std::str::FromStr;
+
+use num_bigint::BigUint;
+use serde::Deserialize;
+use types::types::Address;
+
+use crate::{error::AmmError, order::Order};
+
+/// Basis points in a whole
+const BPS: u32 = 10_000;
+
+/// Most pools a route goes through by default
+pub const DEFAULT_MAX_HOPS: usize = 3;
+
+/// Pool holding reserves of two tokens, whose product is kept constant by swaps
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConstantProductPool {
+    token0: Address,
+    token1: Address,
+    reserve0: BigUint,
+    reserve1: BigUint,
+    /// fee kept from the swapped tokens, in basis points
+    fee_bps: u32,
+}
+
+impl ConstantProductPool {
+    pub fn new(
+        token0: Address,
+        token1: Address,
+        reserve0: BigUint,
+        reserve1: BigUint,
+        fee_bps: u32,
+    ) -> Result<Self, AmmError> {
+        if token0 == token1 {
+            return Err(AmmError::InvalidValue("token pair", token0.to_string()));
+        }
+        if fee_bps >= BPS {
+            return Err(AmmError::InvalidValue("fee", fee_bps.to_string()));
+        }
+        Ok(Self {
+            token0,
+            token1,
+            reserve0,
+            reserve1,
+            fee_bps,
+        })
+    }
+
+    pub fn tokens(&self) -> (Address, Address) {
+        (self.token0, self.token1)
+    }
+
+    pub fn reserves(&self) -> (&BigUint, &BigUint) {
+        (&self.reserve0, &self.reserve1)
+    }
+
+    pub fn fee_bps(&self) -> u32 {
+        self.fee_bps
+    }
+
+    /// Whether the pool trades `token_in` for `token_out`
+    pub fn trades(&self, token_in: Address, token_out: Address) -> bool {
+        (token_in, token_out) == (self.token0, self.token1)
+            || (token_in, token_out) == (self.token1, self.token0)
+    }
+
+    /// Reserves of `token_in` and of the other token
+    fn reserves_from(&self, token_in: Address) -> Result<(&BigUint, &BigUint), AmmError> {
+        if token_in == self.token0 {
+            Ok((&self.reserve0, &self.reserve1))
+        } else if token_in == self.token1 {
+            Ok((&self.reserve1, &self.reserve0))
+        } else {
+            Err(AmmError::UnknownToken(token_in))
+        }
+    }
+
+    /// Tokens given for `amount_in` tokens `token_in`, after fees
+    pub fn amount_out(&self, token_in: Address, amount_in: &BigUint) -> Result<BigUint, AmmError> {
+        let (reserve_in, reserve_out) = self.reserves_from(token_in)?;
+        if amount_in == &BigUint::from(0_u8) {
+            return Err(AmmError::ZeroAmount);
+        }
+        let amount_in_with_fee = amount_in * (BPS - self.fee_bps);
+        let amount_out =
+            &amount_in_with_fee * reserve_out / (reserve_in * BPS + &amount_in_with_fee);
+        if amount_out == BigUint::from(0_u8) {
+            return Err(AmmError::InsufficientLiquidity);
+        }
+        Ok(amount_out)
+    }
+
+    /// Least tokens `token_in` giving `amount_out` of the other token, after fees
+    pub fn amount_in(&self, token_in: Address, amount_out: &BigUint) -> Result<BigUint, AmmError> {
+        let (reserve_in, reserve_out) = self.reserves_from(token_in)?;
+        if amount_out == &BigUint::from(0_u8) {
+            return Err(AmmError::ZeroAmount);
+        }
+        if amount_out >= reserve_out {
+            return Err(AmmError::InsufficientLiquidity);
+        }
+        let numerator = reserve_in * amount_out * BPS;
+        let denominator = (reserve_out - amount_out) * (BPS - self.fee_bps);
+        Ok(numerator / denominator + 1_u8)
+    }
+
+    /// Swaps `amount_in` tokens `token_in`, updating the reserves, and returns the tokens given
+    pub fn swap(&mut self, token_in: Address, amount_in: &BigUint) -> Result<BigUint, AmmError> {
+        let amount_out = self.amount_out(token_in, amount_in)?;
+        if token_in == self.token0 {
+            self.reserve0 += amount_in;
+            self.reserve1 -= &amount_out;
+        } else {
+            self.reserve1 += amount_in;
+            self.reserve0 -= &amount_out;
+        }
+        Ok(amount_out)
+    }
+}
+
+#[derive(Deserialize)]
+struct PoolSnapshot {
+    token0: String,
+    token1: String,
+    reserve0: String,
+    reserve1: String,
+    fee_bps: u32,
+}
+
+#[derive(Deserialize)]
+struct AmmSnapshot {
+    pools: Vec<PoolSnapshot>,
+}
+
+fn parse_address(address: &str) -> Result<Address, AmmError> {
+    Address::from_str(address).map_err(|_| AmmError::InvalidValue("token", address.to_string()))
+}
+
+fn parse_reserve(reserve: &str) -> Result<BigUint, AmmError> {
+    BigUint::parse_bytes(reserve.as_bytes(), 10)
+        .ok_or_else(|| AmmError::InvalidValue("reserve", reserve.to_string()))
+}
+
+/// Swap through a sequence of pools, the i-th pool trading `tokens[i]` for `tokens[i + 1]`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Route {
+    /// indices of the pools in the [`AmmSimulator`]
+    pub pools: Vec<usize>,
+    pub tokens: Vec<Address>,
+    pub amount_in: BigUint,
+    pub amount_out: BigUint,
+}
+
+/// Set of AMM pools. Direct swaps go through the first pool trading the requested tokens,
+/// while routed swaps go through any pools
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AmmSimulator {
+    pools: Vec<ConstantProductPool>,
+    max_hops: usize,
+    /// number of equal parts a routed swap may be split into
+    route_splits: usize,
+}
+
+impl Default for AmmSimulator {
+    fn default() -> Self {
+        Self::new(vec![])
+    }
+}
+
+impl AmmSimulator {
+    pub fn new(pools: Vec<ConstantProductPool>) -> Self {
+        Self {
+            pools,
+            max_hops: DEFAULT_MAX_HOPS,
+            route_splits: 1,
+        }
+    }
+
+    /// Simulator routing swaps through at most `max_hops` pools
+    pub fn with_max_hops(mut self, max_hops: usize) -> Self {
+        self.max_hops = max_hops;
+        self
+    }
+
+    /// Simulator splitting routed swaps into up to `route_splits` equal parts, each routed
+    /// after the previous ones are swapped
+    pub fn with_route_splits(mut self, route_splits: usize) -> Self {
+        self.route_splits = route_splits.max(1);
+        self
+    }
+
+    /// Loads pools from a json snapshot of their reserves
+    pub fn from_json(json: &str) -> Result<Self, AmmError> {
+        let snapshot: AmmSnapshot = serde_json::from_str(json)?;
+        let pools = snapshot
+            .pools
+            .iter()
+            .map(|pool| {
+                ConstantProductPool::new(
+                    parse_address(&pool.token0)?,
+                    parse_address(&pool.token1)?,
+                    parse_reserve(&pool.reserve0)?,
+                    parse_reserve(&pool.reserve1)?,
+                    pool.fee_bps,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::new(pools))
+    }
+
+    pub fn pools(&self) -> &[ConstantProductPool] {
+        &self.pools
+    }
+
+    /// Pool trading `token_in` for `token_out`
+    pub fn pool(
+        &self,
+        token_in: Address,
+        token_out: Address,
+    ) -> Result<&ConstantProductPool, AmmError> {
+        self.pools
+            .iter()
+            .find(|pool| pool.trades(token_in, token_out))
+            .ok_or(AmmError::UnknownPool {
+                token_in,
+                token_out,
+            })
+    }
+
+    fn pool_mut(
+        &mut self,
+        token_in: Address,
+        token_out: Address,
+    ) -> Result<&mut ConstantProductPool, AmmError> {
+        self.pools
+            .iter_mut()
+            .find(|pool| pool.trades(token_in, token_out))
+            .ok_or(AmmError::UnknownPool {
+                token_in,
+                token_out,
+            })
+    }
+
+    /// Tokens `token_out` given for `amount_in` tokens `token_in`, after fees
+    pub fn amount_out(
+        &self,
+        token_in: Address,
+        token_out: Address,
+        amount_in: &BigUint,
+    ) -> Result<BigUint, AmmError> {
+        self.pool(token_in, token_out)?
+            .amount_out(token_in, amount_in)
+    }
+
+    /// Swaps `amount_in` tokens `token_in` for tokens `token_out`, returning the tokens given
+    pub fn swap(
+        &mut self,
+        token_in: Address,
+        token_out: Address,
+        amount_in: &BigUint,
+    ) -> Result<BigUint, AmmError> {
+        self.pool_mut(token_in, token_out)?
+            .swap(token_in, amount_in)
+    }
+
+    /// Pools and tokens of every path from `token_in` to `token_out` through distinct
+    /// tokens, of at most `max_hops` pools, shortest first
+    fn paths(&self, token_in: Address, token_out: Address) -> Vec<(Vec<usize>, Vec<Address>)> {
+        let mut paths = vec![];
+        let mut frontier = vec![(vec![], vec![token_in])];
+        for _ in 0..self.max_hops {
+            let mut next_frontier = vec![];
+            for (pools, tokens) in frontier {
+                let last = tokens[tokens.len() - 1];
+                for (index, pool) in self.pools.iter().enumerate() {
+                    let next = match pool.tokens() {
+                        (token0, token1) if token0 == last => token1,
+                        (token0, token1) if token1 == last => token0,
+                        _ => continue,
+                    };
+                    if tokens.contains(&next) {
+                        continue;
+                    }
+                    let path = (
+                        [pools.as_slice(), &[index]].concat(),
+                        [tokens.as_slice(), &[next]].concat(),
+                    );
+                    if next == token_out {
+                        paths.push(path);
+                    } else {
+                        next_frontier.push(path);
+                    }
+                }
+            }
+            frontier = next_frontier;
+        }
+        paths
+    }
+
+    /// Tokens given at the end of a path of pools for `amount_in` tokens
+    fn path_amount_out(
+        &self,
+        pools: &[usize],
+        tokens: &[Address],
+        amount_in: &BigUint,
+    ) -> Result<BigUint, AmmError> {
+        pools
+            .iter()
+            .zip(tokens)
+            .try_fold(amount_in.clone(), |amount, (&pool, &token)| {
+                self.pools[pool].amount_out(token, &amount)
+            })
+    }
+
+    /// Route giving the most tokens `token_out` for `amount_in` tokens `token_in`, after fees.
+    /// Ties are broken by the fewest pools
+    pub fn best_route(
+        &self,
+        token_in: Address,
+        token_out: Address,
+        amount_in: &BigUint,
+    ) -> Result<Route, AmmError> {
+        let paths = self.paths(token_in, token_out);
+        if paths.is_empty() {
+            return Err(AmmError::UnknownPool {
+                token_in,
+                token_out,
+            });
+        }
+        let mut best: Option<Route> = None;
+        for (pools, tokens) in paths {
+            let Ok(amount_out) = self.path_amount_out(&pools, &tokens, amount_in) else {
+                continue;
+            };
+            if best
+                .as_ref()
+                .map_or(true, |best| amount_out > best.amount_out)
+            {
+                best = Some(Route {
+                    pools,
+                    tokens,
+                    amount_in: amount_in.clone(),
+                    amount_out,
+                });
+            }
+        }
+        best.ok_or(AmmError::InsufficientLiquidity)
+    }
+
+    /// Routes giving the most tokens `token_out` for `amount_in` tokens `token_in`, either
+    /// the best route, or the best routes of the successive parts of a split swap. Routes
+    /// are swapped in order
+    pub fn routes(
+        &self,
+        token_in: Address,
+        token_out: Address,
+        amount_in: &BigUint,
+    ) -> Result<Vec<Route>, AmmError> {
+        let best_route = self.best_route(token_in, token_out, amount_in)?;
+        if self.route_splits == 1 {
+            return Ok(vec![best_route]);
+        }
+
+        let mut amm = self.clone();
+        let mut split_routes = vec![];
+        let part = amount_in / self.route_splits;
+        let last_part = amount_in - &part * (self.route_splits - 1);
+        for split in 0..self.route_splits {
+            let amount = if split + 1 == self.route_splits {
+                &last_part
+            } else {
+                &part
+            };
+            if amount == &BigUint::from(0_u8) {
+                continue;
+            }
+            let Ok(route) = amm.best_route(token_in, token_out, amount) else {
+                return Ok(vec![best_route]);
+            };
+            amm.swap_route(&route)?;
+            split_routes.push(route);
+        }
+
+        let split_amount_out = split_routes
+            .iter()
+            .map(|route| &route.amount_out)
+            .sum::<BigUint>();
+        if split_amount_out > best_route.amount_out {
+            Ok(split_routes)
+        } else {
+            Ok(vec![best_route])
+        }
+    }
+
+    /// Swaps along `route`, updating the reserves of its pools, and returns the tokens given
+    pub fn swap_route(&mut self, route: &Route) -> Result<BigUint, AmmError> {
+        let mut amount = route.amount_in.clone();
+        for (&pool, &token) in route.pools.iter().zip(&route.tokens) {
+            amount = self.pools[pool].swap(token, &amount)?;
+        }
+        Ok(amount)
+    }
+
+    /// Fills `order` by swapping `sold_amount` of its sell tokens along the best routes, for
+    /// instance its residual size once matched within a batch, if the swap satisfies the
+    /// order's limit. Returns the routes swapped
+    pub fn fill_order(
+        &mut self,
+        order: &Order,
+        sold_amount: &BigUint,
+    ) -> Result<Vec<Route>, AmmError> {
+        let routes = self.routes(order.sell_token, order.buy_token, sold_amount)?;
+        let amount_out = routes
+            .iter()
+            .map(|route| &route.amount_out)
+            .sum::<BigUint>();
+        if !order.accepts_fill(sold_amount, &amount_out) {
+            return Err(AmmError::LimitNotMet { amount_out });
+        }
+        for route in &routes {
+            self.swap_route(route)?;
+        }
+        Ok(routes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::token;
+
+    use super::*;
+
+    const SNAPSHOT: &str = r#"{
+        "pools": [
+            {
+                "token0": "0x0101010101010101010101010101010101010101",
+                "token1": "0x0202020202020202020202020202020202020202",
+                "reserve0": "1000",
+                "reserve1": "1000",
+                "fee_bps": 30
+            },
+            {
+                "token0": "0x0202020202020202020202020202020202020202",
+                "token1": "0x0303030303030303030303030303030303030303",
+                "reserve0": "1000000000000000000000000",
+                "reserve1": "2000000000000000000000000",
+                "fee_bps": 5
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn it_works_constant_product_pool() {
+        let mut pool = ConstantProductPool::new(
+            token(1),
+            token(2),
+            BigUint::from(1000_u32),
+            BigUint::from(1000_u32),
+            30,
+        )
+        .unwrap();
+
+        // 100 * 0.997 * 1000 / (1000 + 100 * 0.997), rounded down
+        let amount_out = pool.amount_out(token(1), &BigUint::from(100_u8)).unwrap();
+        assert_eq!(amount_out, BigUint::from(90_u8));
+        assert_eq!(
+            pool.amount_in(token(1), &amount_out).unwrap(),
+            BigUint::from(100_u8)
+        );
+        assert!(matches!(
+            pool.amount_in(token(1), &BigUint::from(1000_u32)),
+            Err(AmmError::InsufficientLiquidity)
+        ));
+        assert!(matches!(
+            pool.amount_out(token(3), &BigUint::from(100_u8)),
+            Err(AmmError::UnknownToken(_))
+        ));
+
+        assert_eq!(
+            pool.swap(token(1), &BigUint::from(100_u8)).unwrap(),
+            amount_out
+        );
+        assert_eq!(
+            pool.reserves(),
+            (&BigUint::from(1100_u32), &BigUint::from(910_u32))
+        );
+        // the other way round, 90 * 0.997 * 1100 / (910 + 90 * 0.997), rounded down
+        assert_eq!(
+            pool.swap(token(2), &BigUint::from(90_u8)).unwrap(),
+            BigUint::from(98_u8)
+        );
+    }
+
+    #[test]
+    fn it_works_amm_simulator_from_json() {
+        let mut amm = AmmSimulator::from_json(SNAPSHOT).unwrap();
+        assert_eq!(amm.pools().len(), 2);
+        assert_eq!(amm.pool(token(3), token(2)).unwrap().fee_bps(), 5);
+        assert_eq!(
+            amm.amount_out(token(2), token(3), &BigUint::from(1000_u32))
+                .unwrap(),
+            BigUint::from(1998_u32)
+        );
+        assert!(matches!(
+            amm.amount_out(token(1), token(3), &BigUint::from(1000_u32)),
+            Err(AmmError::UnknownPool { .. })
+        ));
+
+        let order = |min_buy_amount: u8| Order {
+            sell_token: token(1),
+            buy_token: token(2),
+            sell_amount: BigUint::from(100_u8),
+            min_buy_amount: BigUint::from(min_buy_amount),
+            partially_fillable: false,
+        };
+        assert!(matches!(
+            amm.fill_order(&order(95), &BigUint::from(100_u8)),
+            Err(AmmError::LimitNotMet { .. })
+        ));
+        assert_eq!(
+            amm.fill_order(&order(90), &BigUint::from(100_u8)).unwrap(),
+            vec![Route {
+                pools: vec![0],
+                tokens: vec![token(1), token(2)],
+                amount_in: BigUint::from(100_u8),
+                amount_out: BigUint::from(90_u8),
+            }]
+        );
+        assert_eq!(
+            amm.pool(token(1), token(2)).unwrap().reserves(),
+            (&BigUint::from(1100_u32), &BigUint::from(910_u32))
+        );
+
+        assert!(matches!(
+            AmmSimulator::from_json(&SNAPSHOT.replace("\"fee_bps\": 5", "\"fee_bps\": 10000")),
+            Err(AmmError::InvalidValue("fee", _))
+        ));
+        assert!(matches!(
+            AmmSimulator::from_json(&SNAPSHOT.replace("\"1000\"", "\"-1\"")),
+            Err(AmmError::InvalidValue("reserve", _))
+        ));
+    }
+
+    fn pool(token: u8, other_token: u8, reserve: u32) -> ConstantProductPool {
+        ConstantProductPool::new(
+            self::token(token),
+            self::token(other_token),
+            BigUint::from(reserve),
+            BigUint::from(reserve),
+            30,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn it_works_amm_simulator_multi_hop_routes() {
+        let amm = AmmSimulator::new(vec![pool(1, 2, 1000), pool(2, 3, 1000)]);
+        // 100 tokens 1 give 90 tokens 2, which give 82 tokens 3
+        assert_eq!(
+            amm.best_route(token(1), token(3), &BigUint::from(100_u8))
+                .unwrap(),
+            Route {
+                pools: vec![0, 1],
+                tokens: vec![token(1), token(2), token(3)],
+                amount_in: BigUint::from(100_u8),
+                amount_out: BigUint::from(82_u8),
+            }
+        );
+        assert!(matches!(
+            amm.clone()
+                .with_max_hops(1)
+                .best_route(token(1), token(3), &BigUint::from(100_u8)),
+            Err(AmmError::UnknownPool { .. })
+        ));
+
+        // a shallow direct pool gives 49 tokens, a deep one 90
+        let mut pools = amm.pools().to_vec();
+        pools.push(pool(3, 1, 100));
+        let route = AmmSimulator::new(pools.clone())
+            .best_route(token(1), token(3), &BigUint::from(100_u8))
+            .unwrap();
+        assert_eq!(route.pools, vec![0, 1]);
+        pools[2] = pool(3, 1, 1000);
+        let route = AmmSimulator::new(pools)
+            .best_route(token(1), token(3), &BigUint::from(100_u8))
+            .unwrap();
+        assert_eq!(
+            (route.pools, route.amount_out),
+            (vec![2], BigUint::from(90_u8))
+        );
+    }
+
+    #[test]
+    fn it_works_amm_simulator_split_routes() {
+        let amm = AmmSimulator::new(vec![pool(1, 2, 1000), pool(1, 2, 1000)]);
+        // a single pool gives 166 tokens for 200
+        let routes = amm
+            .routes(token(1), token(2), &BigUint::from(200_u8))
+            .unwrap();
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].amount_out, BigUint::from(166_u8));
+
+        // each half goes through its own pool
+        let mut amm = amm.with_route_splits(2);
+        let routes = amm
+            .routes(token(1), token(2), &BigUint::from(200_u8))
+            .unwrap();
+        assert_eq!(
+            routes
+                .iter()
+                .map(|route| (route.pools.clone(), route.amount_out.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (vec![0], BigUint::from(90_u8)),
+                (vec![1], BigUint::from(90_u8))
+            ]
+        );
+        for route in &routes {
+            assert_eq!(amm.swap_route(route).unwrap(), route.amount_out);
+        }
+        assert_eq!(
+            amm.pools()[1].reserves(),
+            (&BigUint::from(1100_u32), &BigUint::from(910_u32))
+        );
+    }
+}
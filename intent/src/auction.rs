@@ -0,0 +1,803 @@
+//! Batch auction settling swap intents at uniform clearing prices.
+//!
+//! The intents of a batch are grouped by token pair, whatever their direction, and every
+//! pair is cleared at a single price. The candidate prices are the limit prices of the pair's
+//! intents, and the auction keeps the candidate which best meets its [`AuctionObjective`].
+//! At a price of `quote_amount` quote tokens for `base_amount` base tokens, intents are filled
+//! by whole units of that price, so that the tokens sold on one side of the pair are exactly
+//! the tokens bought on the other side. Intents accepting the price are filled best limit
+//! first, until one side of the pair is exhausted, and an intent which is not partially
+//! fillable is only filled if it fits as a whole. The sizes left unfilled by the auction can
+//! be routed through an [`AmmSimulator`], when they are within their intents' limits.
+//!
+//! Clearing prices are the settlement's [`ClearingPrice`]s, whose base token is the pair's
+//! token with the lower address, and whose amounts are coprime, so that a [`Solution`]
+//! converts into the [`SwapSettlement`] proven by a
+//! [`SwapSettlementCircuit`](crate::swap_settlement::SwapSettlementCircuit).
+use std::{cmp::Ordering, collections::BTreeMap};
+
+use anyhow::anyhow;
+use num_bigint::BigUint;
+use plonky2::{
+    iop::witness::PartialWitness,
+    plonk::{circuit_builder::CircuitBuilder, config::GenericConfig},
+};
+use types::types::{Address, PublicKey, RecoverableSignature};
+
+use crate::{
+    amm::{AmmSimulator, Route},
+    error::FillError,
+    intent::IntentSignatureVerifier,
+    order::Order,
+    pool::token_pair,
+    solver::{verify_signature_proofs, IntentSignature, Solver},
+    swap_intent::SwapIntent,
+    swap_settlement::{constrain_swap_settlement, ClearingPrice, SwapFill, SwapSettlement},
+    swap_solver::{SwapExecution, SwapSolver},
+    D, F,
+};
+
+/// Fill of an intent of the batch
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fill {
+    /// index of the intent in the batch
+    pub intent: usize,
+    /// tokens sold by the intent, its order's sell token
+    pub sold_amount: BigUint,
+    /// tokens bought by the intent, its order's buy token
+    pub bought_amount: BigUint,
+}
+
+/// Fill of an intent of the batch through AMM pools
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RoutedFill {
+    /// index of the intent in the batch
+    pub intent: usize,
+    /// routes swapping the intent's sold tokens, in order
+    pub routes: Vec<Route>,
+}
+
+impl RoutedFill {
+    pub fn sold_amount(&self) -> BigUint {
+        self.routes.iter().map(|route| &route.amount_in).sum()
+    }
+
+    pub fn bought_amount(&self) -> BigUint {
+        self.routes.iter().map(|route| &route.amount_out).sum()
+    }
+}
+
+/// Fills of a batch, with the clearing price of every traded token pair
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Solution {
+    /// fills within the batch, in batch order
+    pub fills: Vec<Fill>,
+    /// clearing prices, in token pair order
+    pub prices: Vec<ClearingPrice>,
+    /// fills through AMM pools, in batch order
+    pub routes: Vec<RoutedFill>,
+}
+
+impl Solution {
+    /// Returns the indices of the filled intents, in batch order.
+    pub fn filled_intents(&self) -> Vec<usize> {
+        let mut filled_intents = self
+            .fills
+            .iter()
+            .map(|fill| fill.intent)
+            .chain(self.routes.iter().map(|routed_fill| routed_fill.intent))
+            .collect::<Vec<_>>();
+        filled_intents.sort();
+        filled_intents.dedup();
+        filled_intents
+    }
+
+    /// Fills within the batch, then fills through AMM pools, each summing its routes
+    pub fn settled_fills(&self) -> Vec<Fill> {
+        let routed_fills = self.routes.iter().map(|routed_fill| Fill {
+            intent: routed_fill.intent,
+            sold_amount: routed_fill.sold_amount(),
+            bought_amount: routed_fill.bought_amount(),
+        });
+        self.fills.iter().cloned().chain(routed_fills).collect()
+    }
+
+    /// Clearing price of the pair of `token` and `other_token`, if the pair is traded
+    pub fn price(&self, token: Address, other_token: Address) -> Option<&ClearingPrice> {
+        self.price_index(token, other_token)
+            .map(|index| &self.prices[index])
+    }
+
+    fn price_index(&self, token: Address, other_token: Address) -> Option<usize> {
+        let (base_token, quote_token) = token_pair(token, other_token);
+        self.prices
+            .iter()
+            .position(|price| price.base_token == base_token && price.quote_token == quote_token)
+    }
+
+    /// Settlement of the fills within the batch of `intents`, at the solution's uniform
+    /// clearing prices, with each fill's amounts in its price's quote and base tokens, and of
+    /// its fills through AMM pools
+    pub fn settlement(&self, intents: &[SwapIntent]) -> Result<SwapSettlement, anyhow::Error> {
+        let fills = self
+            .fills
+            .iter()
+            .map(|fill| {
+                let order = intents
+                    .get(fill.intent)
+                    .ok_or_else(|| anyhow!("Fill of an invalid intent {}", fill.intent))?
+                    .order();
+                let price = self
+                    .price_index(order.sell_token, order.buy_token)
+                    .ok_or_else(|| anyhow!("Intent {} has no clearing price", fill.intent))?;
+                let (quote_amount, base_amount) =
+                    if self.prices[price].sells_quote(&order) == Some(true) {
+                        (&fill.sold_amount, &fill.bought_amount)
+                    } else {
+                        (&fill.bought_amount, &fill.sold_amount)
+                    };
+                Ok(SwapFill {
+                    intent: fill.intent,
+                    price,
+                    quote_amount: quote_amount.clone(),
+                    base_amount: base_amount.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>, anyhow::Error>>()?;
+        Ok(SwapSettlement::new(self.prices.clone(), fills)
+            .with_routed_fills(self.routes.clone())
+            .with_uniform_prices())
+    }
+}
+
+/// Solution of the direct matches of a [`SwapSolver`], without uniform prices
+impl From<SwapExecution> for Solution {
+    fn from(execution: SwapExecution) -> Self {
+        let mut fills = execution
+            .matches
+            .into_iter()
+            .flat_map(|swap_match| {
+                swap_match
+                    .fills()
+                    .map(|(intent, sold_amount, bought_amount)| Fill {
+                        intent,
+                        sold_amount: sold_amount.clone(),
+                        bought_amount: bought_amount.clone(),
+                    })
+            })
+            .collect::<Vec<_>>();
+        fills.sort_by_key(|fill| fill.intent);
+        Self {
+            fills,
+            ..Self::default()
+        }
+    }
+}
+
+/// Criterion by which the clearing price of a token pair is chosen. Ties are broken by the
+/// other criterion, then by the lowest price
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AuctionObjective {
+    /// most base tokens traded
+    #[default]
+    Volume,
+    /// most surplus of the filled intents, the tokens they buy beyond their limit prices,
+    /// valued in quote tokens at the clearing price
+    Surplus,
+}
+
+/// Fraction `numerator / denominator`
+type Ratio = (BigUint, BigUint);
+
+fn add_ratios(a: Ratio, b: Ratio) -> Ratio {
+    (&a.0 * &b.1 + &b.0 * &a.1, a.1 * b.1)
+}
+
+fn cmp_ratios(a: &Ratio, b: &Ratio) -> Ordering {
+    (&a.0 * &b.1).cmp(&(&b.0 * &a.1))
+}
+
+/// Intent able to trade some units at a candidate price
+struct Bid {
+    intent: usize,
+    units: BigUint,
+    whole: bool,
+}
+
+/// Units taken from each bid, best first, to trade at most `units` units
+fn take(bids: &[Bid], units: &BigUint) -> Vec<BigUint> {
+    let mut taken = BigUint::from(0_u8);
+    bids.iter()
+        .map(|bid| {
+            let take = if bid.whole {
+                if &taken + &bid.units <= *units {
+                    bid.units.clone()
+                } else {
+                    BigUint::from(0_u8)
+                }
+            } else {
+                (units - &taken).min(bid.units.clone())
+            };
+            taken += &take;
+            take
+        })
+        .collect()
+}
+
+/// Solver settling batches of [`SwapIntent`]s by a batch auction. Replays, cancellations and
+/// fills across batches are tracked by its [`SwapSolver`].
+#[derive(Clone, Debug, Default)]
+pub struct BatchAuction {
+    objective: AuctionObjective,
+    swap_solver: SwapSolver,
+    /// liquidity for the sizes the auction leaves unfilled
+    amm: Option<AmmSimulator>,
+}
+
+impl BatchAuction {
+    pub fn new(objective: AuctionObjective) -> Self {
+        Self {
+            objective,
+            swap_solver: SwapSolver::new(),
+            amm: None,
+        }
+    }
+
+    /// Auction routing the sizes it leaves unfilled through the pools of `amm`
+    pub fn with_amm(mut self, amm: AmmSimulator) -> Self {
+        self.amm = Some(amm);
+        self
+    }
+
+    pub fn amm(&self) -> Option<&AmmSimulator> {
+        self.amm.as_ref()
+    }
+
+    /// Auction tracking replays, cancellations and fills with `swap_solver`
+    pub fn with_swap_solver(mut self, swap_solver: SwapSolver) -> Self {
+        self.swap_solver = swap_solver;
+        self
+    }
+
+    pub fn objective(&self) -> AuctionObjective {
+        self.objective
+    }
+
+    pub fn swap_solver(&self) -> &SwapSolver {
+        &self.swap_solver
+    }
+
+    pub fn swap_solver_mut(&mut self) -> &mut SwapSolver {
+        &mut self.swap_solver
+    }
+
+    /// Records the fills of a solution for `intents`, once it is settled, so that later
+    /// batches do not overfill its intents. Nothing is recorded if a fill is rejected.
+    pub fn record_solution(
+        &mut self,
+        intents: &[SwapIntent],
+        solution: &Solution,
+    ) -> Result<(), FillError> {
+        let fills = solution.settled_fills();
+        self.swap_solver.record_fills(fills.iter().map(|fill| {
+            (
+                &intents[fill.intent],
+                &fill.sold_amount,
+                &fill.bought_amount,
+            )
+        }))
+    }
+
+    /// Clears the intents of a token pair, given by index with their orders and remaining
+    /// sizes, at the candidate price best meeting the objective
+    fn clear_pair(
+        &self,
+        (base_token, quote_token): (Address, Address),
+        orders: &[(usize, Order, BigUint)],
+    ) -> Option<(ClearingPrice, Vec<Fill>)> {
+        let mut prices = orders
+            .iter()
+            .filter(|(_, order, _)| {
+                order.sell_amount != BigUint::from(0_u8)
+                    && order.min_buy_amount != BigUint::from(0_u8)
+            })
+            .map(|(_, order, _)| {
+                let (quote_amount, base_amount) = if order.sell_token == base_token {
+                    (&order.min_buy_amount, &order.sell_amount)
+                } else {
+                    (&order.sell_amount, &order.min_buy_amount)
+                };
+                ClearingPrice::reduced(
+                    quote_token,
+                    base_token,
+                    quote_amount.clone(),
+                    base_amount.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        prices.sort_by(ClearingPrice::cmp_price);
+        prices.dedup();
+
+        let mut best: Option<(BigUint, Ratio, ClearingPrice, Vec<Fill>)> = None;
+        for price in prices {
+            let fills = Self::clear_at(&price, orders);
+            if fills.is_empty() {
+                continue;
+            }
+            let volume = fills
+                .iter()
+                .filter(|fill| orders[fill.intent].1.sell_token == base_token)
+                .map(|fill| fill.sold_amount.clone())
+                .sum::<BigUint>();
+            let surplus = fills
+                .iter()
+                .map(|fill| {
+                    let (_, order, _) = &orders[fill.intent];
+                    // surplus in the bought token, valued in quote tokens
+                    let surplus = (
+                        &fill.bought_amount * &order.sell_amount
+                            - &fill.sold_amount * &order.min_buy_amount,
+                        order.sell_amount.clone(),
+                    );
+                    if order.buy_token == base_token {
+                        (
+                            surplus.0 * &price.quote_amount,
+                            surplus.1 * &price.base_amount,
+                        )
+                    } else {
+                        surplus
+                    }
+                })
+                .fold((BigUint::from(0_u8), BigUint::from(1_u8)), add_ratios);
+
+            let better = match &best {
+                None => true,
+                Some((best_volume, best_surplus, _, _)) => {
+                    let by_volume = volume.cmp(best_volume);
+                    let by_surplus = cmp_ratios(&surplus, best_surplus);
+                    match self.objective {
+                        AuctionObjective::Volume => by_volume.then(by_surplus),
+                        AuctionObjective::Surplus => by_surplus.then(by_volume),
+                    }
+                    .is_gt()
+                }
+            };
+            if better {
+                best = Some((volume, surplus, price, fills));
+            }
+        }
+
+        best.map(|(_, _, price, fills)| (price, fills))
+    }
+
+    /// Fills of the pair's intents at `price`, where each fill's `intent` is the position of
+    /// its order in `orders`
+    fn clear_at(price: &ClearingPrice, orders: &[(usize, Order, BigUint)]) -> Vec<Fill> {
+        let zero = BigUint::from(0_u8);
+        let mut sell_base = vec![];
+        let mut sell_quote = vec![];
+        for (position, (_, order, remaining)) in orders.iter().enumerate() {
+            let (unit_sold, unit_bought) = price.unit(order);
+            let units = remaining / unit_sold;
+            let whole = !order.partially_fillable;
+            if units == zero
+                || (whole && remaining % unit_sold != zero)
+                || !order.accepts_fill(&(&units * unit_sold), &(&units * unit_bought))
+            {
+                continue;
+            }
+            let bid = Bid {
+                intent: position,
+                units,
+                whole,
+            };
+            if order.sell_token == price.base_token {
+                sell_base.push((order, bid));
+            } else {
+                sell_quote.push((order, bid));
+            }
+        }
+
+        // best limits first: the lowest price asked for base tokens, and the highest price
+        // offered for them
+        sell_base.sort_by(|(a, _), (b, _)| {
+            (&a.min_buy_amount * &b.sell_amount).cmp(&(&b.min_buy_amount * &a.sell_amount))
+        });
+        sell_quote.sort_by(|(a, _), (b, _)| {
+            (&b.sell_amount * &a.min_buy_amount).cmp(&(&a.sell_amount * &b.min_buy_amount))
+        });
+        let sell_base = sell_base
+            .into_iter()
+            .map(|(_, bid)| bid)
+            .collect::<Vec<_>>();
+        let sell_quote = sell_quote
+            .into_iter()
+            .map(|(_, bid)| bid)
+            .collect::<Vec<_>>();
+
+        // both sides should trade the same units, which whole intents may not fit
+        let total = |bids: &[Bid]| bids.iter().map(|bid| bid.units.clone()).sum::<BigUint>();
+        let mut units = total(&sell_base).min(total(&sell_quote));
+        loop {
+            let taken = take(&sell_base, &units)
+                .into_iter()
+                .sum::<BigUint>()
+                .min(take(&sell_quote, &units).into_iter().sum());
+            if taken == units {
+                break;
+            }
+            units = taken;
+        }
+
+        let mut fills = sell_base
+            .iter()
+            .zip(take(&sell_base, &units))
+            .chain(sell_quote.iter().zip(take(&sell_quote, &units)))
+            .filter(|(_, taken)| taken != &zero)
+            .map(|(bid, taken)| {
+                let (unit_sold, unit_bought) = price.unit(&orders[bid.intent].1);
+                Fill {
+                    intent: bid.intent,
+                    sold_amount: &taken * unit_sold,
+                    bought_amount: taken * unit_bought,
+                }
+            })
+            .collect::<Vec<_>>();
+        fills.sort_by_key(|fill| fill.intent);
+        fills
+    }
+}
+
+impl Solver<SwapIntent> for BatchAuction {
+    type Output = Solution;
+
+    fn execute_runtime(&self, intents: Vec<SwapIntent>) -> Self::Output {
+        let zero = BigUint::from(0_u8);
+        let orders = intents.iter().map(SwapIntent::order).collect::<Vec<_>>();
+        let mut remaining = intents
+            .iter()
+            .map(|intent| {
+                if self.swap_solver.is_cancelled(intent) {
+                    zero.clone()
+                } else {
+                    self.swap_solver.fill_tracker().remaining(intent)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // remaining intents of every token pair, in batch order
+        let mut pairs = BTreeMap::<_, Vec<_>>::new();
+        for (index, order) in orders.iter().enumerate() {
+            if remaining[index] != zero {
+                pairs
+                    .entry(token_pair(order.sell_token, order.buy_token))
+                    .or_default()
+                    .push((index, order.clone(), remaining[index].clone()));
+            }
+        }
+
+        let mut solution = Solution::default();
+        for (pair, orders) in pairs {
+            if let Some((price, fills)) = self.clear_pair(pair, &orders) {
+                solution.prices.push(price);
+                solution.fills.extend(fills.into_iter().map(|fill| Fill {
+                    intent: orders[fill.intent].0,
+                    ..fill
+                }));
+            }
+        }
+        solution.fills.sort_by_key(|fill| fill.intent);
+
+        // an intent which is not partially fillable is filled as a whole
+        for fill in &solution.fills {
+            remaining[fill.intent] = if orders[fill.intent].partially_fillable {
+                &remaining[fill.intent] - &fill.sold_amount
+            } else {
+                zero.clone()
+            };
+        }
+        if let Some(amm) = &self.amm {
+            let mut amm = amm.clone();
+            for (intent, order) in orders.iter().enumerate() {
+                if remaining[intent] == zero {
+                    continue;
+                }
+                if let Ok(routes) = amm.fill_order(order, &remaining[intent]) {
+                    solution.routes.push(RoutedFill { intent, routes });
+                }
+            }
+        }
+        solution
+    }
+
+    fn generate_execute_proof<C: GenericConfig<D, F = F>>(
+        &self,
+        circuit_builder: &mut CircuitBuilder<F, D>,
+        partial_witness: &mut PartialWitness<F>,
+        signature_verifier: &IntentSignatureVerifier<C>,
+        intents: Vec<IntentSignature<SwapIntent, C>>,
+    ) -> Result<(), anyhow::Error> {
+        verify_signature_proofs(signature_verifier, &intents)?;
+
+        let intents = intents
+            .into_iter()
+            .map(|intent_signature| intent_signature.intent)
+            .collect::<Vec<_>>();
+        let solution = self.execute_runtime(intents.clone());
+
+        // the witnessed intents constrain each fill to its intent's limits and its pair's
+        // clearing price, and each routed fill to its intent's limits
+        constrain_swap_settlement(
+            circuit_builder,
+            partial_witness,
+            &intents,
+            &solution.settlement(&intents)?,
+        )
+    }
+
+    fn verify_intents_signatures(
+        &mut self,
+        intents: Vec<(SwapIntent, RecoverableSignature)>,
+    ) -> Result<Vec<PublicKey>, anyhow::Error> {
+        self.swap_solver.verify_intents_signatures(intents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::plonk::circuit_data::CircuitConfig;
+
+    use crate::{
+        aggregation::intent_batch_commitment,
+        amm::ConstantProductPool,
+        swap_intent::SwapDirection,
+        swap_settlement::swap_intent_commitments,
+        test_utils::{
+            proven_intent_signatures, proven_swap_intents, signature_prover, swap_intent,
+            ProofConfig,
+        },
+    };
+
+    use super::*;
+
+    fn fill(intent: usize, sold_amount: u64, bought_amount: u64) -> Fill {
+        Fill {
+            intent,
+            sold_amount: BigUint::from(sold_amount),
+            bought_amount: BigUint::from(bought_amount),
+        }
+    }
+
+    fn price(solution: &Solution) -> (BigUint, BigUint) {
+        let price = solution
+            .price(Address::new([125; 20]), Address::new([64; 20]))
+            .unwrap();
+        assert_eq!(price.base_token, Address::new([64; 20]));
+        (price.quote_amount.clone(), price.base_amount.clone())
+    }
+
+    #[test]
+    fn it_works_batch_auction_uniform_price() {
+        let intents = vec![
+            // buys at most at 5/2 quote tokens per base token
+            swap_intent(SwapDirection::Buy, 100, 40, true),
+            // buys at most at 2
+            swap_intent(SwapDirection::Buy, 60, 30, true),
+            // sells at least at 1
+            swap_intent(SwapDirection::Sell, 50, 50, true),
+            // sells at least at 2
+            swap_intent(SwapDirection::Sell, 60, 30, true),
+        ];
+
+        // at a price of 2, every intent is filled
+        let solution = BatchAuction::default().execute_runtime(intents);
+        assert_eq!(price(&solution), (BigUint::from(2_u8), BigUint::from(1_u8)));
+        assert_eq!(
+            solution.fills,
+            vec![
+                fill(0, 100, 50),
+                fill(1, 60, 30),
+                fill(2, 50, 100),
+                fill(3, 30, 60)
+            ]
+        );
+    }
+
+    #[test]
+    fn it_works_batch_auction_solution_settlement() {
+        let mut intents = vec![
+            swap_intent(SwapDirection::Buy, 100, 40, true),
+            swap_intent(SwapDirection::Buy, 60, 30, true),
+            swap_intent(SwapDirection::Sell, 50, 50, true),
+            swap_intent(SwapDirection::Sell, 60, 30, true),
+        ];
+        // the same order as the first intent, on the reversed quote/base pair
+        intents[0] = {
+            let mut intent = swap_intent(SwapDirection::Sell, 40, 100, true);
+            intent.inputs.quote_token = Address::new([64; 20]);
+            intent.inputs.base_token = Address::new([125; 20]);
+            intent
+        };
+
+        let solution = BatchAuction::default().execute_runtime(intents.clone());
+        let settlement = solution.settlement(&intents).unwrap();
+        assert_eq!(settlement.clearing_prices, solution.prices);
+        assert!(settlement.uniform_prices);
+        let amounts = settlement
+            .fills
+            .iter()
+            .map(|fill| {
+                (
+                    fill.intent,
+                    fill.price,
+                    fill.quote_amount.clone(),
+                    fill.base_amount.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let amount = <BigUint as From<u64>>::from;
+        assert_eq!(
+            amounts,
+            vec![
+                (0, 0, amount(100), amount(50)),
+                (1, 0, amount(60), amount(30)),
+                (2, 0, amount(100), amount(50)),
+                (3, 0, amount(60), amount(30)),
+            ]
+        );
+        settlement.check(&intents).unwrap();
+
+        // fills of intents without a clearing price are not settled
+        let mut unpriced = solution;
+        unpriced.prices.clear();
+        assert!(unpriced.settlement(&intents).is_err());
+    }
+
+    #[test]
+    fn it_works_batch_auction_objectives() {
+        let intents = vec![
+            // buys at most at 10 quote tokens per base token
+            swap_intent(SwapDirection::Buy, 100, 10, true),
+            // sells at least at 1/10
+            swap_intent(SwapDirection::Sell, 10, 100, true),
+        ];
+
+        // 100 base tokens are traded at 1/10, but only 10 at 10
+        let solution = BatchAuction::new(AuctionObjective::Volume).execute_runtime(intents.clone());
+        assert_eq!(
+            price(&solution),
+            (BigUint::from(1_u8), BigUint::from(10_u8))
+        );
+        assert_eq!(solution.fills, vec![fill(0, 10, 100), fill(1, 100, 10)]);
+
+        // the seller's surplus at 10 exceeds the buyer's surplus at 1/10
+        let solution = BatchAuction::new(AuctionObjective::Surplus).execute_runtime(intents);
+        assert_eq!(
+            price(&solution),
+            (BigUint::from(10_u8), BigUint::from(1_u8))
+        );
+        assert_eq!(solution.fills, vec![fill(0, 100, 10), fill(1, 10, 100)]);
+    }
+
+    #[test]
+    fn it_works_batch_auction_whole_intents() {
+        let intents = vec![
+            swap_intent(SwapDirection::Buy, 90, 50, true),
+            swap_intent(SwapDirection::Sell, 80, 80, false),
+            swap_intent(SwapDirection::Sell, 20, 20, false),
+            // another token pair, without counterparty
+            {
+                let mut intent = swap_intent(SwapDirection::Sell, 10, 10, true);
+                intent.inputs.base_token = Address::new([65; 20]);
+                intent
+            },
+        ];
+
+        // the second seller does not fit once the first one is filled
+        let mut auction = BatchAuction::default();
+        let solution = auction.execute_runtime(intents.clone());
+        assert_eq!(price(&solution), (BigUint::from(1_u8), BigUint::from(1_u8)));
+        assert_eq!(solution.fills, vec![fill(0, 80, 80), fill(1, 80, 80)]);
+        assert_eq!(solution.prices.len(), 1);
+        for fill in &solution.fills {
+            assert!(intents[fill.intent]
+                .order()
+                .accepts_fill(&fill.sold_amount, &fill.bought_amount));
+        }
+
+        // the buyer's remaining 10 quote tokens do not fit the second seller
+        auction.record_solution(&intents, &solution).unwrap();
+        let solution = auction.execute_runtime(intents);
+        assert_eq!(solution, Solution::default());
+    }
+
+    #[test]
+    fn it_works_batch_auction_routes_residual_intents() {
+        let intents = vec![
+            swap_intent(SwapDirection::Buy, 200, 100, true),
+            swap_intent(SwapDirection::Sell, 50, 50, true),
+        ];
+        let pool = ConstantProductPool::new(
+            Address::new([125; 20]),
+            Address::new([64; 20]),
+            BigUint::from(1000_u32),
+            BigUint::from(1000_u32),
+            30,
+        )
+        .unwrap();
+        let mut auction = BatchAuction::default().with_amm(AmmSimulator::new(vec![pool]));
+
+        // the buyer's residual 100 quote tokens are swapped for 90 base tokens
+        let solution = auction.execute_runtime(intents.clone());
+        assert_eq!(solution.fills, vec![fill(0, 100, 50), fill(1, 50, 100)]);
+        assert_eq!(
+            solution.routes,
+            vec![RoutedFill {
+                intent: 0,
+                routes: vec![Route {
+                    pools: vec![0],
+                    tokens: vec![Address::new([125; 20]), Address::new([64; 20])],
+                    amount_in: BigUint::from(100_u8),
+                    amount_out: BigUint::from(90_u8),
+                }],
+            }]
+        );
+        assert_eq!(solution.filled_intents(), vec![0, 1]);
+        let settlement = solution.settlement(&intents).unwrap();
+        assert_eq!(settlement.routed_fills, solution.routes);
+        settlement.check(&intents).unwrap();
+
+        auction.record_solution(&intents, &solution).unwrap();
+        assert_eq!(
+            auction.swap_solver().fill_tracker().remaining(&intents[0]),
+            BigUint::from(0_u8)
+        );
+    }
+
+    #[test]
+    fn it_works_batch_auction_generate_execute_proof() {
+        let proven_intents = proven_swap_intents();
+        let intents = proven_intents
+            .iter()
+            .map(|proven_intent| proven_intent.intent.clone())
+            .collect::<Vec<_>>();
+        let intent_signatures = proven_intent_signatures();
+        let pool = ConstantProductPool::new(
+            Address::new([125; 20]),
+            Address::new([64; 20]),
+            BigUint::from(1000_u32),
+            BigUint::from(1000_u32),
+            30,
+        )
+        .unwrap();
+        let auction = BatchAuction::default().with_amm(AmmSimulator::new(vec![pool]));
+
+        let mut circuit_builder =
+            CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let mut partial_witness = PartialWitness::new();
+        auction
+            .generate_execute_proof(
+                &mut circuit_builder,
+                &mut partial_witness,
+                &signature_prover().verifier().unwrap(),
+                intent_signatures,
+            )
+            .unwrap();
+        let circuit_data = circuit_builder.build::<ProofConfig>();
+        let proof_with_pis = circuit_data.prove(partial_witness).unwrap();
+
+        // the proof exposes the batch and the settlement of its solution, with its routes
+        let solution = auction.execute_runtime(intents.clone());
+        assert!(!solution.routes.is_empty());
+        let settlement = solution.settlement(&intents).unwrap();
+        assert_eq!(
+            proof_with_pis.public_inputs,
+            [
+                intent_batch_commitment(&swap_intent_commitments(&intents)).elements,
+                settlement.commitment().elements,
+            ]
+            .concat()
+        );
+        circuit_data.verify(proof_with_pis).unwrap();
+    }
+}
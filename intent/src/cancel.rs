@@ -0,0 +1,397 @@
+//! Cancellation of signed intents, and a circuit proving that a batch excludes the
+//! cancelled intents.
+//!
+//! A [`CancelIntent`] is an EIP-712 message, signed by the intent's sender, which references
+//! the cancelled intent either by its structured hash or by its nonce. The exclusion circuit
+//! witnesses the batch's intents, so that it exposes the same batch commitment as the batch's
+//! aggregated signature proof (see [`crate::aggregation`]), and checks that no cancellation,
+//! by structured hash or by nonce, references one of them with its sender.
+use anyhow::anyhow;
+use plonky2::{
+    field::types::Field,
+    hash::{
+        hash_types::{HashOut, HashOutTarget},
+        poseidon::PoseidonHash,
+    },
+    iop::witness::{PartialWitness, WitnessWrite},
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::{
+            CircuitConfig, CircuitData, CommonCircuitData, VerifierCircuitData,
+            VerifierOnlyCircuitData,
+        },
+        config::{GenericConfig, Hasher},
+        proof::ProofWithPublicInputs,
+    },
+};
+use types::types::{Address, Message, PrivateKey, RecoverableSignature};
+
+use crate::{
+    aggregation::{intent_batch_commitment, intent_batch_commitment_target},
+    circuit::IntentCircuitInterface,
+    keccak::CircuitBuilderKeccak,
+    pool::PooledIntent,
+    public_inputs::bytes_to_u32_words,
+    structured_hash::{Eip712Domain, StructuredHash, StructuredHashInterface},
+    D, F,
+};
+
+/// Structured hash referencing no intent, as a cancellation by nonce does
+const NO_INTENT_HASH: [u8; 32] = [0; 32];
+
+/// Intent referenced by a [`CancelIntent`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CancelTarget {
+    /// the intent with this structured hash
+    IntentHash([u8; 32]),
+    /// the sender's intent with this nonce
+    Nonce(u64),
+}
+
+/// Cancellation of one of the sender's intents
+#[derive(Clone, Debug, StructuredHash)]
+#[eip712(crate = "crate")]
+pub struct CancelIntent {
+    /// address
+    pub(crate) from: Address,
+    /// structured hash of the cancelled intent, zero when cancelling by nonce
+    pub(crate) intent_hash: [u8; 32],
+    /// nonce of the cancelled intent, zero when cancelling by structured hash
+    pub(crate) nonce: u64,
+}
+
+impl CancelIntent {
+    pub fn by_intent_hash(from: Address, intent_hash: [u8; 32]) -> Self {
+        Self {
+            from,
+            intent_hash,
+            nonce: 0,
+        }
+    }
+
+    pub fn by_nonce(from: Address, nonce: u64) -> Self {
+        Self {
+            from,
+            intent_hash: NO_INTENT_HASH,
+            nonce,
+        }
+    }
+
+    pub fn from(&self) -> Address {
+        self.from
+    }
+
+    pub fn target(&self) -> CancelTarget {
+        if self.intent_hash == NO_INTENT_HASH {
+            CancelTarget::Nonce(self.nonce)
+        } else {
+            CancelTarget::IntentHash(self.intent_hash)
+        }
+    }
+
+    /// Signs the cancellation's EIP-712 signing hash in `domain`, the one of its intents
+    pub fn sign(&self, private_key: PrivateKey, domain: &Eip712Domain) -> RecoverableSignature {
+        let message = Message::new_message(self.signing_hash(domain));
+        private_key.sign_message_recoverable(&message)
+    }
+}
+
+/// Proof that a batch of intents excludes the cancelled intents
+pub struct CancellationExclusionProofData<C: GenericConfig<D, F = F>> {
+    pub proof_with_pis: ProofWithPublicInputs<F, C, D>,
+    pub common: CommonCircuitData<F, D>,
+    pub verifier_only: VerifierOnlyCircuitData<C, D>,
+}
+
+/// Key of the cancellation of `from`'s intent referenced by `target`. Keys bind the sender, so
+/// that a cancellation signed by another account than the intent's sender excludes no intent
+pub fn cancellation_key(from: &Address, target: CancelTarget) -> HashOut<F> {
+    let from = bytes_to_u32_words(from.as_bytes());
+    let elements = match target {
+        CancelTarget::IntentHash(intent_hash) => [bytes_to_u32_words(&intent_hash), from].concat(),
+        CancelTarget::Nonce(nonce) => [
+            from,
+            vec![
+                F::from_canonical_u32(nonce as u32),
+                F::from_canonical_u32((nonce >> 32) as u32),
+            ],
+        ]
+        .concat(),
+    };
+    PoseidonHash::hash_no_pad(&elements)
+}
+
+/// Keys of the cancellations which exclude `intent`, by structured hash and by nonce
+pub fn intent_cancellation_keys<I: PooledIntent>(intent: &I) -> [HashOut<F>; 2] {
+    [
+        CancelTarget::IntentHash(intent.structured_hash()),
+        CancelTarget::Nonce(intent.nonce()),
+    ]
+    .map(|target| cancellation_key(&intent.get_from(), target))
+}
+
+/// Commitment of a list of cancellation keys, as exposed by an exclusion proof
+pub fn cancellations_commitment(cancellation_keys: &[HashOut<F>]) -> HashOut<F> {
+    let elements = cancellation_keys
+        .iter()
+        .flat_map(|key| key.elements)
+        .collect::<Vec<_>>();
+    PoseidonHash::hash_no_pad(&elements)
+}
+
+/// Circuit proving that a batch of intents excludes the intents cancelled by a list of
+/// cancellations, given by their [`cancellation_key`]s, whether by structured hash or by nonce
+pub struct CancellationExclusionCircuit<C, I>
+where
+    C: GenericConfig<D, F = F>,
+    I: IntentCircuitInterface,
+{
+    data: CircuitData<F, C, D>,
+    intent_targets: Vec<I::IntentTarget>,
+    cancellation_key_targets: Vec<HashOutTarget>,
+}
+
+impl<C, I> CancellationExclusionCircuit<C, I>
+where
+    C: GenericConfig<D, F = F>,
+    I: IntentCircuitInterface + PooledIntent,
+{
+    /// Builds the circuit for a batch of `num_intents` intents and `num_cancellations`
+    /// cancellations
+    pub fn new(num_intents: usize, num_cancellations: usize) -> Result<Self, anyhow::Error> {
+        if num_intents == 0 {
+            return Err(anyhow!("Batch should not be empty"));
+        }
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut circuit_builder = CircuitBuilder::<F, D>::new(config);
+
+        let intent_targets = (0..num_intents)
+            .map(|_| I::add_virtual_intent_target(&mut circuit_builder))
+            .collect::<Vec<_>>();
+        let cancellation_key_targets = circuit_builder.add_virtual_hashes(num_cancellations);
+
+        let mut intent_commitment_targets = vec![];
+        for intent_target in &intent_targets {
+            let structured_hash_target =
+                I::structured_hash_target(&mut circuit_builder, intent_target);
+            let from_words =
+                circuit_builder.bytes_to_u32_words_be(&I::from_target(intent_target).0);
+            let hash_words = circuit_builder.bytes_to_u32_words_be(&structured_hash_target);
+            let nonce_limbs = I::nonce_target(intent_target)
+                .limbs
+                .iter()
+                .map(|limb| limb.0)
+                .collect::<Vec<_>>();
+            let intent_keys = [
+                [hash_words.clone(), from_words.clone()].concat(),
+                [from_words, nonce_limbs].concat(),
+            ]
+            .map(|elements| circuit_builder.hash_n_to_hash_no_pad::<PoseidonHash>(elements));
+
+            for intent_key in &intent_keys {
+                for cancellation_key in &cancellation_key_targets {
+                    let mut equal = circuit_builder._true();
+                    for (&a, &b) in intent_key.elements.iter().zip(&cancellation_key.elements) {
+                        let equal_element = circuit_builder.is_equal(a, b);
+                        equal = circuit_builder.and(equal, equal_element);
+                    }
+                    circuit_builder.assert_zero(equal.target);
+                }
+            }
+            intent_commitment_targets
+                .push(circuit_builder.hash_n_to_hash_no_pad::<PoseidonHash>(hash_words));
+        }
+
+        let batch_commitment_target =
+            intent_batch_commitment_target(&mut circuit_builder, &intent_commitment_targets);
+        let cancellation_elements = cancellation_key_targets
+            .iter()
+            .flat_map(|key| key.elements)
+            .collect();
+        let cancellations_commitment_target =
+            circuit_builder.hash_n_to_hash_no_pad::<PoseidonHash>(cancellation_elements);
+        circuit_builder.register_public_inputs(&batch_commitment_target.elements);
+        circuit_builder.register_public_inputs(&cancellations_commitment_target.elements);
+
+        Ok(Self {
+            data: circuit_builder.build::<C>(),
+            intent_targets,
+            cancellation_key_targets,
+        })
+    }
+
+    /// Proves that none of `intents` is cancelled by the cancellations with the given keys
+    pub fn prove(
+        &self,
+        intents: &[I],
+        cancellation_keys: &[HashOut<F>],
+    ) -> Result<CancellationExclusionProofData<C>, anyhow::Error> {
+        if intents.len() != self.intent_targets.len()
+            || cancellation_keys.len() != self.cancellation_key_targets.len()
+        {
+            return Err(anyhow!("Batch does not have the circuit's shape"));
+        }
+        if let Some(index) = intents.iter().position(|intent| {
+            intent_cancellation_keys(intent)
+                .iter()
+                .any(|key| cancellation_keys.contains(key))
+        }) {
+            return Err(anyhow!("Intent {} is cancelled", index));
+        }
+
+        let mut partial_witness = PartialWitness::<F>::new();
+        for (intent, intent_target) in intents.iter().zip(&self.intent_targets) {
+            intent.set_intent_target(&mut partial_witness, intent_target);
+        }
+        for (key, target) in cancellation_keys.iter().zip(&self.cancellation_key_targets) {
+            partial_witness.set_hash_target(*target, *key);
+        }
+
+        let proof_with_pis = self.data.prove(partial_witness)?;
+        Ok(CancellationExclusionProofData {
+            proof_with_pis,
+            common: self.data.common.clone(),
+            verifier_only: self.data.verifier_only.clone(),
+        })
+    }
+
+    pub fn verifier_data(&self) -> VerifierCircuitData<F, C, D> {
+        self.data.verifier_data()
+    }
+}
+
+/// Proves that a batch of intents excludes the intents cancelled by the cancellations
+/// with the given keys
+pub fn prove_cancelled_intents_excluded<C, I>(
+    intents: &[I],
+    cancellation_keys: &[HashOut<F>],
+) -> Result<CancellationExclusionProofData<C>, anyhow::Error>
+where
+    C: GenericConfig<D, F = F>,
+    I: IntentCircuitInterface + PooledIntent,
+{
+    CancellationExclusionCircuit::<C, I>::new(intents.len(), cancellation_keys.len())?
+        .prove(intents, cancellation_keys)
+}
+
+/// Verifies an exclusion proof for the batch of intents of type `I` with the given
+/// commitments, and the cancellations with the given keys, against the rebuilt circuit
+pub fn verify_cancelled_intents_excluded<C, I>(
+    exclusion_proof: &CancellationExclusionProofData<C>,
+    intent_commitments: &[HashOut<F>],
+    cancellation_keys: &[HashOut<F>],
+) -> Result<(), anyhow::Error>
+where
+    C: GenericConfig<D, F = F>,
+    I: IntentCircuitInterface + PooledIntent,
+{
+    let expected_public_inputs = [
+        intent_batch_commitment(intent_commitments).elements,
+        cancellations_commitment(cancellation_keys).elements,
+    ]
+    .concat();
+    if exclusion_proof.proof_with_pis.public_inputs != expected_public_inputs {
+        return Err(anyhow!(
+            "Exclusion proof does not cover the given intents and cancellations"
+        ));
+    }
+    let verifier_data = CancellationExclusionCircuit::<C, I>::new(
+        intent_commitments.len(),
+        cancellation_keys.len(),
+    )?
+    .verifier_data();
+    if exclusion_proof.verifier_only.circuit_digest != verifier_data.verifier_only.circuit_digest {
+        return Err(anyhow!("Exclusion proof is not from the exclusion circuit"));
+    }
+    verifier_data.verify(exclusion_proof.proof_with_pis.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::plonk::config::PoseidonGoldilocksConfig;
+
+    use crate::{
+        intent::Intent,
+        swap_intent::{SwapDirection, SwapIntent},
+        swap_settlement::swap_intent_commitments,
+        test_utils::swap_intent,
+    };
+
+    use super::*;
+
+    type C = PoseidonGoldilocksConfig;
+
+    #[test]
+    fn it_works_cancel_intent_type_encoding() {
+        assert_eq!(
+            CancelIntent::type_encode(),
+            "CancelIntent(address from,bytes32 intent_hash,uint64 nonce)"
+        );
+    }
+
+    #[test]
+    fn it_works_cancel_intent_target() {
+        let from = Address::new([1; 20]);
+        assert_eq!(
+            CancelIntent::by_intent_hash(from, [2; 32]).target(),
+            CancelTarget::IntentHash([2; 32])
+        );
+        assert_eq!(
+            CancelIntent::by_nonce(from, 3).target(),
+            CancelTarget::Nonce(3)
+        );
+    }
+
+    #[test]
+    fn it_works_cancellation_exclusion_proof() {
+        let intents = (0..3)
+            .map(|nonce| {
+                let mut intent = swap_intent(SwapDirection::Buy, 100, 10, false);
+                intent.inputs.nonce = nonce;
+                intent
+            })
+            .collect::<Vec<_>>();
+        let from = intents[0].get_from();
+        let stranger = Address::new([1; 20]);
+        let cancellation_keys = vec![
+            cancellation_key(&from, CancelTarget::Nonce(3)),
+            // cancellations of the batch's intents, by another account than their sender
+            cancellation_key(&stranger, CancelTarget::Nonce(1)),
+            cancellation_key(
+                &stranger,
+                CancelTarget::IntentHash(intents[2].structured_hash()),
+            ),
+        ];
+
+        let exclusion_proof =
+            prove_cancelled_intents_excluded::<C, _>(&intents, &cancellation_keys).unwrap();
+        let intent_commitments = swap_intent_commitments(&intents);
+        verify_cancelled_intents_excluded::<C, SwapIntent>(
+            &exclusion_proof,
+            &intent_commitments,
+            &cancellation_keys,
+        )
+        .unwrap();
+        assert!(verify_cancelled_intents_excluded::<C, SwapIntent>(
+            &exclusion_proof,
+            &intent_commitments,
+            &cancellation_keys[..1],
+        )
+        .is_err());
+
+        // a batch with an intent cancelled by its sender, by nonce or by structured hash,
+        // cannot be proven
+        for target in [
+            CancelTarget::Nonce(1),
+            CancelTarget::IntentHash(intents[1].structured_hash()),
+        ] {
+            let mut cancellation_keys = cancellation_keys.clone();
+            cancellation_keys.push(cancellation_key(&from, target));
+            let error = prove_cancelled_intents_excluded::<C, _>(&intents, &cancellation_keys)
+                .err()
+                .unwrap();
+            assert!(error.to_string().contains("Intent 1 is cancelled"));
+        }
+    }
+}
@@ -1,20 +1,116 @@
-use plonky2::plonk::circuit_builder::CircuitBuilder;
+use keccak_hash::keccak;
+use plonky2::{
+    iop::{target::BoolTarget, witness::PartialWitness},
+    plonk::circuit_builder::CircuitBuilder,
+};
 use plonky2_ecdsa::gadgets::{
+    biguint::BigUintTarget,
     curve::CircuitBuilderCurve,
     ecdsa::{verify_message_circuit, ECDSAPublicKeyTarget, ECDSASignatureTarget},
     nonnative::{CircuitBuilderNonNative, NonNativeTarget},
 };
+use plonky2_u32::gadgets::arithmetic_u32::CircuitBuilderU32;
+
+use crate::{
+    keccak::CircuitBuilderKeccak,
+    public_inputs::{
+        intent_commitment_target, PublicInputsOptions, SignerPublicInput, COORDINATE_LIMBS,
+    },
+    structured_hash::{Eip712Domain, EIP712_PREFIX},
+    C, D, F, FF,
+};
 
-use crate::{C, D, F, FF};
+/// Size, in bytes, of an Ethereum address
+pub const ADDRESS_BYTES: usize = 20;
+
+/// Ethereum address, as a byte string (see [`crate::keccak`] for its representation)
+#[derive(Clone, Debug)]
+pub struct AddressTarget(pub Vec<BoolTarget>);
 
 pub trait ECDSAIntentCircuit {
+    /// Verifies an ECDSA signature, and that the signer's public key
+    /// is the one of the intent's `from` address
     fn verify_intent_signature(&mut self) -> ECDSASignatureCircuitTargets;
+    fn add_virtual_address_target(&mut self) -> AddressTarget;
+    /// Derives the address of a public key, the last 20 bytes of the keccak256
+    /// hash of its uncompressed `x ‖ y` encoding
+    fn public_key_to_address(&mut self, public_key: &ECDSAPublicKeyTarget<C>) -> AddressTarget;
+    fn connect_address(&mut self, a: &AddressTarget, b: &AddressTarget);
+    /// Verifies an ECDSA signature over an intent whose fields are witnessed, the signed
+    /// message being the intent's EIP-712 signing hash in `domain`, as signed by
+    /// `eth_signTypedData_v4`
+    fn verify_intent_fields_signature<I: IntentCircuitInterface>(
+        &mut self,
+        domain: &Eip712Domain,
+    ) -> IntentSignatureCircuitTargets<I>;
+    /// Registers the values selected by `options` as public inputs,
+    /// in the order described in [`crate::public_inputs`]
+    fn register_intent_public_inputs(
+        &mut self,
+        structured_hash: &[BoolTarget],
+        public_key: &ECDSAPublicKeyTarget<C>,
+        address: &AddressTarget,
+        options: &PublicInputsOptions,
+    );
+}
+
+/// Intent whose fields can be witnessed in a circuit, so that its EIP-712 structured
+/// hash is computed in the circuit rather than trusted as an opaque message
+pub trait IntentCircuitInterface {
+    type IntentTarget;
+
+    fn add_virtual_intent_target(circuit_builder: &mut CircuitBuilder<F, D>) -> Self::IntentTarget;
+    /// In-circuit `hashStruct` of the intent, as a 32-byte string
+    fn structured_hash_target(
+        circuit_builder: &mut CircuitBuilder<F, D>,
+        intent_target: &Self::IntentTarget,
+    ) -> Vec<BoolTarget>;
+    /// Account which should have signed the intent
+    fn from_target(intent_target: &Self::IntentTarget) -> &AddressTarget;
+    /// Sequence number of the intent among its sender's intents, as an `uint64`
+    fn nonce_target(intent_target: &Self::IntentTarget) -> &BigUintTarget;
+    fn set_intent_target(
+        &self,
+        partial_witness: &mut PartialWitness<F>,
+        intent_target: &Self::IntentTarget,
+    );
+}
+
+/// In-circuit EIP-712 encoding, each member being encoded as a 32-byte string
+pub trait CircuitBuilderEip712 {
+    fn encode_address_target(&mut self, address: &AddressTarget) -> Vec<BoolTarget>;
+    /// Encodes an `uint256` value, which should have at most 8 limbs
+    fn encode_uint256_target(&mut self, value: &BigUintTarget) -> Vec<BoolTarget>;
+    /// Encodes a `bool`, or an `uint8` restricted to 0 and 1
+    fn encode_bool_target(&mut self, value: BoolTarget) -> Vec<BoolTarget>;
+    /// Computes `hashStruct(s) = keccak256(typeHash ‖ encodeData(s))`, with the
+    /// type hash derived from the (constant) type encoding
+    fn hash_struct_target(
+        &mut self,
+        type_encoding: &str,
+        encoded_members: &[Vec<BoolTarget>],
+    ) -> Vec<BoolTarget>;
+    /// Computes `keccak256("\x19\x01" ‖ domainSeparator ‖ hashStruct(message))`, with the
+    /// separator of the (constant) `domain`
+    fn signing_hash_target(
+        &mut self,
+        domain: &Eip712Domain,
+        structured_hash: &[BoolTarget],
+    ) -> Vec<BoolTarget>;
 }
 
 pub struct ECDSASignatureCircuitTargets {
     pub(crate) public_key_ecdsa_target: ECDSAPublicKeyTarget<C>,
     pub(crate) message_nonnative_target: NonNativeTarget<FF>,
     pub(crate) signature_ecdsa_signature_target: ECDSASignatureTarget<C>,
+    pub(crate) from_address_target: AddressTarget,
+}
+
+pub struct IntentSignatureCircuitTargets<I: IntentCircuitInterface> {
+    pub(crate) signature_targets: ECDSASignatureCircuitTargets,
+    pub(crate) intent_target: I::IntentTarget,
+    /// The intent's structured hash, as a 32-byte string
+    pub(crate) structured_hash_target: Vec<BoolTarget>,
 }
 
 impl ECDSAIntentCircuit for CircuitBuilder<F, D> {
@@ -38,18 +134,146 @@ impl ECDSAIntentCircuit for CircuitBuilder<F, D> {
             public_key_ecdsa_target.clone(),
         );
 
+        let from_address_target = self.add_virtual_address_target();
+        let signer_address_target = self.public_key_to_address(&public_key_ecdsa_target);
+        self.connect_address(&signer_address_target, &from_address_target);
+
         ECDSASignatureCircuitTargets {
             public_key_ecdsa_target,
             message_nonnative_target,
             signature_ecdsa_signature_target,
+            from_address_target,
+        }
+    }
+
+    fn add_virtual_address_target(&mut self) -> AddressTarget {
+        AddressTarget(self.add_virtual_bytes_target(ADDRESS_BYTES))
+    }
+
+    fn public_key_to_address(&mut self, public_key: &ECDSAPublicKeyTarget<C>) -> AddressTarget {
+        let x_biguint_target = self.nonnative_to_canonical_biguint(&public_key.0.x);
+        let y_biguint_target = self.nonnative_to_canonical_biguint(&public_key.0.y);
+        let x_bytes_target = self.biguint_to_bytes_be(&x_biguint_target, 32);
+        let y_bytes_target = self.biguint_to_bytes_be(&y_biguint_target, 32);
+
+        let public_key_hash_target = self.keccak256(&[x_bytes_target, y_bytes_target].concat());
+        AddressTarget(public_key_hash_target[8 * (32 - ADDRESS_BYTES)..].to_vec())
+    }
+
+    fn connect_address(&mut self, a: &AddressTarget, b: &AddressTarget) {
+        self.connect_bytes(&a.0, &b.0);
+    }
+
+    fn verify_intent_fields_signature<I: IntentCircuitInterface>(
+        &mut self,
+        domain: &Eip712Domain,
+    ) -> IntentSignatureCircuitTargets<I> {
+        let signature_targets = self.verify_intent_signature();
+        let intent_target = I::add_virtual_intent_target(self);
+
+        // as for `Message::into_plonky2_message`, the hash is assumed to be
+        // lower than the scalar field order, which fails with probability ~2^(-128)
+        let structured_hash_target = I::structured_hash_target(self, &intent_target);
+        let signing_hash_target = self.signing_hash_target(domain, &structured_hash_target);
+        let message_biguint_target = self.bytes_be_to_biguint(&signing_hash_target);
+        let message_nonnative_target = self.biguint_to_nonnative::<FF>(&message_biguint_target);
+        self.connect_nonnative(
+            &message_nonnative_target,
+            &signature_targets.message_nonnative_target,
+        );
+        self.connect_address(
+            I::from_target(&intent_target),
+            &signature_targets.from_address_target,
+        );
+
+        IntentSignatureCircuitTargets {
+            signature_targets,
+            intent_target,
+            structured_hash_target,
+        }
+    }
+
+    fn register_intent_public_inputs(
+        &mut self,
+        structured_hash: &[BoolTarget],
+        public_key: &ECDSAPublicKeyTarget<C>,
+        address: &AddressTarget,
+        options: &PublicInputsOptions,
+    ) {
+        if options.message_hash {
+            let message_words = self.bytes_to_u32_words_be(structured_hash);
+            self.register_public_inputs(&message_words);
+        }
+        match options.signer {
+            SignerPublicInput::None => {}
+            SignerPublicInput::PublicKey => {
+                for coordinate in [&public_key.0.x, &public_key.0.y] {
+                    let mut limbs = self.nonnative_to_canonical_biguint(coordinate).limbs;
+                    let zero = self.zero_u32();
+                    limbs.resize(COORDINATE_LIMBS, zero);
+                    for limb in limbs {
+                        self.register_public_input(limb.0);
+                    }
+                }
+            }
+            SignerPublicInput::Address => {
+                let address_words = self.bytes_to_u32_words_be(&address.0);
+                self.register_public_inputs(&address_words);
+            }
+        }
+        if options.intent_commitment {
+            let commitment = intent_commitment_target(self, structured_hash);
+            self.register_public_inputs(&commitment.elements);
         }
     }
 }
 
+impl CircuitBuilderEip712 for CircuitBuilder<F, D> {
+    fn encode_address_target(&mut self, address: &AddressTarget) -> Vec<BoolTarget> {
+        let padding = self.constant_bytes(&[0u8; 32 - ADDRESS_BYTES]);
+        [padding, address.0.clone()].concat()
+    }
+
+    fn encode_uint256_target(&mut self, value: &BigUintTarget) -> Vec<BoolTarget> {
+        self.biguint_to_bytes_be(value, 32)
+    }
+
+    fn encode_bool_target(&mut self, value: BoolTarget) -> Vec<BoolTarget> {
+        let mut output = self.constant_bytes(&[0u8; 32]);
+        // least significant bit of the last byte
+        output[8 * 31] = value;
+        output
+    }
+
+    fn hash_struct_target(
+        &mut self,
+        type_encoding: &str,
+        encoded_members: &[Vec<BoolTarget>],
+    ) -> Vec<BoolTarget> {
+        let type_hash = keccak(type_encoding).to_fixed_bytes();
+        let type_hash_target = self.constant_bytes(&type_hash);
+        let input = [&[type_hash_target], encoded_members].concat().concat();
+        self.keccak256(&input)
+    }
+
+    fn signing_hash_target(
+        &mut self,
+        domain: &Eip712Domain,
+        structured_hash: &[BoolTarget],
+    ) -> Vec<BoolTarget> {
+        let prefix_target =
+            self.constant_bytes(&[EIP712_PREFIX.as_slice(), &domain.separator()].concat());
+        self.keccak256(&[prefix_target.as_slice(), structured_hash].concat())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use plonky2::{
-        field::{secp256k1_scalar::Secp256K1Scalar, types::Sample},
+        field::{
+            secp256k1_scalar::Secp256K1Scalar,
+            types::{PrimeField, Sample},
+        },
         iop::witness::PartialWitness,
         plonk::{
             circuit_data::CircuitConfig,
@@ -61,6 +285,13 @@ mod tests {
         ecdsa::{sign_message, ECDSAPublicKey, ECDSASecretKey, ECDSASignature},
         secp256k1::Secp256K1,
     };
+    use plonky2_ecdsa::gadgets::biguint::WitnessBigUint;
+    use types::types::{Address, PrivateKey, PublicKey};
+
+    use crate::{
+        keccak::WitnessKeccak,
+        public_inputs::{IntentPublicValues, PublicInputsOptions, SignerPublicInput},
+    };
 
     use super::*;
 
@@ -99,8 +330,13 @@ mod tests {
             public_key_ecdsa_target,
             message_nonnative_target,
             signature_ecdsa_signature_target,
+            from_address_target,
         } = builder.verify_intent_signature();
 
+        let from = Address::from_public_key(&PublicKey::from_plonky2_public_key(pk).unwrap());
+        let from_target = AddressTarget(builder.constant_bytes(from.as_bytes()));
+        builder.connect_address(&from_target, &from_address_target);
+
         builder.connect_affine_point(&public_key_ecdsa_target.0, &pk_target.0);
         builder.connect_nonnative(&sig_target.r, &signature_ecdsa_signature_target.r);
         builder.connect_nonnative(&sig_target.s, &signature_ecdsa_signature_target.s);
@@ -112,4 +348,85 @@ mod tests {
 
         data.verify(proof).expect("Failed to verify proof data")
     }
+
+    #[test]
+    fn it_works_public_key_to_address_circuit() {
+        type C = PoseidonGoldilocksConfig;
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let mut pw = PartialWitness::<F>::new();
+
+        let private_key = PrivateKey::new();
+        let public_key = PublicKey::from_private_key(&private_key);
+        let address = Address::from_public_key(&public_key);
+        let ECDSAPublicKey(public_key) = public_key.into_plonky2_public_key();
+
+        let public_key_target = ECDSAPublicKeyTarget(builder.add_virtual_affine_point_target());
+        let x_biguint_target = builder.nonnative_to_canonical_biguint(&public_key_target.0.x);
+        let y_biguint_target = builder.nonnative_to_canonical_biguint(&public_key_target.0.y);
+        pw.set_biguint_target(&x_biguint_target, &public_key.x.to_canonical_biguint());
+        pw.set_biguint_target(&y_biguint_target, &public_key.y.to_canonical_biguint());
+
+        let address_target = builder.public_key_to_address(&public_key_target);
+        let expected_address_target = AddressTarget(builder.constant_bytes(address.as_bytes()));
+        builder.connect_address(&address_target, &expected_address_target);
+
+        let data = builder.build::<C>();
+        let proof = data.prove(pw).unwrap();
+        data.verify(proof).expect("Failed to verify proof data")
+    }
+
+    #[test]
+    fn it_works_register_intent_public_inputs() {
+        type C = PoseidonGoldilocksConfig;
+
+        let private_key = PrivateKey::new();
+        let public_key = PublicKey::from_private_key(&private_key);
+        let address = Address::from_public_key(&public_key);
+        let structured_hash = keccak("intent").to_fixed_bytes();
+
+        for signer in [
+            SignerPublicInput::None,
+            SignerPublicInput::PublicKey,
+            SignerPublicInput::Address,
+        ] {
+            let options = PublicInputsOptions::new(true, signer, true);
+            let config = CircuitConfig::standard_recursion_config();
+            let mut builder = CircuitBuilder::<F, D>::new(config);
+            let mut pw = PartialWitness::<F>::new();
+
+            let structured_hash_target = builder.add_virtual_bytes_target(32);
+            let public_key_target = ECDSAPublicKeyTarget(builder.add_virtual_affine_point_target());
+            let address_target = builder.add_virtual_address_target();
+            builder.register_intent_public_inputs(
+                &structured_hash_target,
+                &public_key_target,
+                &address_target,
+                &options,
+            );
+
+            let ECDSAPublicKey(public_key_point) = public_key.clone().into_plonky2_public_key();
+            pw.set_bytes_target(&structured_hash_target, &structured_hash);
+            let x_biguint_target = builder.nonnative_to_canonical_biguint(&public_key_target.0.x);
+            let y_biguint_target = builder.nonnative_to_canonical_biguint(&public_key_target.0.y);
+            pw.set_biguint_target(
+                &x_biguint_target,
+                &public_key_point.x.to_canonical_biguint(),
+            );
+            pw.set_biguint_target(
+                &y_biguint_target,
+                &public_key_point.y.to_canonical_biguint(),
+            );
+            pw.set_bytes_target(&address_target.0, address.as_bytes());
+
+            let data = builder.build::<C>();
+            let proof = data.prove(pw).unwrap();
+            assert_eq!(
+                proof.public_inputs,
+                IntentPublicValues::new(&options, structured_hash, &public_key).to_field_elements()
+            );
+            data.verify(proof).expect("Failed to verify proof data")
+        }
+    }
 }
@@ -0,0 +1,671 @@
+//! Competition between solvers over the same batch of swap intents.
+//!
+//! Every solver's solution is validated against the batch, then scored by each of the
+//! competition's [`SolutionScorer`]s. Valid solutions are ranked by their scores, compared
+//! metric by metric in the order the scorers were added, and ties are won by the solver
+//! added first.
+use std::collections::BTreeMap;
+
+use num_bigint::BigUint;
+
+use crate::{
+    amm::{AmmSimulator, Route},
+    auction::Solution,
+    error::{ReplayError, SolutionError},
+    order::Order,
+    scoring::SolutionScorer,
+    solver::Solver,
+    swap_intent::SwapIntent,
+    swap_solver::SwapSolver,
+};
+
+/// Solver taking part in a [`Competition`]
+pub trait CompetingSolver {
+    fn solve(&self, intents: Vec<SwapIntent>) -> Solution;
+}
+
+impl<S> CompetingSolver for S
+where
+    S: Solver<SwapIntent>,
+    S::Output: Into<Solution>,
+{
+    fn solve(&self, intents: Vec<SwapIntent>) -> Solution {
+        self.execute_runtime(intents).into()
+    }
+}
+
+/// Checks that a solution fills `intents` within their limits and remaining sizes, at its
+/// clearing prices if it has any, that its routes trade the tokens of their intents through
+/// pools of `amm`, and that its fills within the batch buy as many tokens as they sell. Filled intents must not be cancelled, expired at time `now`, or have a nonce
+/// already used, by `swap_solver`'s records of settled batches.
+///
+/// Routes are replayed, in order, on a copy of `amm`, and each should swap exactly its
+/// `amount_in` for its `amount_out`. A solution without `amm` routes nothing.
+///
+/// A batch may hold several intents of one account, whose later nonces are only used once the
+/// earlier are, so an intent's nonce may be ahead of the one expected.
+pub fn validate_solution(
+    intents: &[SwapIntent],
+    solution: &Solution,
+    swap_solver: &SwapSolver,
+    amm: Option<&AmmSimulator>,
+    now: u64,
+) -> Result<(), SolutionError> {
+    let mut amm = amm.cloned().unwrap_or_default();
+    for routed_fill in &solution.routes {
+        let order = intents
+            .get(routed_fill.intent)
+            .ok_or(SolutionError::UnknownIntent(routed_fill.intent))?
+            .order();
+        if routed_fill.routes.is_empty()
+            || routed_fill
+                .routes
+                .iter()
+                .any(|route| !replay_route(&mut amm, &order, route))
+        {
+            return Err(SolutionError::InvalidRoute(routed_fill.intent));
+        }
+    }
+
+    let zero = BigUint::default();
+    for (index, price) in solution.prices.iter().enumerate() {
+        if price.quote_amount == zero
+            || price.base_amount == zero
+            || price.quote_token == price.base_token
+            || !solution
+                .price(price.quote_token, price.base_token)
+                .map_or(false, |first| std::ptr::eq(first, price))
+        {
+            return Err(SolutionError::InvalidPrice(index));
+        }
+    }
+    // a solution without clearing prices, such as direct matches or rings, prices each fill
+    // on its own
+    if !solution.prices.is_empty() {
+        for fill in &solution.fills {
+            let order = intents
+                .get(fill.intent)
+                .ok_or(SolutionError::UnknownIntent(fill.intent))?
+                .order();
+            let price = solution
+                .price(order.sell_token, order.buy_token)
+                .ok_or(SolutionError::OffPrice(fill.intent))?;
+            let (unit_sold, unit_bought) = price.unit(&order);
+            if &fill.sold_amount * unit_bought != &fill.bought_amount * unit_sold {
+                return Err(SolutionError::OffPrice(fill.intent));
+            }
+        }
+    }
+
+    let mut sold_amounts = vec![BigUint::default(); intents.len()];
+    let mut fill_counts = vec![0_usize; intents.len()];
+    for fill in solution.settled_fills() {
+        let order = intents
+            .get(fill.intent)
+            .ok_or(SolutionError::UnknownIntent(fill.intent))?
+            .order();
+        if !order.accepts_fill(&fill.sold_amount, &fill.bought_amount) {
+            return Err(SolutionError::OutOfLimits(fill.intent));
+        }
+        sold_amounts[fill.intent] += &fill.sold_amount;
+        fill_counts[fill.intent] += 1;
+    }
+    for (index, intent) in intents.iter().enumerate() {
+        if fill_counts[index] == 0 {
+            continue;
+        }
+        if swap_solver.is_cancelled(intent) {
+            return Err(SolutionError::Cancelled(index));
+        }
+        match swap_solver.nonce_tracker().check(
+            &intent.inputs.from,
+            intent.inputs.nonce,
+            intent.constraints.deadline,
+            now,
+        ) {
+            Ok(()) | Err(ReplayError::NonceOutOfOrder { .. }) => {}
+            Err(e) => return Err(SolutionError::Replayed(index, e)),
+        }
+        let remaining = swap_solver.fill_tracker().remaining(intent);
+        // an intent which is not partially fillable is filled at once
+        let overfilled = if intent.constraints.partially_fillable {
+            sold_amounts[index] > remaining
+        } else {
+            fill_counts[index] > 1 || remaining == zero
+        };
+        if overfilled {
+            return Err(SolutionError::Overfilled(index));
+        }
+    }
+
+    // tokens sold and bought within the batch, by token. The tokens of routed fills are
+    // bought and sold by their pools, which swap exactly the replayed amounts
+    let mut balances = BTreeMap::<_, (BigUint, BigUint)>::new();
+    for fill in &solution.fills {
+        let order = intents[fill.intent].order();
+        balances.entry(order.sell_token).or_default().0 += &fill.sold_amount;
+        balances.entry(order.buy_token).or_default().1 += &fill.bought_amount;
+    }
+    match balances
+        .into_iter()
+        .find(|(_, (sold, bought))| sold != bought)
+    {
+        Some((token, _)) => Err(SolutionError::Unbalanced(token)),
+        None => Ok(()),
+    }
+}
+
+/// Whether `route` swaps `order`'s sold tokens for its bought tokens, each hop through a pool
+/// of `amm` trading the hop's tokens, and swaps exactly its `amount_in` for its `amount_out`
+/// once replayed on `amm`
+fn replay_route(amm: &mut AmmSimulator, order: &Order, route: &Route) -> bool {
+    let hops_trade_tokens = !route.pools.is_empty()
+        && route.tokens.len() == route.pools.len() + 1
+        && route.tokens.first() == Some(&order.sell_token)
+        && route.tokens.last() == Some(&order.buy_token)
+        && route
+            .pools
+            .iter()
+            .zip(route.tokens.windows(2))
+            .all(|(&pool, tokens)| {
+                amm.pools()
+                    .get(pool)
+                    .map_or(false, |pool| pool.trades(tokens[0], tokens[1]))
+            });
+    hops_trade_tokens
+        && amm
+            .swap_route(route)
+            .map_or(false, |amount_out| amount_out == route.amount_out)
+}
+
+/// Score of a solution by one metric
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Score {
+    pub metric: &'static str,
+    pub value: BigUint,
+}
+
+/// Valid solution of a solver, with its score by every metric
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RankedSolution {
+    pub solver: String,
+    pub solution: Solution,
+    /// scores, in the order of the competition's scorers
+    pub scores: Vec<Score>,
+}
+
+/// Outcome of a competition
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Ranking {
+    /// valid solutions, best first
+    pub ranked: Vec<RankedSolution>,
+    /// solvers whose solution is invalid, with the reason
+    pub rejected: Vec<(String, SolutionError)>,
+}
+
+impl Ranking {
+    /// Best valid solution, if any
+    pub fn winner(&self) -> Option<&RankedSolution> {
+        self.ranked.first()
+    }
+}
+
+/// Competition between named solvers, scored by a list of metrics
+#[derive(Default)]
+pub struct Competition<'a> {
+    solvers: Vec<(String, Box<dyn CompetingSolver + 'a>)>,
+    scorers: Vec<Box<dyn SolutionScorer + 'a>>,
+    swap_solver: SwapSolver,
+    amm: Option<AmmSimulator>,
+}
+
+impl<'a> Competition<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_solver(mut self, name: &str, solver: impl CompetingSolver + 'a) -> Self {
+        self.solvers.push((name.to_string(), Box::new(solver)));
+        self
+    }
+
+    /// Competition ranking solutions by `scorer`, after the scorers already added
+    pub fn with_scorer(mut self, scorer: impl SolutionScorer + 'a) -> Self {
+        self.scorers.push(Box::new(scorer));
+        self
+    }
+
+    /// Competition validating solutions against the cancellations, nonces and remaining sizes
+    /// recorded by `swap_solver`
+    pub fn with_swap_solver(mut self, swap_solver: SwapSolver) -> Self {
+        self.swap_solver = swap_solver;
+        self
+    }
+
+    /// Competition replaying the routes of solutions on the pools of `amm`, the snapshot the
+    /// solvers route through
+    pub fn with_amm(mut self, amm: AmmSimulator) -> Self {
+        self.amm = Some(amm);
+        self
+    }
+
+    pub fn amm(&self) -> Option<&AmmSimulator> {
+        self.amm.as_ref()
+    }
+
+    /// Collects, validates and ranks the solutions of every solver for `intents`
+    pub fn run(&self, intents: &[SwapIntent]) -> Ranking {
+        let now = SwapSolver::now();
+        let mut ranking = Ranking::default();
+        for (name, solver) in &self.solvers {
+            let solution = solver.solve(intents.to_vec());
+            if let Err(e) = validate_solution(
+                intents,
+                &solution,
+                &self.swap_solver,
+                self.amm.as_ref(),
+                now,
+            ) {
+                ranking.rejected.push((name.clone(), e));
+                continue;
+            }
+            let scores = self
+                .scorers
+                .iter()
+                .map(|scorer| Score {
+                    metric: scorer.metric(),
+                    value: scorer.score(intents, &solution),
+                })
+                .collect();
+            ranking.ranked.push(RankedSolution {
+                solver: name.clone(),
+                solution,
+                scores,
+            });
+        }
+
+        // best scores first, the sort keeping the solvers' order on ties
+        ranking.ranked.sort_by(|a, b| {
+            let scores = |ranked: &RankedSolution| {
+                ranked
+                    .scores
+                    .iter()
+                    .map(|score| score.value.clone())
+                    .collect::<Vec<_>>()
+            };
+            scores(b).cmp(&scores(a))
+        });
+        ranking
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use types::types::Address;
+
+    use crate::{
+        amm::ConstantProductPool,
+        auction::{BatchAuction, Fill},
+        fill::FillTracker,
+        nonce::NonceTracker,
+        scoring::{FilledIntentsScorer, ReferencePrices, SurplusScorer},
+        swap_intent::SwapDirection,
+        swap_settlement::ClearingPrice,
+        test_utils::{swap_intent, token},
+    };
+
+    use super::*;
+
+    /// Solver filling the first intent far below its limit
+    struct GreedySolver;
+
+    impl CompetingSolver for GreedySolver {
+        fn solve(&self, _intents: Vec<SwapIntent>) -> Solution {
+            Solution {
+                fills: vec![Fill {
+                    intent: 0,
+                    sold_amount: BigUint::from(100_u8),
+                    bought_amount: BigUint::from(1_u8),
+                }],
+                ..Solution::default()
+            }
+        }
+    }
+
+    #[test]
+    fn it_works_validate_solution() {
+        let intents = vec![
+            swap_intent(SwapDirection::Buy, 100, 50, true),
+            swap_intent(SwapDirection::Sell, 100, 50, true),
+        ];
+        let fill = |intent: usize, sold_amount: u8, bought_amount: u8| Fill {
+            intent,
+            sold_amount: BigUint::from(sold_amount),
+            bought_amount: BigUint::from(bought_amount),
+        };
+        let solution = |fills: Vec<Fill>| Solution {
+            fills,
+            ..Solution::default()
+        };
+        let swap_solver = SwapSolver::new();
+
+        let valid = solution(vec![fill(0, 100, 50), fill(1, 50, 100)]);
+        assert_eq!(
+            validate_solution(&intents, &valid, &swap_solver, None, 0),
+            Ok(())
+        );
+        assert_eq!(
+            validate_solution(
+                &intents,
+                &solution(vec![fill(2, 1, 1)]),
+                &swap_solver,
+                None,
+                0
+            ),
+            Err(SolutionError::UnknownIntent(2))
+        );
+        assert_eq!(
+            validate_solution(
+                &intents,
+                &solution(vec![fill(0, 100, 40), fill(1, 40, 100)]),
+                &swap_solver,
+                None,
+                0
+            ),
+            Err(SolutionError::OutOfLimits(0))
+        );
+        assert_eq!(
+            validate_solution(
+                &intents,
+                &solution(vec![fill(0, 60, 30), fill(0, 60, 30), fill(1, 50, 100)]),
+                &swap_solver,
+                None,
+                0
+            ),
+            Err(SolutionError::Overfilled(0))
+        );
+        assert_eq!(
+            validate_solution(
+                &intents,
+                &solution(vec![fill(0, 100, 50), fill(1, 40, 100)]),
+                &swap_solver,
+                None,
+                0
+            ),
+            Err(SolutionError::Unbalanced(Address::new([64; 20])))
+        );
+    }
+
+    #[test]
+    fn it_works_validate_solution_prices() {
+        let intents = vec![
+            swap_intent(SwapDirection::Buy, 100, 50, true),
+            swap_intent(SwapDirection::Sell, 100, 50, true),
+        ];
+        let fills = vec![
+            Fill {
+                intent: 0,
+                sold_amount: BigUint::from(100_u8),
+                bought_amount: BigUint::from(50_u8),
+            },
+            Fill {
+                intent: 1,
+                sold_amount: BigUint::from(50_u8),
+                bought_amount: BigUint::from(100_u8),
+            },
+        ];
+        let solution = |quote_amount: u8, base_amount: u8| Solution {
+            fills: fills.clone(),
+            prices: vec![ClearingPrice {
+                quote_token: token(125),
+                base_token: token(64),
+                quote_amount: BigUint::from(quote_amount),
+                base_amount: BigUint::from(base_amount),
+            }],
+            ..Solution::default()
+        };
+        let swap_solver = SwapSolver::new();
+
+        // both fills trade 2 quote tokens per base token
+        assert_eq!(
+            validate_solution(&intents, &solution(2, 1), &swap_solver, None, 0),
+            Ok(())
+        );
+        assert_eq!(
+            validate_solution(&intents, &solution(3, 1), &swap_solver, None, 0),
+            Err(SolutionError::OffPrice(0))
+        );
+        assert_eq!(
+            validate_solution(&intents, &solution(0, 1), &swap_solver, None, 0),
+            Err(SolutionError::InvalidPrice(0))
+        );
+        let mut repeated_pair = solution(2, 1);
+        repeated_pair.prices.push(repeated_pair.prices[0].clone());
+        assert_eq!(
+            validate_solution(&intents, &repeated_pair, &swap_solver, None, 0),
+            Err(SolutionError::InvalidPrice(1))
+        );
+        // the fills have no price for their pair
+        let mut other_pair = solution(2, 1);
+        other_pair.prices[0].base_token = token(65);
+        assert_eq!(
+            validate_solution(&intents, &other_pair, &swap_solver, None, 0),
+            Err(SolutionError::OffPrice(0))
+        );
+        // the price's tokens are not in token pair order
+        let mut reversed_pair = solution(1, 2);
+        reversed_pair.prices[0].quote_token = token(64);
+        reversed_pair.prices[0].base_token = token(125);
+        assert_eq!(
+            validate_solution(&intents, &reversed_pair, &swap_solver, None, 0),
+            Err(SolutionError::InvalidPrice(0))
+        );
+    }
+
+    #[test]
+    fn it_works_validate_solution_routes() {
+        let intents = vec![
+            swap_intent(SwapDirection::Buy, 200, 100, true),
+            swap_intent(SwapDirection::Sell, 50, 50, true),
+        ];
+        let pool = ConstantProductPool::new(
+            Address::new([125; 20]),
+            Address::new([64; 20]),
+            BigUint::from(1000_u32),
+            BigUint::from(1000_u32),
+            30,
+        )
+        .unwrap();
+        let amm = AmmSimulator::new(vec![pool]);
+        // the buyer's residual 100 quote tokens are swapped for 90 base tokens
+        let solution = BatchAuction::default()
+            .with_amm(amm.clone())
+            .execute_runtime(intents.clone());
+        assert_eq!(solution.routes.len(), 1);
+        let swap_solver = SwapSolver::new();
+        assert_eq!(
+            validate_solution(&intents, &solution, &swap_solver, Some(&amm), 0),
+            Ok(())
+        );
+
+        // the route's pools are not known
+        assert_eq!(
+            validate_solution(&intents, &solution, &swap_solver, None, 0),
+            Err(SolutionError::InvalidRoute(0))
+        );
+        // the pool does not give the route's amount
+        let mut overpaid = solution.clone();
+        overpaid.routes[0].routes[0].amount_out = BigUint::from(95_u8);
+        assert_eq!(
+            validate_solution(&intents, &overpaid, &swap_solver, Some(&amm), 0),
+            Err(SolutionError::InvalidRoute(0))
+        );
+        // a pool for each token of the route
+        let mut extra_pool = solution.clone();
+        extra_pool.routes[0].routes[0].pools = vec![0, 0];
+        assert_eq!(
+            validate_solution(&intents, &extra_pool, &swap_solver, Some(&amm), 0),
+            Err(SolutionError::InvalidRoute(0))
+        );
+        // the same route twice, the second one swapping at the reserves the first one left
+        let mut repeated_route = solution;
+        let route = repeated_route.routes[0].routes[0].clone();
+        repeated_route.routes[0].routes.push(route);
+        assert_eq!(
+            validate_solution(&intents, &repeated_route, &swap_solver, Some(&amm), 0),
+            Err(SolutionError::InvalidRoute(0))
+        );
+
+        // competitions replay routes on their own snapshot
+        let competition = Competition::new().with_solver(
+            "batch auction",
+            BatchAuction::default().with_amm(amm.clone()),
+        );
+        assert_eq!(
+            competition.run(&intents).rejected,
+            vec![("batch auction".to_string(), SolutionError::InvalidRoute(0))]
+        );
+        let competition = competition.with_amm(amm);
+        assert!(competition.amm().is_some());
+        assert_eq!(competition.run(&intents).ranked.len(), 1);
+    }
+
+    #[test]
+    fn it_works_validate_solution_against_swap_solver() {
+        let mut intents = vec![
+            swap_intent(SwapDirection::Buy, 100, 50, true),
+            swap_intent(SwapDirection::Sell, 100, 50, true),
+        ];
+        // the second intent of the account is executed once its first is
+        intents[1].inputs.nonce = 1;
+        let solution = Solution {
+            fills: vec![
+                Fill {
+                    intent: 0,
+                    sold_amount: BigUint::from(100_u8),
+                    bought_amount: BigUint::from(50_u8),
+                },
+                Fill {
+                    intent: 1,
+                    sold_amount: BigUint::from(50_u8),
+                    bought_amount: BigUint::from(100_u8),
+                },
+            ],
+            ..Solution::default()
+        };
+        assert_eq!(
+            validate_solution(&intents, &solution, &SwapSolver::new(), None, 0),
+            Ok(())
+        );
+
+        let mut nonce_tracker = NonceTracker::new();
+        nonce_tracker.cancel_nonce(&token(255), 0);
+        let swap_solver = SwapSolver::new().with_nonce_tracker(nonce_tracker);
+        assert_eq!(
+            validate_solution(&intents, &solution, &swap_solver, None, 0),
+            Err(SolutionError::Cancelled(0))
+        );
+
+        let mut nonce_tracker = NonceTracker::new();
+        nonce_tracker
+            .use_nonce(&token(255), 0, u64::MAX, 0)
+            .unwrap();
+        let swap_solver = SwapSolver::new().with_nonce_tracker(nonce_tracker);
+        assert_eq!(
+            validate_solution(&intents, &solution, &swap_solver, None, 0),
+            Err(SolutionError::Replayed(
+                0,
+                ReplayError::NonceAlreadyUsed {
+                    account: token(255),
+                    nonce: 0
+                }
+            ))
+        );
+
+        let mut expiring = intents.clone();
+        expiring[1].constraints.deadline = 10;
+        assert_eq!(
+            validate_solution(&expiring, &solution, &SwapSolver::new(), None, 11),
+            Err(SolutionError::Replayed(
+                1,
+                ReplayError::Expired {
+                    deadline: 10,
+                    now: 11
+                }
+            ))
+        );
+
+        let mut fill_tracker = FillTracker::new();
+        fill_tracker
+            .record_fill(&intents[0], &BigUint::from(60_u8), &BigUint::from(30_u8))
+            .unwrap();
+        let swap_solver = SwapSolver::new().with_fill_tracker(fill_tracker);
+        assert_eq!(
+            validate_solution(&intents, &solution, &swap_solver, None, 0),
+            Err(SolutionError::Overfilled(0))
+        );
+    }
+
+    #[test]
+    fn it_works_competition() {
+        let intents = vec![
+            // buys at most at 2 quote tokens per base token
+            swap_intent(SwapDirection::Buy, 100, 50, true),
+            // sells at least at 1
+            swap_intent(SwapDirection::Sell, 30, 30, true),
+            // sells at least at 2
+            swap_intent(SwapDirection::Sell, 60, 30, true),
+        ];
+        let prices = ReferencePrices::new()
+            .with_price(Address::new([125; 20]), BigUint::from(1_u8))
+            .with_price(Address::new([64; 20]), BigUint::from(3_u8));
+
+        let ranking = Competition::new()
+            .with_solver("batch auction", BatchAuction::default())
+            .with_solver("swap solver", SwapSolver::new())
+            .with_solver("greedy", GreedySolver)
+            .with_scorer(FilledIntentsScorer)
+            .with_scorer(SurplusScorer::new(prices))
+            .run(&intents);
+
+        assert_eq!(
+            ranking.rejected,
+            vec![("greedy".to_string(), SolutionError::OutOfLimits(0))]
+        );
+        // both solvers fill every intent, the buyer keeping 15 base tokens of surplus with
+        // the swap solver's direct matches, and the first seller 30 quote tokens with the
+        // auction's uniform price of 2
+        let scores = ranking
+            .ranked
+            .iter()
+            .map(|ranked| {
+                let values = ranked
+                    .scores
+                    .iter()
+                    .map(|score| (score.metric, score.value.clone()))
+                    .collect::<Vec<_>>();
+                (ranked.solver.as_str(), values)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            scores,
+            vec![
+                (
+                    "swap solver",
+                    vec![
+                        ("filled_intents", BigUint::from(3_u8)),
+                        ("surplus", BigUint::from(45_u8))
+                    ]
+                ),
+                (
+                    "batch auction",
+                    vec![
+                        ("filled_intents", BigUint::from(3_u8)),
+                        ("surplus", BigUint::from(30_u8))
+                    ]
+                ),
+            ]
+        );
+        assert_eq!(ranking.winner().unwrap().solver, "swap solver");
+    }
+}
@@ -0,0 +1,148 @@
+use num_bigint::BigUint;
+use thiserror::Error;
+use types::types::Address;
+
+#[derive(Debug, Error)]
+pub enum TypedDataError {
+    #[error("Invalid typed data json: {0}")]
+    InvalidJson(#[from] serde_json::Error),
+    #[error("Unknown type {0}")]
+    UnknownType(String),
+    #[error("Missing value for member {0}")]
+    MissingMember(String),
+    #[error("Invalid value {1} for type {0}")]
+    InvalidValue(String, String),
+}
+
+#[derive(Debug, Error)]
+pub enum SerializationError {
+    #[error("Unsupported {0} format version {1}")]
+    UnsupportedVersion(&'static str, u8),
+    #[error("Invalid {0} encoding")]
+    InvalidEncoding(&'static str),
+    #[error("Verifier data has a Merkle cap of height {actual}, but the circuit's is {expected}")]
+    CapHeightMismatch { expected: usize, actual: usize },
+    #[error("Hex string should be 0x prefixed")]
+    MissingHexPrefix,
+    #[error("Invalid hex string: {0}")]
+    InvalidHex(#[from] hex::FromHexError),
+    #[error("Invalid json envelope: {0}")]
+    InvalidJson(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum VerificationError {
+    #[error("Circuit exposes {actual} public inputs, but {expected} are expected")]
+    UnexpectedCircuit { expected: usize, actual: usize },
+    #[error("Proof has {actual} public inputs, but {expected} are expected")]
+    PublicInputsCount { expected: usize, actual: usize },
+    #[error("Proof public inputs do not match the expected {0}")]
+    PublicInputMismatch(&'static str),
+    #[error("Invalid proof: {0}")]
+    InvalidProof(String),
+    #[error("Public inputs do not bind proofs to their intents: {0}")]
+    UnboundPublicInputs(&'static str),
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ReplayError {
+    #[error("Intent expired at {deadline}, it is now {now}")]
+    Expired { deadline: u64, now: u64 },
+    #[error("Intent of {account} with nonce {nonce} was cancelled")]
+    Cancelled { account: Address, nonce: u64 },
+    #[error("Nonce {nonce} of {account} was already used")]
+    NonceAlreadyUsed { account: Address, nonce: u64 },
+    #[error("Nonce {nonce} of {account} is out of order, expected {expected}")]
+    NonceOutOfOrder {
+        account: Address,
+        nonce: u64,
+        expected: u64,
+    },
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum FillError {
+    #[error("Intent is not partially fillable, and was already filled")]
+    AlreadyFilled,
+    #[error("Fill of {fill} overfills the intent, whose remaining size is {remaining}")]
+    Overfilled { fill: BigUint, remaining: BigUint },
+    #[error("Fill is not within the intent's constraints")]
+    OutOfLimits,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum IntentError {
+    #[error("Intent {field} of {amount} does not fit into an uint256")]
+    AmountOverflow {
+        field: &'static str,
+        amount: BigUint,
+    },
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PoolError {
+    #[error("Invalid intent signature: {0}")]
+    InvalidSignature(String),
+    #[error("Intent is from {from} but was signed by {signer}")]
+    WrongSigner { from: Address, signer: Address },
+    #[error(transparent)]
+    Intent(#[from] IntentError),
+    #[error("Intent is already in the pool")]
+    Duplicate,
+    #[error("Another intent of {account} with nonce {nonce} is already in the pool")]
+    NonceTaken { account: Address, nonce: u64 },
+    #[error(transparent)]
+    Replay(#[from] ReplayError),
+    #[error(
+        "Intents of {account} spend {required} of {token}, more than its balance of {balance}"
+    )]
+    InsufficientBalance {
+        account: Address,
+        token: Address,
+        required: BigUint,
+        balance: BigUint,
+    },
+}
+
+#[derive(Debug, Error)]
+pub enum AmmError {
+    #[error("Invalid AMM snapshot json: {0}")]
+    InvalidJson(#[from] serde_json::Error),
+    #[error("Invalid {0} in AMM snapshot: {1}")]
+    InvalidValue(&'static str, String),
+    #[error("No pool trades {token_in} for {token_out}")]
+    UnknownPool {
+        token_in: Address,
+        token_out: Address,
+    },
+    #[error("Pool does not trade {0}")]
+    UnknownToken(Address),
+    #[error("Pool cannot swap an amount of zero")]
+    ZeroAmount,
+    #[error("Pool does not hold enough liquidity for the swap")]
+    InsufficientLiquidity,
+    #[error("Swap gives {amount_out} tokens, below the order's limit")]
+    LimitNotMet { amount_out: BigUint },
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SolutionError {
+    #[error("Solution fills intent {0}, which is not in the batch")]
+    UnknownIntent(usize),
+    #[error("Fill of intent {0} is not within its limit")]
+    OutOfLimits(usize),
+    #[error("Fills of intent {0} exceed its remaining size")]
+    Overfilled(usize),
+    #[error("Fills within the batch sell and buy different amounts of {0}")]
+    Unbalanced(Address),
+    #[error("Routes of intent {0} do not trade its tokens")]
+    InvalidRoute(usize),
+    #[error("Intent {0} was cancelled by its sender")]
+    Cancelled(usize),
+    #[error("Intent {0} cannot be executed: {1}")]
+    Replayed(usize, ReplayError),
+    #[error("Clearing price {0} is zero, trades a token for itself or repeats a token pair")]
+    InvalidPrice(usize),
+    #[error("Fill of intent {0} is not at the clearing price of its token pair")]
+    OffPrice(usize),
+}
@@ -0,0 +1,162 @@
+//! Cumulative fills of swap intents across batches.
+//!
+//! An intent's fills are counted in the tokens its order sells (see [`SwapIntent::order`]). An intent
+//! which is not partially fillable is filled at most once, a partially fillable intent is
+//! filled by any number of fills, as long as their sum does not exceed its size.
+use std::collections::HashMap;
+
+use num_bigint::BigUint;
+
+use crate::{error::FillError, structured_hash::StructuredHashInterface, swap_intent::SwapIntent};
+
+/// Filled amount of every intent, by structured hash
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FillTracker {
+    filled: HashMap<[u8; 32], BigUint>,
+}
+
+impl FillTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Amount of `intent` filled so far
+    pub fn filled(&self, intent: &SwapIntent) -> BigUint {
+        self.filled
+            .get(&intent.structured_hash())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Amount of `intent` which can still be filled
+    pub fn remaining(&self, intent: &SwapIntent) -> BigUint {
+        let filled = self.filled(intent);
+        if filled >= *intent.size() {
+            return BigUint::default();
+        }
+        intent.size() - filled
+    }
+
+    /// Checks that `intent` can be filled by selling `sold_amount` tokens of its order for
+    /// `bought_amount` tokens, given its previous fills
+    pub fn check_fill(
+        &self,
+        intent: &SwapIntent,
+        sold_amount: &BigUint,
+        bought_amount: &BigUint,
+    ) -> Result<(), FillError> {
+        if !intent.order().accepts_fill(sold_amount, bought_amount) {
+            return Err(FillError::OutOfLimits);
+        }
+        let filled = self.filled(intent);
+        if !intent.constraints.partially_fillable {
+            if filled != BigUint::default() {
+                return Err(FillError::AlreadyFilled);
+            }
+            return Ok(());
+        }
+        let remaining = self.remaining(intent);
+        if sold_amount > &remaining {
+            return Err(FillError::Overfilled {
+                fill: sold_amount.clone(),
+                remaining,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks a fill as [`FillTracker::check_fill`] does, then records it
+    pub fn record_fill(
+        &mut self,
+        intent: &SwapIntent,
+        sold_amount: &BigUint,
+        bought_amount: &BigUint,
+    ) -> Result<(), FillError> {
+        self.check_fill(intent, sold_amount, bought_amount)?;
+        // an intent which is not partially fillable is filled at once
+        let fill = if intent.constraints.partially_fillable {
+            sold_amount.clone()
+        } else {
+            intent.size().clone()
+        };
+        *self.filled.entry(intent.structured_hash()).or_default() += fill;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{swap_intent::SwapDirection, test_utils::swap_intent};
+
+    use super::*;
+
+    #[test]
+    fn it_works_fill_tracker_partial_fills() {
+        // buys up to 100 quote tokens worth of base tokens, at 2 base tokens per quote token
+        let intent = swap_intent(SwapDirection::Buy, 100, 200, true);
+        let mut fill_tracker = FillTracker::new();
+
+        fill_tracker
+            .record_fill(&intent, &BigUint::from(30_u8), &BigUint::from(60_u8))
+            .unwrap();
+        // a better price than the limit price
+        fill_tracker
+            .record_fill(&intent, &BigUint::from(50_u8), &BigUint::from(110_u8))
+            .unwrap();
+        assert_eq!(fill_tracker.filled(&intent), BigUint::from(80_u8));
+        assert_eq!(fill_tracker.remaining(&intent), BigUint::from(20_u8));
+
+        assert_eq!(
+            fill_tracker.record_fill(&intent, &BigUint::from(10_u8), &BigUint::from(19_u8)),
+            Err(FillError::OutOfLimits)
+        );
+        assert_eq!(
+            fill_tracker.record_fill(&intent, &BigUint::from(30_u8), &BigUint::from(60_u8)),
+            Err(FillError::Overfilled {
+                fill: BigUint::from(30_u8),
+                remaining: BigUint::from(20_u8)
+            })
+        );
+        fill_tracker
+            .record_fill(&intent, &BigUint::from(20_u8), &BigUint::from(40_u8))
+            .unwrap();
+        assert_eq!(fill_tracker.remaining(&intent), BigUint::default());
+    }
+
+    #[test]
+    fn it_works_fill_tracker_sell_fills() {
+        // sells up to 200 base tokens, for at least 1 quote token per 2 base tokens
+        let intent = swap_intent(SwapDirection::Sell, 100, 200, true);
+        let mut fill_tracker = FillTracker::new();
+
+        // fills are given in the tokens the intent sells and buys
+        fill_tracker
+            .record_fill(&intent, &BigUint::from(100_u8), &BigUint::from(50_u8))
+            .unwrap();
+        assert_eq!(fill_tracker.remaining(&intent), BigUint::from(100_u8));
+        assert_eq!(
+            fill_tracker.check_fill(&intent, &BigUint::from(100_u8), &BigUint::from(49_u8)),
+            Err(FillError::OutOfLimits)
+        );
+    }
+
+    #[test]
+    fn it_fails_fill_tracker_refill() {
+        let intent = swap_intent(SwapDirection::Buy, 100, 200, false);
+        let mut fill_tracker = FillTracker::new();
+
+        // a fraction of an intent which is not partially fillable is out of its limits
+        assert_eq!(
+            fill_tracker.check_fill(&intent, &BigUint::from(50_u8), &BigUint::from(100_u8)),
+            Err(FillError::OutOfLimits)
+        );
+        fill_tracker
+            .record_fill(&intent, &BigUint::from(100_u8), &BigUint::from(200_u8))
+            .unwrap();
+        assert_eq!(fill_tracker.remaining(&intent), BigUint::default());
+        assert_eq!(
+            fill_tracker.record_fill(&intent, &BigUint::from(100_u8), &BigUint::from(200_u8)),
+            Err(FillError::AlreadyFilled)
+        );
+    }
+}
@@ -1,21 +1,26 @@
-use plonky2::{
-    iop::witness::PartialWitness,
-    plonk::{
-        circuit_builder::CircuitBuilder,
-        circuit_data::{CircuitConfig, CommonCircuitData, VerifierOnlyCircuitData},
-        config::GenericConfig,
-        proof::ProofWithPublicInputs,
-    },
+use anyhow::anyhow;
+use plonky2::plonk::{
+    circuit_data::{CommonCircuitData, VerifierCircuitData, VerifierOnlyCircuitData},
+    config::GenericConfig,
+    proof::ProofWithPublicInputs,
 };
-use types::types::{Message, PrivateKey, PublicKey, Signature};
+use types::types::{Address, Message, PrivateKey, PublicKey, RecoverableSignature, Signature};
 
 use crate::{
-    circuit::ECDSAIntentCircuit, structured_hash::StructuredHashInterface,
-    witness::ECDSAIntentWitness, D, F,
+    circuit::IntentCircuitInterface,
+    error::VerificationError,
+    prover::IntentSignatureProver,
+    public_inputs::{
+        intent_commitment, IntentPublicValues, PublicInputsOptions, SignerPublicInput,
+        SignerPublicValue,
+    },
+    structured_hash::{Eip712Domain, StructuredHashInterface},
+    D, F,
 };
 
 /// Reference enum from a label to the actual
 /// computation execution, and a proof generation
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum ExecuteRuntime {
     Swap,
 }
@@ -29,12 +34,35 @@ pub trait Intent {
         constraints: Self::Constraints,
         execute_runtime: ExecuteRuntime,
     ) -> Self;
-    fn sign_intent(&self, private_key: PrivateKey) -> Signature;
+    /// Signs the intent's EIP-712 signing hash in `domain`, as `eth_signTypedData_v4` does
+    fn sign_intent(&self, private_key: PrivateKey, domain: &Eip712Domain) -> RecoverableSignature;
     fn get_constraints(&self) -> Self::Constraints;
     fn get_inputs(&self) -> Self::Inputs;
+    /// Account which authorizes the intent, and should sign it
+    fn get_from(&self) -> Address;
     fn get_runtime_execution(&self) -> ExecuteRuntime;
 }
 
+/// Public key which signed `message_hash`, rejecting malleable signatures (with a high `s`),
+/// which recover but do not verify
+pub(crate) fn recover_signer(
+    message_hash: [u8; 32],
+    signature: &RecoverableSignature,
+) -> Result<PublicKey, anyhow::Error> {
+    let message = Message::new_message(message_hash);
+    let public_key = signature
+        .recover_public_key(&message)
+        .map_err(|e| anyhow!("{}", e))?;
+    if !libsecp256k1::verify(
+        message.as_libsecp256k1_message(),
+        signature.to_signature().as_libsecp256k1_signature(),
+        public_key.as_libsecp256k1_public_key(),
+    ) {
+        return Err(anyhow!("signature is malleable"));
+    }
+    Ok(public_key)
+}
+
 #[allow(dead_code)]
 pub struct SignatureProofData<C: GenericConfig<D, F = F>> {
     pub proof_with_pis: ProofWithPublicInputs<F, C, D>,
@@ -42,41 +70,393 @@ pub struct SignatureProofData<C: GenericConfig<D, F = F>> {
     pub verifier_only: VerifierOnlyCircuitData<C, D>,
 }
 
+/// Proves a single intent's signature, see [`IntentSignatureProver`] to prove many
 #[allow(dead_code)]
 pub fn generate_intent_signature_proof<C, I>(
     intent: I,
     public_key: PublicKey,
     signature: Signature,
+    domain: Eip712Domain,
+    public_inputs_options: PublicInputsOptions,
 ) -> Result<SignatureProofData<C>, anyhow::Error>
 where
     C: GenericConfig<D, F = F>,
-    I: StructuredHashInterface + Intent,
+    I: StructuredHashInterface + IntentCircuitInterface + Intent,
+{
+    IntentSignatureProver::<C, I>::new(domain, public_inputs_options)
+        .prove(&intent, public_key, signature)
+}
+
+/// Verifies an intent signature proof against the signature circuit's verifier data,
+/// without the prover's circuit data nor the witness. The proof should expose exactly
+/// the `expected` public values, see [`IntentSignatureVerifier`] for the verifier data.
+pub fn verify_intent_signature_proof<C>(
+    verifier_only: &VerifierOnlyCircuitData<C, D>,
+    common: &CommonCircuitData<F, D>,
+    proof_with_pis: &ProofWithPublicInputs<F, C, D>,
+    expected: &IntentPublicValues,
+) -> Result<(), VerificationError>
+where
+    C: GenericConfig<D, F = F>,
 {
-    let config = CircuitConfig::standard_ecc_config();
-    let mut circuit_builder = CircuitBuilder::new(config);
-    let mut partial_witness = PartialWitness::<F>::new();
-
-    let signature_targets = circuit_builder.verify_intent_signature();
-    let message = Message::from_slice(&intent.structured_hash())?;
-
-    let message_conversion = message.into_plonky2_message();
-    let public_key_conversion = public_key.into_plonky2_public_key();
-    let signature_conversion = signature.into_plonky2_signature();
-
-    partial_witness.verify_signed_intent(
-        &mut circuit_builder,
-        message_conversion,
-        public_key_conversion,
-        signature_conversion,
-        signature_targets,
-    );
-
-    let circuit_data = circuit_builder.build::<C>();
-    let proof_with_pis = circuit_data.prove(partial_witness.clone())?;
-
-    Ok(SignatureProofData {
-        proof_with_pis,
-        common: circuit_data.common,
-        verifier_only: circuit_data.verifier_only,
-    })
+    let expected_public_inputs = expected.labeled_field_elements();
+    let expected_len = expected_public_inputs
+        .iter()
+        .map(|(_, elements)| elements.len())
+        .sum();
+    if common.num_public_inputs != expected_len {
+        return Err(VerificationError::UnexpectedCircuit {
+            expected: expected_len,
+            actual: common.num_public_inputs,
+        });
+    }
+    if proof_with_pis.public_inputs.len() != expected_len {
+        return Err(VerificationError::PublicInputsCount {
+            expected: expected_len,
+            actual: proof_with_pis.public_inputs.len(),
+        });
+    }
+
+    let mut public_inputs = proof_with_pis.public_inputs.as_slice();
+    for (label, elements) in expected_public_inputs {
+        let (actual, rest) = public_inputs.split_at(elements.len());
+        if actual != elements {
+            return Err(VerificationError::PublicInputMismatch(label));
+        }
+        public_inputs = rest;
+    }
+
+    let verifier_data = VerifierCircuitData {
+        verifier_only: verifier_only.clone(),
+        common: common.clone(),
+    };
+    verifier_data
+        .verify(proof_with_pis.clone())
+        .map_err(|e| VerificationError::InvalidProof(e.to_string()))
+}
+
+/// Verifier of intent signature proofs against the signature circuit's verifier data, which
+/// derives the values each proof should expose from the intent it is given with.
+///
+/// Proofs are verified against the expected circuit's verifier data, and never against the
+/// one shipped with a proof: a proof of any circuit registering the same public inputs
+/// verifies against its own data. The crate's other verifiers likewise rebuild the circuit
+/// of the proof they verify, from the claimed statement, and compare its digest.
+pub struct IntentSignatureVerifier<C: GenericConfig<D, F = F>> {
+    verifier_data: VerifierCircuitData<F, C, D>,
+    public_inputs_options: PublicInputsOptions,
+}
+
+impl<C: GenericConfig<D, F = F>> IntentSignatureVerifier<C> {
+    /// Verifier of the signature circuit of `verifier_data`, exposing the values selected by
+    /// `public_inputs_options`. The message hash or the intent commitment should be exposed,
+    /// so that a proof is bound to its intent, and the signer should not be exposed as a
+    /// public key, which intents do not hold.
+    pub fn new(
+        verifier_data: VerifierCircuitData<F, C, D>,
+        public_inputs_options: PublicInputsOptions,
+    ) -> Result<Self, VerificationError> {
+        if !public_inputs_options.message_hash && !public_inputs_options.intent_commitment {
+            return Err(VerificationError::UnboundPublicInputs(
+                "neither the message hash nor the intent commitment is exposed",
+            ));
+        }
+        if public_inputs_options.signer == SignerPublicInput::PublicKey {
+            return Err(VerificationError::UnboundPublicInputs(
+                "the signer public key cannot be derived from the intent",
+            ));
+        }
+        Ok(Self {
+            verifier_data,
+            public_inputs_options,
+        })
+    }
+
+    pub fn verifier_data(&self) -> &VerifierCircuitData<F, C, D> {
+        &self.verifier_data
+    }
+
+    pub fn public_inputs_options(&self) -> PublicInputsOptions {
+        self.public_inputs_options
+    }
+
+    /// Values a proof of a signature of `intent` by its sender should expose
+    pub fn expected_public_values<I>(&self, intent: &I) -> IntentPublicValues
+    where
+        I: StructuredHashInterface + Intent,
+    {
+        let structured_hash = intent.structured_hash();
+        let signer = (self.public_inputs_options.signer == SignerPublicInput::Address)
+            .then(|| SignerPublicValue::Address(intent.get_from()));
+        IntentPublicValues {
+            message_hash: self
+                .public_inputs_options
+                .message_hash
+                .then_some(structured_hash),
+            signer,
+            intent_commitment: self
+                .public_inputs_options
+                .intent_commitment
+                .then(|| intent_commitment(&structured_hash)),
+        }
+    }
+
+    /// Verifies that `proof_with_pis` proves a signature of `intent` by its sender
+    pub fn verify<I>(
+        &self,
+        intent: &I,
+        proof_with_pis: &ProofWithPublicInputs<F, C, D>,
+    ) -> Result<(), VerificationError>
+    where
+        I: StructuredHashInterface + Intent,
+    {
+        verify_intent_signature_proof(
+            &self.verifier_data.verifier_only,
+            &self.verifier_data.common,
+            proof_with_pis,
+            &self.expected_public_values(intent),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::{
+        field::types::{Field, PrimeField},
+        iop::witness::PartialWitness,
+        plonk::{
+            circuit_builder::CircuitBuilder, circuit_data::CircuitConfig,
+            config::PoseidonGoldilocksConfig,
+        },
+    };
+    use plonky2_ecdsa::{
+        curve::ecdsa::ECDSAPublicKey,
+        gadgets::{
+            biguint::WitnessBigUint, curve::CircuitBuilderCurve, ecdsa::ECDSAPublicKeyTarget,
+            nonnative::CircuitBuilderNonNative,
+        },
+    };
+
+    use crate::{
+        circuit::ECDSAIntentCircuit,
+        keccak::{CircuitBuilderKeccak, WitnessKeccak},
+        test_utils::{proven_swap_intents, signature_prover, ProvenSwapIntent},
+    };
+
+    use super::*;
+
+    type C = PoseidonGoldilocksConfig;
+
+    /// Proof of a circuit exposing the public inputs of the signature circuit,
+    /// without verifying any signature
+    fn public_inputs_proof(
+        options: &PublicInputsOptions,
+        structured_hash: [u8; 32],
+        public_key: &PublicKey,
+    ) -> SignatureProofData<C> {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let mut pw = PartialWitness::<F>::new();
+
+        let structured_hash_target = builder.add_virtual_bytes_target(32);
+        let public_key_target = ECDSAPublicKeyTarget(builder.add_virtual_affine_point_target());
+        let address_target = builder.add_virtual_address_target();
+        builder.register_intent_public_inputs(
+            &structured_hash_target,
+            &public_key_target,
+            &address_target,
+            options,
+        );
+
+        let ECDSAPublicKey(public_key_point) = public_key.clone().into_plonky2_public_key();
+        let x_biguint_target = builder.nonnative_to_canonical_biguint(&public_key_target.0.x);
+        let y_biguint_target = builder.nonnative_to_canonical_biguint(&public_key_target.0.y);
+        pw.set_biguint_target(
+            &x_biguint_target,
+            &public_key_point.x.to_canonical_biguint(),
+        );
+        pw.set_biguint_target(
+            &y_biguint_target,
+            &public_key_point.y.to_canonical_biguint(),
+        );
+        pw.set_bytes_target(&structured_hash_target, &structured_hash);
+        pw.set_bytes_target(
+            &address_target.0,
+            Address::from_public_key(public_key).as_bytes(),
+        );
+
+        let data = builder.build::<C>();
+        SignatureProofData {
+            proof_with_pis: data.prove(pw).unwrap(),
+            common: data.common,
+            verifier_only: data.verifier_only,
+        }
+    }
+
+    #[test]
+    fn it_works_verify_intent_signature_proof() {
+        let public_key = PublicKey::from_private_key(&PrivateKey::new());
+        let structured_hash = [3u8; 32];
+        let options = PublicInputsOptions::all();
+        let SignatureProofData {
+            proof_with_pis,
+            common,
+            verifier_only,
+        } = public_inputs_proof(&options, structured_hash, &public_key);
+
+        let expected = IntentPublicValues::new(&options, structured_hash, &public_key);
+        verify_intent_signature_proof(&verifier_only, &common, &proof_with_pis, &expected).unwrap();
+
+        let other_public_key = PublicKey::from_private_key(&PrivateKey::new());
+        let other_signer = IntentPublicValues::new(&options, structured_hash, &other_public_key);
+        assert!(matches!(
+            verify_intent_signature_proof(&verifier_only, &common, &proof_with_pis, &other_signer),
+            Err(VerificationError::PublicInputMismatch("signer address"))
+        ));
+
+        let fewer_values = IntentPublicValues::new(
+            &PublicInputsOptions::default(),
+            structured_hash,
+            &public_key,
+        );
+        assert!(matches!(
+            verify_intent_signature_proof(&verifier_only, &common, &proof_with_pis, &fewer_values),
+            Err(VerificationError::UnexpectedCircuit { .. })
+        ));
+
+        // public inputs which are not the proven ones
+        let mut forged_proof = proof_with_pis;
+        forged_proof.public_inputs[0] += F::ONE;
+        let mut forged_hash = structured_hash;
+        forged_hash[3] += 1;
+        let forged = IntentPublicValues {
+            message_hash: Some(forged_hash),
+            ..expected
+        };
+        assert!(matches!(
+            verify_intent_signature_proof(&verifier_only, &common, &forged_proof, &forged),
+            Err(VerificationError::InvalidProof(_))
+        ));
+    }
+
+    #[test]
+    fn it_works_verify_intent_signature_proof_of_signature_circuit() {
+        let prover = signature_prover();
+        let ProvenSwapIntent {
+            intent,
+            public_key,
+            signature_proof_data,
+        } = &proven_swap_intents()[0];
+        let expected = IntentPublicValues::new(
+            &prover.public_inputs_options(),
+            intent.structured_hash(),
+            public_key,
+        );
+        verify_intent_signature_proof(
+            prover.verifier_only_data(),
+            prover.common_data(),
+            &signature_proof_data.proof_with_pis,
+            &expected,
+        )
+        .unwrap();
+
+        // the proof of another intent
+        let ProvenSwapIntent {
+            intent: other_intent,
+            public_key: other_public_key,
+            ..
+        } = &proven_swap_intents()[1];
+        let other_intent_values = IntentPublicValues::new(
+            &prover.public_inputs_options(),
+            other_intent.structured_hash(),
+            other_public_key,
+        );
+        assert!(matches!(
+            verify_intent_signature_proof(
+                prover.verifier_only_data(),
+                prover.common_data(),
+                &signature_proof_data.proof_with_pis,
+                &other_intent_values,
+            ),
+            Err(VerificationError::PublicInputMismatch("message hash"))
+        ));
+    }
+
+    #[test]
+    fn it_fails_verify_intent_signature_proof_of_other_circuit() {
+        let prover = signature_prover();
+        let ProvenSwapIntent {
+            intent,
+            public_key,
+            signature_proof_data,
+        } = &proven_swap_intents()[0];
+        let options = prover.public_inputs_options();
+        let expected = IntentPublicValues::new(&options, intent.structured_hash(), public_key);
+
+        // exposes the same public inputs, without verifying any signature
+        let other_circuit_proof =
+            public_inputs_proof(&options, intent.structured_hash(), public_key);
+        assert_eq!(
+            other_circuit_proof.proof_with_pis.public_inputs,
+            signature_proof_data.proof_with_pis.public_inputs
+        );
+        // it verifies against its own verifier data, but not against the signature circuit's
+        verify_intent_signature_proof(
+            &other_circuit_proof.verifier_only,
+            &other_circuit_proof.common,
+            &other_circuit_proof.proof_with_pis,
+            &expected,
+        )
+        .unwrap();
+        assert!(matches!(
+            verify_intent_signature_proof(
+                prover.verifier_only_data(),
+                prover.common_data(),
+                &other_circuit_proof.proof_with_pis,
+                &expected,
+            ),
+            Err(VerificationError::InvalidProof(_))
+        ));
+    }
+
+    #[test]
+    fn it_works_intent_signature_verifier() {
+        let prover = signature_prover();
+        let verifier = prover.verifier().unwrap();
+        let ProvenSwapIntent {
+            intent,
+            public_key,
+            signature_proof_data,
+        } = &proven_swap_intents()[0];
+        verifier
+            .verify(intent, &signature_proof_data.proof_with_pis)
+            .unwrap();
+
+        // the proof of another intent
+        let other_intent = &proven_swap_intents()[1].intent;
+        assert!(matches!(
+            verifier.verify(other_intent, &signature_proof_data.proof_with_pis),
+            Err(VerificationError::PublicInputMismatch("message hash"))
+        ));
+        // a proof of another circuit exposing the intent's values
+        let other_circuit_proof = public_inputs_proof(
+            &verifier.public_inputs_options(),
+            intent.structured_hash(),
+            public_key,
+        );
+        assert!(matches!(
+            verifier.verify(intent, &other_circuit_proof.proof_with_pis),
+            Err(VerificationError::InvalidProof(_))
+        ));
+
+        // public inputs which do not bind a proof to its intent
+        for options in [
+            PublicInputsOptions::new(false, SignerPublicInput::Address, false),
+            PublicInputsOptions::new(true, SignerPublicInput::PublicKey, true),
+        ] {
+            assert!(matches!(
+                IntentSignatureVerifier::new(prover.verifier_data(), options),
+                Err(VerificationError::UnboundPublicInputs(_))
+            ));
+        }
+    }
 }
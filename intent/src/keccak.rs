@@ -0,0 +1,330 @@
+//! Keccak-256 gadget, as used by Ethereum (with the original `0x01` padding, not SHA-3's).
+//!
+//! Byte strings are represented in the circuit as bits, 8 bits per byte, least significant
+//! bit first. This is the bit order of the Keccak state, so that absorbing a block and
+//! squeezing the digest do not involve any reordering. Within the permutation, bits are
+//! replaced by their signs `(-1)^b`, xor-ing two bits then being a single multiplication,
+//! and chi is applied by the [`KeccakChiGate`].
+use plonky2::{
+    field::types::Field,
+    iop::{
+        target::{BoolTarget, Target},
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::circuit_builder::CircuitBuilder,
+};
+use plonky2_ecdsa::gadgets::biguint::BigUintTarget;
+use plonky2_u32::gadgets::arithmetic_u32::U32Target;
+
+use crate::{
+    keccak_chi_gate::{KeccakChiGate, ROW_BITS},
+    D, F,
+};
+
+/// Size, in bytes, of a Keccak-256 input block
+pub const KECCAK256_RATE: usize = 136;
+/// Size, in bytes, of a Keccak-256 digest
+pub const KECCAK256_OUTPUT: usize = 32;
+
+const KECCAK_ROUNDS: usize = 24;
+const LANE_BITS: usize = 64;
+const STATE_BITS: usize = 25 * LANE_BITS;
+
+const ROUND_CONSTANTS: [u64; KECCAK_ROUNDS] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808A,
+    0x8000000080008000,
+    0x000000000000808B,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008A,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000A,
+    0x000000008000808B,
+    0x800000000000008B,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800A,
+    0x800000008000000A,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+
+/// Rotation offsets of the rho step, indexed by `[x][y]`
+const ROTATION_OFFSETS: [[usize; 5]; 5] = [
+    [0, 36, 3, 41, 18],
+    [1, 44, 10, 45, 2],
+    [62, 6, 43, 15, 61],
+    [28, 55, 25, 21, 56],
+    [27, 20, 39, 8, 14],
+];
+
+/// Index in the state of bit `z` of lane `(x, y)`
+fn state_index(x: usize, y: usize, z: usize) -> usize {
+    LANE_BITS * (x + 5 * y) + z
+}
+
+pub trait CircuitBuilderKeccak {
+    /// Adds a virtual byte string of `num_bytes` bytes, each bit being range checked
+    fn add_virtual_bytes_target(&mut self, num_bytes: usize) -> Vec<BoolTarget>;
+    /// Constant byte string
+    fn constant_bytes(&mut self, bytes: &[u8]) -> Vec<BoolTarget>;
+    fn connect_bytes(&mut self, a: &[BoolTarget], b: &[BoolTarget]);
+    /// Big endian encoding of `value` on `num_bytes` bytes, `value` should fit into them
+    fn biguint_to_bytes_be(&mut self, value: &BigUintTarget, num_bytes: usize) -> Vec<BoolTarget>;
+    /// Integer whose big endian encoding is the byte string `bytes`
+    fn bytes_be_to_biguint(&mut self, bytes: &[BoolTarget]) -> BigUintTarget;
+    /// Packs a byte string, whose length is a multiple of 4, into big endian 32-bit words
+    fn bytes_to_u32_words_be(&mut self, bytes: &[BoolTarget]) -> Vec<Target>;
+    /// Keccak-256 digest of a byte string, whose length is fixed by the circuit
+    fn keccak256(&mut self, input: &[BoolTarget]) -> Vec<BoolTarget>;
+}
+
+impl CircuitBuilderKeccak for CircuitBuilder<F, D> {
+    fn add_virtual_bytes_target(&mut self, num_bytes: usize) -> Vec<BoolTarget> {
+        (0..8 * num_bytes)
+            .map(|_| self.add_virtual_bool_target_safe())
+            .collect()
+    }
+
+    fn constant_bytes(&mut self, bytes: &[u8]) -> Vec<BoolTarget> {
+        bytes
+            .iter()
+            .flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1 == 1))
+            .map(|bit| self.constant_bool(bit))
+            .collect()
+    }
+
+    fn connect_bytes(&mut self, a: &[BoolTarget], b: &[BoolTarget]) {
+        assert_eq!(a.len(), b.len(), "byte strings should have the same length");
+        for (a, b) in a.iter().zip(b) {
+            self.connect(a.target, b.target);
+        }
+    }
+
+    fn biguint_to_bytes_be(&mut self, value: &BigUintTarget, num_bytes: usize) -> Vec<BoolTarget> {
+        assert!(
+            value.num_limbs() * 4 <= num_bytes,
+            "value does not fit into {} bytes",
+            num_bytes
+        );
+        // little endian bits of the whole value, splitting also range checks the limbs
+        let mut bits = value
+            .limbs
+            .iter()
+            .flat_map(|limb| self.split_le(limb.0, 32))
+            .collect::<Vec<_>>();
+        bits.resize(8 * num_bytes, self._false());
+        bits.chunks(8).rev().flatten().copied().collect()
+    }
+
+    fn bytes_be_to_biguint(&mut self, bytes: &[BoolTarget]) -> BigUintTarget {
+        assert_eq!(
+            bytes.len() % 32,
+            0,
+            "byte string should be made of 32-bit limbs"
+        );
+        let bits = bytes.chunks(8).rev().flatten().copied().collect::<Vec<_>>();
+        let limbs = bits
+            .chunks(32)
+            .map(|limb_bits| U32Target(self.le_sum(limb_bits.iter())))
+            .collect();
+        BigUintTarget { limbs }
+    }
+
+    fn bytes_to_u32_words_be(&mut self, bytes: &[BoolTarget]) -> Vec<Target> {
+        assert_eq!(
+            bytes.len() % 32,
+            0,
+            "byte string should be made of 32-bit words"
+        );
+        bytes
+            .chunks(32)
+            .map(|word| {
+                let bits = word.chunks(8).rev().flatten().copied().collect::<Vec<_>>();
+                self.le_sum(bits.iter())
+            })
+            .collect()
+    }
+
+    fn keccak256(&mut self, input: &[BoolTarget]) -> Vec<BoolTarget> {
+        assert_eq!(input.len() % 8, 0, "input should be a byte string");
+        let input_length = input.len() / 8;
+
+        // pad10*1, with Keccak's `0x01` domain byte
+        let num_blocks = input_length / KECCAK256_RATE + 1;
+        let mut padding = vec![0u8; num_blocks * KECCAK256_RATE - input_length];
+        padding[0] |= 0x01;
+        *padding.last_mut().expect("padding is not empty") |= 0x80;
+        let padding = self.constant_bytes(&padding);
+        let padded_input = [input, padding.as_slice()].concat();
+
+        let one = self.one();
+        let mut state = vec![one; STATE_BITS];
+        for block in padded_input.chunks(8 * KECCAK256_RATE) {
+            for (state_sign, block_bit) in state.iter_mut().zip(block) {
+                let block_sign = bit_to_sign(self, *block_bit);
+                *state_sign = self.mul(*state_sign, block_sign);
+            }
+            keccak_f(self, &mut state);
+        }
+
+        state[..8 * KECCAK256_OUTPUT]
+            .iter()
+            .map(|sign| sign_to_bit(self, *sign))
+            .collect()
+    }
+}
+
+/// Maps a bit `b` to `(-1)^b`, so that xor-ing bits amounts to multiplying their signs
+fn bit_to_sign(circuit_builder: &mut CircuitBuilder<F, D>, bit: BoolTarget) -> Target {
+    let one = circuit_builder.one();
+    circuit_builder.arithmetic(-F::TWO, F::ONE, bit.target, one, one)
+}
+
+fn sign_to_bit(circuit_builder: &mut CircuitBuilder<F, D>, sign: Target) -> BoolTarget {
+    let one = circuit_builder.one();
+    let half = F::TWO.inverse();
+    BoolTarget::new_unsafe(circuit_builder.arithmetic(-half, half, sign, one, one))
+}
+
+/// Keccak-f[1600] permutation, over the signs `(-1)^b` of the state bits
+fn keccak_f(circuit_builder: &mut CircuitBuilder<F, D>, state: &mut [Target]) {
+    assert_eq!(state.len(), STATE_BITS);
+    let chi_gate = KeccakChiGate::new_from_config(&circuit_builder.config);
+
+    for round_constant in ROUND_CONSTANTS {
+        // theta's column parities, applied to the state by the chi gate
+        let mut column_parities = Vec::with_capacity(5 * LANE_BITS);
+        for x in 0..5 {
+            for z in 0..LANE_BITS {
+                let column = (0..5).map(|y| state[state_index(x, y, z)]);
+                column_parities.push(circuit_builder.mul_many(column));
+            }
+        }
+        let mut parities = Vec::with_capacity(5 * LANE_BITS);
+        for x in 0..5 {
+            for z in 0..LANE_BITS {
+                let left = column_parities[LANE_BITS * ((x + 4) % 5) + z];
+                let right =
+                    column_parities[LANE_BITS * ((x + 1) % 5) + (z + LANE_BITS - 1) % LANE_BITS];
+                parities.push(circuit_builder.mul(left, right));
+            }
+        }
+
+        // rho and pi, which only move bits around
+        let mut permuted = state.to_vec();
+        let mut permuted_parities = state.to_vec();
+        for x in 0..5 {
+            for y in 0..5 {
+                let offset = ROTATION_OFFSETS[x][y];
+                for z in 0..LANE_BITS {
+                    let index = state_index(y, (2 * x + 3 * y) % 5, (z + offset) % LANE_BITS);
+                    permuted[index] = state[state_index(x, y, z)];
+                    permuted_parities[index] = parities[LANE_BITS * x + z];
+                }
+            }
+        }
+
+        // chi, on rows of 5 bits
+        for y in 0..5 {
+            for z in 0..LANE_BITS {
+                let (row, op) = circuit_builder.find_slot(chi_gate.clone(), &[], &[]);
+                for x in 0..ROW_BITS {
+                    let index = state_index(x, y, z);
+                    circuit_builder.connect(
+                        permuted[index],
+                        Target::wire(row, KeccakChiGate::wire_ith_input(op, x)),
+                    );
+                    circuit_builder.connect(
+                        permuted_parities[index],
+                        Target::wire(row, KeccakChiGate::wire_ith_parity(op, x)),
+                    );
+                    state[index] = Target::wire(row, KeccakChiGate::wire_ith_output(op, x));
+                }
+            }
+        }
+
+        // iota
+        for z in (0..LANE_BITS).filter(|z| (round_constant >> z) & 1 == 1) {
+            state[z] = circuit_builder.neg(state[z]);
+        }
+    }
+}
+
+pub trait WitnessKeccak {
+    fn set_bytes_target(&mut self, target: &[BoolTarget], bytes: &[u8]);
+}
+
+impl WitnessKeccak for PartialWitness<F> {
+    fn set_bytes_target(&mut self, target: &[BoolTarget], bytes: &[u8]) {
+        assert_eq!(target.len(), 8 * bytes.len());
+        for (byte_target, byte) in target.chunks(8).zip(bytes) {
+            for (i, bit_target) in byte_target.iter().enumerate() {
+                self.set_bool_target(*bit_target, (byte >> i) & 1 == 1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use keccak_hash::keccak;
+    use num_bigint::BigUint;
+    use plonky2::plonk::{circuit_data::CircuitConfig, config::PoseidonGoldilocksConfig};
+    use plonky2_ecdsa::gadgets::biguint::{CircuitBuilderBiguint, WitnessBigUint};
+
+    use super::*;
+
+    type C = PoseidonGoldilocksConfig;
+
+    #[test]
+    fn it_works_keccak256_circuit() {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let mut pw = PartialWitness::<F>::new();
+
+        // a single block, and a full block whose padding spans another block
+        let inputs = [
+            vec![42u8; 64],
+            (0..KECCAK256_RATE).map(|i| i as u8).collect(),
+        ];
+        for input in inputs {
+            let input_target = builder.add_virtual_bytes_target(input.len());
+            pw.set_bytes_target(&input_target, &input);
+            let digest_target = builder.keccak256(&input_target);
+            let expected_digest_target = builder.constant_bytes(keccak(&input).as_bytes());
+            builder.connect_bytes(&digest_target, &expected_digest_target);
+        }
+
+        let data = builder.build::<C>();
+        let proof = data.prove(pw).unwrap();
+        data.verify(proof).expect("Failed to verify proof data")
+    }
+
+    #[test]
+    fn it_works_biguint_bytes_be_conversion() {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let mut pw = PartialWitness::<F>::new();
+
+        let value_target = builder.add_virtual_biguint_target(2);
+        pw.set_biguint_target(&value_target, &BigUint::from(0x0102030405060708_u64));
+        let bytes_target = builder.biguint_to_bytes_be(&value_target, 12);
+        let expected_bytes_target = builder.constant_bytes(&[0, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8]);
+        builder.connect_bytes(&bytes_target, &expected_bytes_target);
+        let round_trip_target = builder.bytes_be_to_biguint(&bytes_target);
+        builder.connect_biguint(&round_trip_target, &value_target);
+
+        let data = builder.build::<C>();
+        let proof = data.prove(pw).unwrap();
+        data.verify(proof).expect("Failed to verify proof data")
+    }
+}
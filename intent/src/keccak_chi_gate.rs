@@ -0,0 +1,248 @@
+//! Gate applying the end of a Keccak-f round, theta's parity and chi, to rows of 5 state bits.
+//!
+//! State bits are represented by their signs `(-1)^b`. For each bit `j` of a row, the gate
+//! takes the bit before theta `s'_j` and the theta column parity `d_j` it is combined with, and
+//! outputs chi's result `s_j * (1 + p_{j+1} * p_{j+2} / 2 - p_{j+1})`, where `s_j = s'_j * d_j`
+//! and `p_j = 1 + s_j`. This is `a ^ (!b & c)` over signs, as `p_j = 2 * (1 - b_j)`.
+use plonky2::{
+    field::{extension::Extendable, types::Field},
+    gates::{gate::Gate, util::StridedConstraintConsumer},
+    hash::hash_types::RichField,
+    iop::{
+        ext_target::ExtensionTarget,
+        generator::{GeneratedValues, SimpleGenerator, WitnessGenerator},
+        target::Target,
+        witness::{PartitionWitness, Witness, WitnessWrite},
+    },
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::CircuitConfig,
+        vars::{EvaluationTargets, EvaluationVars, EvaluationVarsBase},
+    },
+};
+
+/// Number of bits in a row of the Keccak state
+pub const ROW_BITS: usize = 5;
+
+/// Routed wires of an operation: the row's bits before theta, their parities and the outputs
+const ROUTED_WIRES_PER_OP: usize = 3 * ROW_BITS;
+/// All wires of an operation, including the row's bits after theta
+const WIRES_PER_OP: usize = 4 * ROW_BITS;
+
+#[derive(Clone, Debug)]
+pub struct KeccakChiGate {
+    /// Number of rows processed by the gate
+    pub num_ops: usize,
+}
+
+impl KeccakChiGate {
+    pub fn new_from_config(config: &CircuitConfig) -> Self {
+        Self {
+            num_ops: Self::num_ops(config),
+        }
+    }
+
+    /// Maximum number of rows that fit into one gate for the given config.
+    fn num_ops(config: &CircuitConfig) -> usize {
+        (config.num_routed_wires / ROUTED_WIRES_PER_OP).min(config.num_wires / WIRES_PER_OP)
+    }
+
+    pub fn wire_ith_input(i: usize, j: usize) -> usize {
+        ROUTED_WIRES_PER_OP * i + j
+    }
+    pub fn wire_ith_parity(i: usize, j: usize) -> usize {
+        ROUTED_WIRES_PER_OP * i + ROW_BITS + j
+    }
+    pub fn wire_ith_output(i: usize, j: usize) -> usize {
+        ROUTED_WIRES_PER_OP * i + 2 * ROW_BITS + j
+    }
+    /// Bits after theta, which do not need to be routed
+    fn wire_ith_theta_output(&self, i: usize, j: usize) -> usize {
+        ROUTED_WIRES_PER_OP * self.num_ops + ROW_BITS * i + j
+    }
+}
+
+/// Chi's output for the bit `j` of a row, from the row's bits after theta
+fn chi<F: Field>(theta_outputs: &[F], j: usize) -> F {
+    let b = F::ONE + theta_outputs[(j + 1) % ROW_BITS];
+    let c = F::ONE + theta_outputs[(j + 2) % ROW_BITS];
+    let v = b * c * F::TWO.inverse() - b;
+    theta_outputs[j] * (F::ONE + v)
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for KeccakChiGate {
+    fn id(&self) -> String {
+        format!("{self:?}")
+    }
+
+    fn eval_unfiltered(&self, vars: EvaluationVars<F, D>) -> Vec<F::Extension> {
+        let mut constraints = Vec::with_capacity(2 * ROW_BITS * self.num_ops);
+        for i in 0..self.num_ops {
+            let theta_outputs = (0..ROW_BITS)
+                .map(|j| vars.local_wires[self.wire_ith_theta_output(i, j)])
+                .collect::<Vec<_>>();
+            for j in 0..ROW_BITS {
+                let input = vars.local_wires[Self::wire_ith_input(i, j)];
+                let parity = vars.local_wires[Self::wire_ith_parity(i, j)];
+                let output = vars.local_wires[Self::wire_ith_output(i, j)];
+                constraints.push(theta_outputs[j] - input * parity);
+                constraints.push(output - chi(&theta_outputs, j));
+            }
+        }
+        constraints
+    }
+
+    fn eval_unfiltered_base_one(
+        &self,
+        vars: EvaluationVarsBase<F>,
+        mut yield_constr: StridedConstraintConsumer<F>,
+    ) {
+        for i in 0..self.num_ops {
+            let theta_outputs = (0..ROW_BITS)
+                .map(|j| vars.local_wires[self.wire_ith_theta_output(i, j)])
+                .collect::<Vec<_>>();
+            for j in 0..ROW_BITS {
+                let input = vars.local_wires[Self::wire_ith_input(i, j)];
+                let parity = vars.local_wires[Self::wire_ith_parity(i, j)];
+                let output = vars.local_wires[Self::wire_ith_output(i, j)];
+                yield_constr.one(theta_outputs[j] - input * parity);
+                yield_constr.one(output - chi(&theta_outputs, j));
+            }
+        }
+    }
+
+    fn eval_unfiltered_circuit(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+        vars: EvaluationTargets<D>,
+    ) -> Vec<ExtensionTarget<D>> {
+        let half = F::TWO.inverse();
+        let mut constraints = Vec::with_capacity(2 * ROW_BITS * self.num_ops);
+        for i in 0..self.num_ops {
+            let theta_outputs = (0..ROW_BITS)
+                .map(|j| vars.local_wires[self.wire_ith_theta_output(i, j)])
+                .collect::<Vec<_>>();
+            let offsets = theta_outputs
+                .iter()
+                .map(|theta_output| builder.add_const_extension(*theta_output, F::ONE))
+                .collect::<Vec<_>>();
+            for j in 0..ROW_BITS {
+                let input = vars.local_wires[Self::wire_ith_input(i, j)];
+                let parity = vars.local_wires[Self::wire_ith_parity(i, j)];
+                let output = vars.local_wires[Self::wire_ith_output(i, j)];
+                constraints.push(builder.arithmetic_extension(
+                    F::NEG_ONE,
+                    F::ONE,
+                    input,
+                    parity,
+                    theta_outputs[j],
+                ));
+
+                let b = offsets[(j + 1) % ROW_BITS];
+                let c = offsets[(j + 2) % ROW_BITS];
+                let v = builder.arithmetic_extension(half, F::NEG_ONE, b, c, b);
+                let chi = builder.mul_add_extension(theta_outputs[j], v, theta_outputs[j]);
+                constraints.push(builder.sub_extension(output, chi));
+            }
+        }
+        constraints
+    }
+
+    fn generators(&self, row: usize, _local_constants: &[F]) -> Vec<Box<dyn WitnessGenerator<F>>> {
+        (0..self.num_ops)
+            .map(|i| {
+                let generator: Box<dyn WitnessGenerator<F>> = Box::new(
+                    KeccakChiGenerator {
+                        gate: self.clone(),
+                        row,
+                        i,
+                    }
+                    .adapter(),
+                );
+                generator
+            })
+            .collect()
+    }
+
+    fn num_wires(&self) -> usize {
+        WIRES_PER_OP * self.num_ops
+    }
+
+    fn num_constants(&self) -> usize {
+        0
+    }
+
+    fn degree(&self) -> usize {
+        3
+    }
+
+    fn num_constraints(&self) -> usize {
+        2 * ROW_BITS * self.num_ops
+    }
+}
+
+#[derive(Clone, Debug)]
+struct KeccakChiGenerator {
+    gate: KeccakChiGate,
+    row: usize,
+    i: usize,
+}
+
+impl<F: RichField> SimpleGenerator<F> for KeccakChiGenerator {
+    fn dependencies(&self) -> Vec<Target> {
+        (0..ROW_BITS)
+            .flat_map(|j| {
+                [
+                    KeccakChiGate::wire_ith_input(self.i, j),
+                    KeccakChiGate::wire_ith_parity(self.i, j),
+                ]
+            })
+            .map(|wire| Target::wire(self.row, wire))
+            .collect()
+    }
+
+    fn run_once(&self, witness: &PartitionWitness<F>, out_buffer: &mut GeneratedValues<F>) {
+        let get_wire = |wire: usize| -> F { witness.get_target(Target::wire(self.row, wire)) };
+
+        let theta_outputs = (0..ROW_BITS)
+            .map(|j| {
+                get_wire(KeccakChiGate::wire_ith_input(self.i, j))
+                    * get_wire(KeccakChiGate::wire_ith_parity(self.i, j))
+            })
+            .collect::<Vec<F>>();
+        for j in 0..ROW_BITS {
+            out_buffer.set_target(
+                Target::wire(self.row, self.gate.wire_ith_theta_output(self.i, j)),
+                theta_outputs[j],
+            );
+            out_buffer.set_target(
+                Target::wire(self.row, KeccakChiGate::wire_ith_output(self.i, j)),
+                chi(&theta_outputs, j),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::{
+        gates::gate_testing::{test_eval_fns, test_low_degree},
+        plonk::config::PoseidonGoldilocksConfig,
+    };
+
+    use crate::{D, F};
+
+    use super::*;
+
+    #[test]
+    fn it_works_keccak_chi_gate_low_degree() {
+        let gate = KeccakChiGate::new_from_config(&CircuitConfig::standard_ecc_config());
+        test_low_degree::<F, _, 4>(gate);
+    }
+
+    #[test]
+    fn it_works_keccak_chi_gate_eval_fns() -> Result<(), anyhow::Error> {
+        let gate = KeccakChiGate::new_from_config(&CircuitConfig::standard_ecc_config());
+        test_eval_fns::<F, PoseidonGoldilocksConfig, _, D>(gate)
+    }
+}
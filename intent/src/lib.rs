@@ -1,12 +1,33 @@
 use plonky2::field::{goldilocks_field::GoldilocksField, secp256k1_scalar::Secp256K1Scalar};
 use plonky2_ecdsa::curve::secp256k1::Secp256K1;
 
+pub mod aggregation;
+pub mod amm;
+pub mod auction;
+pub mod cancel;
 pub mod circuit;
-mod intent;
+pub mod competition;
+pub mod error;
+pub mod fill;
+pub mod intent;
+pub mod keccak;
+pub mod keccak_chi_gate;
+pub mod nonce;
+pub mod order;
+pub mod pool;
+pub mod prover;
+pub mod public_inputs;
+pub mod ring;
+pub mod scoring;
+pub mod serialization;
 pub mod solver;
 pub mod structured_hash;
-mod swap_intent;
-mod swap_solver;
+pub mod swap_intent;
+pub mod swap_settlement;
+pub mod swap_solver;
+#[cfg(test)]
+mod test_utils;
+pub mod typed_data;
 pub mod witness;
 
 pub const D: usize = 2;
@@ -0,0 +1,177 @@
+//! Replay protection of signed intents.
+//!
+//! Each intent carries the sender's nonce and a deadline, both covered by its signature.
+//! An account's intents are accepted in nonce order, starting from zero, and each nonce
+//! is accepted once, so that a signed intent cannot be replayed in a later batch. A cancelled
+//! nonce is never accepted, and is skipped once the account's previous nonces are used.
+use std::collections::{HashMap, HashSet};
+
+use types::types::Address;
+
+use crate::error::ReplayError;
+
+/// Next expected nonce of every account
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NonceTracker {
+    next_nonces: HashMap<Address, u64>,
+    cancelled_nonces: HashSet<(Address, u64)>,
+}
+
+impl NonceTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Next nonce expected from `account`, zero for an account without accepted intents
+    pub fn next_nonce(&self, account: &Address) -> u64 {
+        self.next_nonces.get(account).copied().unwrap_or_default()
+    }
+
+    /// Whether the intent of `account` with the given `nonce` was cancelled
+    pub fn is_cancelled(&self, account: &Address, nonce: u64) -> bool {
+        self.cancelled_nonces.contains(&(*account, nonce))
+    }
+
+    /// Checks that an intent of `account` with the given `nonce` and `deadline` can be
+    /// accepted at time `now`, without using its nonce
+    pub fn check(
+        &self,
+        account: &Address,
+        nonce: u64,
+        deadline: u64,
+        now: u64,
+    ) -> Result<(), ReplayError> {
+        if now > deadline {
+            return Err(ReplayError::Expired { deadline, now });
+        }
+        if self.is_cancelled(account, nonce) {
+            return Err(ReplayError::Cancelled {
+                account: *account,
+                nonce,
+            });
+        }
+        let expected = self.next_nonce(account);
+        if nonce < expected {
+            return Err(ReplayError::NonceAlreadyUsed {
+                account: *account,
+                nonce,
+            });
+        }
+        if nonce > expected {
+            return Err(ReplayError::NonceOutOfOrder {
+                account: *account,
+                nonce,
+                expected,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks an intent as [`NonceTracker::check`] does, then uses its nonce
+    pub fn use_nonce(
+        &mut self,
+        account: &Address,
+        nonce: u64,
+        deadline: u64,
+        now: u64,
+    ) -> Result<(), ReplayError> {
+        self.check(account, nonce, deadline, now)?;
+        self.advance(account, nonce + 1);
+        Ok(())
+    }
+
+    /// Accounts and nonces of the cancelled intents, in no particular order
+    pub fn cancelled_nonces(&self) -> impl Iterator<Item = &(Address, u64)> {
+        self.cancelled_nonces.iter()
+    }
+
+    /// Cancels the intent of `account` with the given `nonce`, unless its nonce is already used
+    pub fn cancel_nonce(&mut self, account: &Address, nonce: u64) {
+        let next_nonce = self.next_nonce(account);
+        if nonce < next_nonce {
+            return;
+        }
+        self.cancelled_nonces.insert((*account, nonce));
+        self.advance(account, next_nonce);
+    }
+
+    /// Sets the next nonce of `account`, skipping the cancelled ones
+    fn advance(&mut self, account: &Address, mut next_nonce: u64) {
+        while self.cancelled_nonces.contains(&(*account, next_nonce)) {
+            next_nonce += 1;
+        }
+        self.next_nonces.insert(*account, next_nonce);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_works_nonce_tracker() {
+        let account = Address::new([1; 20]);
+        let other_account = Address::new([2; 20]);
+        let mut nonce_tracker = NonceTracker::new();
+
+        nonce_tracker.use_nonce(&account, 0, 100, 50).unwrap();
+        nonce_tracker.use_nonce(&account, 1, 100, 100).unwrap();
+        assert_eq!(nonce_tracker.next_nonce(&account), 2);
+        assert_eq!(nonce_tracker.next_nonce(&other_account), 0);
+        nonce_tracker.use_nonce(&other_account, 0, 100, 50).unwrap();
+    }
+
+    #[test]
+    fn it_fails_nonce_tracker_replays() {
+        let account = Address::new([1; 20]);
+        let mut nonce_tracker = NonceTracker::new();
+        nonce_tracker.use_nonce(&account, 0, 100, 50).unwrap();
+
+        assert_eq!(
+            nonce_tracker.use_nonce(&account, 1, 100, 101),
+            Err(ReplayError::Expired {
+                deadline: 100,
+                now: 101
+            })
+        );
+        assert_eq!(
+            nonce_tracker.use_nonce(&account, 0, 100, 50),
+            Err(ReplayError::NonceAlreadyUsed { account, nonce: 0 })
+        );
+        assert_eq!(
+            nonce_tracker.use_nonce(&account, 2, 100, 50),
+            Err(ReplayError::NonceOutOfOrder {
+                account,
+                nonce: 2,
+                expected: 1
+            })
+        );
+        // rejected intents do not use their nonce
+        assert_eq!(nonce_tracker.next_nonce(&account), 1);
+    }
+
+    #[test]
+    fn it_works_nonce_tracker_cancel_nonce() {
+        let account = Address::new([1; 20]);
+        let mut nonce_tracker = NonceTracker::new();
+
+        nonce_tracker.cancel_nonce(&account, 1);
+        assert_eq!(nonce_tracker.next_nonce(&account), 0);
+        nonce_tracker.use_nonce(&account, 0, 100, 50).unwrap();
+        // the cancelled nonce is skipped
+        assert_eq!(nonce_tracker.next_nonce(&account), 2);
+        assert_eq!(
+            nonce_tracker.check(&account, 1, 100, 50),
+            Err(ReplayError::Cancelled { account, nonce: 1 })
+        );
+
+        nonce_tracker.cancel_nonce(&account, 2);
+        assert_eq!(nonce_tracker.next_nonce(&account), 3);
+        // a used nonce cannot be cancelled
+        nonce_tracker.cancel_nonce(&account, 0);
+        assert_eq!(
+            nonce_tracker.check(&account, 0, 100, 50),
+            Err(ReplayError::NonceAlreadyUsed { account, nonce: 0 })
+        );
+    }
+}
@@ -0,0 +1,339 @@
+//! Normalized orders, the direction-free form of swap intents.
+//!
+//! Whatever its direction, a [`SwapIntent`] is a limit order on the token it pays: it sells
+//! at most `sell_amount` of its sell token, and buys at least `min_buy_amount` of its buy token
+//! in exchange. An order which is not partially fillable is filled as a whole, at once, while
+//! a partially fillable order is filled by parts of `sell_amount`, each at its limit price
+//! `min_buy_amount / sell_amount` or better. [`CircuitBuilderOrder`] enforces the same limits
+//! on witnessed orders.
+//!
+//! [`SwapIntent`]: crate::swap_intent::SwapIntent
+use num_bigint::BigUint;
+use plonky2::{iop::target::BoolTarget, plonk::circuit_builder::CircuitBuilder};
+use plonky2_ecdsa::gadgets::biguint::{BigUintTarget, CircuitBuilderBiguint};
+use plonky2_u32::gadgets::arithmetic_u32::U32Target;
+use types::types::Address;
+
+use crate::{
+    circuit::AddressTarget, error::IntentError, keccak::CircuitBuilderKeccak,
+    structured_hash::encode_uint, D, F,
+};
+
+/// Limit order selling `sell_token` for `buy_token`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Order {
+    pub sell_token: Address,
+    pub buy_token: Address,
+    /// most sold tokens, sold as a whole unless the order is partially fillable
+    pub sell_amount: BigUint,
+    /// least bought tokens, in exchange for the whole `sell_amount`
+    pub min_buy_amount: BigUint,
+    pub partially_fillable: bool,
+}
+
+impl Order {
+    /// Checks that the order's amounts fit into the `uint256` of their EIP-712 encoding, as
+    /// they should before an untrusted intent is hashed
+    pub fn check_amounts(&self) -> Result<(), IntentError> {
+        for (field, amount) in [
+            ("sell_amount", &self.sell_amount),
+            ("min_buy_amount", &self.min_buy_amount),
+        ] {
+            if encode_uint(amount, 256).is_none() {
+                return Err(IntentError::AmountOverflow {
+                    field,
+                    amount: amount.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Limit price, `min_buy_amount` bought tokens for `sell_amount` sold tokens
+    pub fn limit_price(&self) -> (&BigUint, &BigUint) {
+        (&self.min_buy_amount, &self.sell_amount)
+    }
+
+    /// Whether the order can be filled by selling `sold_amount` tokens for
+    /// `bought_amount` tokens
+    pub fn accepts_fill(&self, sold_amount: &BigUint, bought_amount: &BigUint) -> bool {
+        if sold_amount > &self.sell_amount {
+            return false;
+        }
+        if self.partially_fillable {
+            bought_amount * &self.sell_amount >= sold_amount * &self.min_buy_amount
+        } else {
+            bought_amount >= &self.min_buy_amount
+        }
+    }
+
+    /// Whether the order buys what `other` sells, and sells what `other` buys
+    pub fn opposes(&self, other: &Order) -> bool {
+        self.sell_token == other.buy_token && self.buy_token == other.sell_token
+    }
+
+    /// Whether the order's limit price is compatible with the one of the opposing `other`,
+    /// that is, whether both orders can be filled at a common price
+    pub fn crosses(&self, other: &Order) -> bool {
+        self.opposes(other)
+            && &self.sell_amount * &other.sell_amount
+                >= &self.min_buy_amount * &other.min_buy_amount
+    }
+}
+
+/// Greatest common divisor
+pub(crate) fn gcd(a: &BigUint, b: &BigUint) -> BigUint {
+    let (mut a, mut b) = (a.clone(), b.clone());
+    while b != BigUint::from(0_u8) {
+        let remainder = &a % &b;
+        a = b;
+        b = remainder;
+    }
+    a
+}
+
+/// Amounts sold by a `taker` order and by an opposing `maker` order matched together, given
+/// the sizes they can still fill. Orders which are not partially fillable are matched as a
+/// whole. Otherwise the match is at the maker's limit price, for as many whole units of that
+/// price as both orders can fill, and a maker which is not partially fillable sells its
+/// whole remaining size.
+pub fn match_orders(
+    taker: &Order,
+    maker: &Order,
+    taker_remaining: &BigUint,
+    maker_remaining: &BigUint,
+) -> Option<(BigUint, BigUint)> {
+    if !taker.opposes(maker) {
+        return None;
+    }
+    if !taker.partially_fillable && !maker.partially_fillable {
+        let (taker_sold, maker_sold) = (taker_remaining, maker_remaining);
+        return (taker.accepts_fill(taker_sold, maker_sold)
+            && maker.accepts_fill(maker_sold, taker_sold))
+        .then(|| (taker_sold.clone(), maker_sold.clone()));
+    }
+
+    let zero = BigUint::from(0_u8);
+    let divisor = gcd(&maker.sell_amount, &maker.min_buy_amount);
+    if divisor == zero {
+        return None;
+    }
+    let unit_maker_sold = &maker.sell_amount / &divisor;
+    let unit_taker_sold = &maker.min_buy_amount / &divisor;
+    let units = if unit_taker_sold == zero {
+        maker_remaining / &unit_maker_sold
+    } else if unit_maker_sold == zero {
+        taker_remaining / &unit_taker_sold
+    } else {
+        (taker_remaining / &unit_taker_sold).min(maker_remaining / &unit_maker_sold)
+    };
+    if units == zero {
+        return None;
+    }
+    let taker_sold = &units * unit_taker_sold;
+    let maker_sold = units * unit_maker_sold;
+
+    let whole_maker = maker.partially_fillable || &maker_sold == maker_remaining;
+    (whole_maker
+        && taker.accepts_fill(&taker_sold, &maker_sold)
+        && maker.accepts_fill(&maker_sold, &taker_sold))
+    .then_some((taker_sold, maker_sold))
+}
+
+/// In-circuit [`Order`]
+pub struct OrderTarget {
+    pub sell_token: AddressTarget,
+    pub buy_token: AddressTarget,
+    pub sell_amount: BigUintTarget,
+    pub min_buy_amount: BigUintTarget,
+    pub partially_fillable: BoolTarget,
+}
+
+pub trait CircuitBuilderOrder {
+    /// `a` if `condition` is set, `b` otherwise, for values of the same number of limbs
+    fn select_biguint(
+        &mut self,
+        condition: BoolTarget,
+        a: &BigUintTarget,
+        b: &BigUintTarget,
+    ) -> BigUintTarget;
+    /// `a` if `condition` is set, `b` otherwise
+    fn select_address(
+        &mut self,
+        condition: BoolTarget,
+        a: &AddressTarget,
+        b: &AddressTarget,
+    ) -> AddressTarget;
+    fn is_equal_address(&mut self, a: &AddressTarget, b: &AddressTarget) -> BoolTarget;
+    /// Constrains a fill of `order`, selling `sold_amount` tokens for `bought_amount` tokens,
+    /// to the order's size and limit price, as [`Order::accepts_fill`] checks it
+    fn assert_order_accepts_fill(
+        &mut self,
+        order: &OrderTarget,
+        sold_amount: &BigUintTarget,
+        bought_amount: &BigUintTarget,
+    );
+}
+
+impl CircuitBuilderOrder for CircuitBuilder<F, D> {
+    fn select_biguint(
+        &mut self,
+        condition: BoolTarget,
+        a: &BigUintTarget,
+        b: &BigUintTarget,
+    ) -> BigUintTarget {
+        assert_eq!(a.num_limbs(), b.num_limbs());
+        let limbs = a
+            .limbs
+            .iter()
+            .zip(&b.limbs)
+            .map(|(a, b)| U32Target(self.select(condition, a.0, b.0)))
+            .collect();
+        BigUintTarget { limbs }
+    }
+
+    fn select_address(
+        &mut self,
+        condition: BoolTarget,
+        a: &AddressTarget,
+        b: &AddressTarget,
+    ) -> AddressTarget {
+        let bits =
+            a.0.iter()
+                .zip(&b.0)
+                // selecting between two bits is a bit
+                .map(|(a, b)| BoolTarget::new_unsafe(self.select(condition, a.target, b.target)))
+                .collect();
+        AddressTarget(bits)
+    }
+
+    fn is_equal_address(&mut self, a: &AddressTarget, b: &AddressTarget) -> BoolTarget {
+        let a_words = self.bytes_to_u32_words_be(&a.0);
+        let b_words = self.bytes_to_u32_words_be(&b.0);
+        let mut equal = self._true();
+        for (a, b) in a_words.into_iter().zip(b_words) {
+            let equal_word = self.is_equal(a, b);
+            equal = self.and(equal, equal_word);
+        }
+        equal
+    }
+
+    fn assert_order_accepts_fill(
+        &mut self,
+        order: &OrderTarget,
+        sold_amount: &BigUintTarget,
+        bought_amount: &BigUintTarget,
+    ) {
+        let size_bound = self.cmp_biguint(sold_amount, &order.sell_amount);
+        self.assert_one(size_bound.target);
+
+        // a partially fillable order is filled at its limit price or better, otherwise the
+        // order buys at least its `min_buy_amount`
+        let limit = self.mul_biguint(sold_amount, &order.min_buy_amount);
+        let value = self.mul_biguint(bought_amount, &order.sell_amount);
+        let price_bound = self.cmp_biguint(&limit, &value);
+        let limit_bound = self.cmp_biguint(&order.min_buy_amount, bought_amount);
+        let within_limits = self.select(
+            order.partially_fillable,
+            price_bound.target,
+            limit_bound.target,
+        );
+        self.assert_one(within_limits);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(sell_amount: u64, min_buy_amount: u64, partially_fillable: bool) -> Order {
+        Order {
+            sell_token: Address::new([1; 20]),
+            buy_token: Address::new([2; 20]),
+            sell_amount: BigUint::from(sell_amount),
+            min_buy_amount: BigUint::from(min_buy_amount),
+            partially_fillable,
+        }
+    }
+
+    fn opposite(order: Order) -> Order {
+        Order {
+            sell_token: order.buy_token,
+            buy_token: order.sell_token,
+            ..order
+        }
+    }
+
+    #[test]
+    fn it_works_order_accepts_fill() {
+        let amount = <BigUint as From<u64>>::from;
+        let whole = order(100, 200, false);
+        assert!(whole.accepts_fill(&amount(100), &amount(200)));
+        assert!(whole.accepts_fill(&amount(90), &amount(250)));
+        assert!(!whole.accepts_fill(&amount(50), &amount(100)));
+        assert!(!whole.accepts_fill(&amount(101), &amount(300)));
+
+        let partial = order(100, 200, true);
+        assert!(partial.accepts_fill(&amount(50), &amount(100)));
+        assert!(partial.accepts_fill(&amount(50), &amount(101)));
+        assert!(!partial.accepts_fill(&amount(50), &amount(99)));
+        assert!(!partial.accepts_fill(&amount(101), &amount(300)));
+    }
+
+    #[test]
+    fn it_works_order_crosses() {
+        // sells 100 for at least 200, 2 bought per sold
+        let order = order(100, 200, true);
+        // sells 90 for at least 30, 1/3 bought per sold
+        let counter_order = opposite(self::order(90, 30, true));
+        assert!(order.crosses(&counter_order));
+        assert!(counter_order.crosses(&order));
+
+        // sells 90 for at least 50, more than the 1/2 bought per sold `order` offers
+        let counter_order = opposite(self::order(90, 50, true));
+        assert!(!order.crosses(&counter_order));
+        // both sell the same token
+        assert!(!order.crosses(&self::order(10, 1, true)));
+    }
+
+    #[test]
+    fn it_works_match_orders() {
+        let amount = <BigUint as From<u64>>::from;
+        // sells up to 100 for at least 2 bought per sold
+        let taker = order(100, 200, true);
+        // sells up to 90 for 1 bought per 3 sold
+        let maker = opposite(order(90, 30, true));
+        assert_eq!(
+            match_orders(&taker, &maker, &amount(100), &amount(90)),
+            Some((amount(30), amount(90)))
+        );
+        assert_eq!(
+            match_orders(&taker, &maker, &amount(20), &amount(90)),
+            Some((amount(20), amount(60)))
+        );
+
+        // a maker which is not partially fillable sells its whole size
+        let maker = opposite(order(90, 30, false));
+        assert_eq!(match_orders(&taker, &maker, &amount(20), &amount(90)), None);
+        assert_eq!(
+            match_orders(&taker, &maker, &amount(100), &amount(90)),
+            Some((amount(30), amount(90)))
+        );
+
+        // orders which are not partially fillable are matched as a whole
+        let taker = order(100, 80, false);
+        assert_eq!(
+            match_orders(&taker, &maker, &amount(100), &amount(90)),
+            Some((amount(100), amount(90)))
+        );
+        assert_eq!(
+            match_orders(
+                &taker,
+                &opposite(order(90, 101, false)),
+                &amount(100),
+                &amount(90)
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,568 @@
+//! Pool of signed intents waiting to be batched.
+//!
+//! Intents are validated when inserted: their signature, of their EIP-712 signing hash in the
+//! pool's domain, should recover their sender, their nonce should not be used nor cancelled, they should not be expired, and the tokens their
+//! sender's pending intents spend should not exceed the sender's balance hint, when one is
+//! known. Nonces above the sender's next nonce are accepted, so that an account can queue
+//! several intents. A batch only holds intents whose nonces follow each other from their
+//! sender's next nonce, so that a solver accepts the whole batch. Intents stay in the pool
+//! until their nonces are used, see [`IntentPool::set_nonce_tracker`], or until their sender
+//! cancels them, see [`IntentPool::cancel`].
+use std::collections::{HashMap, HashSet};
+
+use num_bigint::BigUint;
+use types::types::{Address, RecoverableSignature};
+
+use crate::{
+    cancel::{CancelIntent, CancelTarget},
+    error::{PoolError, ReplayError},
+    intent::{recover_signer, ExecuteRuntime, Intent},
+    nonce::NonceTracker,
+    order::Order,
+    structured_hash::{Eip712Domain, StructuredHashInterface},
+};
+
+/// Intent which can be held by an [`IntentPool`]
+pub trait PooledIntent: Intent + StructuredHashInterface + Clone {
+    /// Sequence number of the intent among its sender's intents
+    fn nonce(&self) -> u64;
+    /// Unix timestamp, in seconds, after which the intent expires
+    fn deadline(&self) -> u64;
+    /// Order traded by the intent, which decides its token pair and the tokens it spends
+    fn pooled_order(&self) -> Order;
+}
+
+/// Token pair of `token` and `other_token`, whatever their order
+pub fn token_pair(token: Address, other_token: Address) -> (Address, Address) {
+    (token.min(other_token), token.max(other_token))
+}
+
+#[derive(Clone, Debug)]
+struct PooledEntry<T> {
+    intent: T,
+    signature: RecoverableSignature,
+}
+
+/// Signed intents, by structured hash, indexed by token pair and by account
+#[derive(Clone, Debug)]
+pub struct IntentPool<T: PooledIntent> {
+    entries: HashMap<[u8; 32], PooledEntry<T>>,
+    /// structured hashes, in insertion order
+    arrivals: Vec<[u8; 32]>,
+    by_pair: HashMap<(Address, Address), Vec<[u8; 32]>>,
+    by_account: HashMap<Address, Vec<[u8; 32]>>,
+    /// domain in which intents and cancellations are signed
+    domain: Eip712Domain,
+    nonce_tracker: NonceTracker,
+    /// structured hash and sender of each intent cancelled by structured hash
+    cancelled_intents: HashSet<([u8; 32], Address)>,
+    /// balance of every known account, by token
+    balance_hints: HashMap<(Address, Address), BigUint>,
+}
+
+impl<T: PooledIntent> Default for IntentPool<T> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            arrivals: vec![],
+            by_pair: HashMap::new(),
+            by_account: HashMap::new(),
+            domain: Eip712Domain::default(),
+            nonce_tracker: NonceTracker::new(),
+            cancelled_intents: HashSet::new(),
+            balance_hints: HashMap::new(),
+        }
+    }
+}
+
+impl<T: PooledIntent> IntentPool<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pool accepting intents and cancellations signed in `domain`, which should name the chain
+    /// and the contract settling the intents. The default domain has no field.
+    pub fn with_domain(mut self, domain: Eip712Domain) -> Self {
+        self.domain = domain;
+        self
+    }
+
+    pub fn domain(&self) -> &Eip712Domain {
+        &self.domain
+    }
+
+    /// Pool accepting intents from the nonces expected by `nonce_tracker`
+    pub fn with_nonce_tracker(mut self, nonce_tracker: NonceTracker) -> Self {
+        self.nonce_tracker = nonce_tracker;
+        self
+    }
+
+    pub fn nonce_tracker(&self) -> &NonceTracker {
+        &self.nonce_tracker
+    }
+
+    /// Updates the nonces once a batch is settled, dropping the intents whose nonces are
+    /// used or cancelled
+    pub fn set_nonce_tracker(&mut self, nonce_tracker: NonceTracker) {
+        self.nonce_tracker = nonce_tracker;
+        self.retain(|pool, intent| {
+            !matches!(
+                pool.nonce_tracker
+                    .check(&intent.get_from(), intent.nonce(), u64::MAX, 0),
+                Err(ReplayError::NonceAlreadyUsed { .. } | ReplayError::Cancelled { .. })
+            )
+        });
+    }
+
+    /// Records a cancellation signed by its sender, then evicts the cancelled intents,
+    /// returning how many were evicted. Cancelled intents are rejected once evicted, whether
+    /// cancelled by structured hash or by nonce, while a cancellation signed by another
+    /// account than the intent's sender cancels none of its intents
+    pub fn cancel(
+        &mut self,
+        cancel_intent: &CancelIntent,
+        signature: &RecoverableSignature,
+    ) -> Result<usize, PoolError> {
+        let public_key = recover_signer(cancel_intent.signing_hash(&self.domain), signature)
+            .map_err(|e| PoolError::InvalidSignature(e.to_string()))?;
+        let from = cancel_intent.from();
+        let signer = Address::from_public_key(&public_key);
+        if from != signer {
+            return Err(PoolError::WrongSigner { from, signer });
+        }
+        match cancel_intent.target() {
+            CancelTarget::IntentHash(intent_hash) => {
+                self.cancelled_intents.insert((intent_hash, from));
+            }
+            CancelTarget::Nonce(nonce) => self.nonce_tracker.cancel_nonce(&from, nonce),
+        }
+
+        let len = self.len();
+        self.retain(|pool, intent| {
+            !pool
+                .cancelled_intents
+                .contains(&(intent.structured_hash(), intent.get_from()))
+                && !pool
+                    .nonce_tracker
+                    .is_cancelled(&intent.get_from(), intent.nonce())
+        });
+        Ok(len - self.len())
+    }
+
+    /// Sets the balance of `token` held by `account`, which bounds the tokens spent by
+    /// the account's intents inserted afterwards
+    pub fn set_balance_hint(&mut self, account: Address, token: Address, balance: BigUint) {
+        self.balance_hints.insert((account, token), balance);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, structured_hash: &[u8; 32]) -> bool {
+        self.entries.contains_key(structured_hash)
+    }
+
+    pub fn get(&self, structured_hash: &[u8; 32]) -> Option<&T> {
+        self.entries.get(structured_hash).map(|entry| &entry.intent)
+    }
+
+    /// Intents trading the pair of `token` and `other_token`, on either side, in insertion order
+    pub fn intents_by_pair(&self, token: Address, other_token: Address) -> Vec<&T> {
+        self.intents(self.by_pair.get(&token_pair(token, other_token)))
+    }
+
+    /// Intents from `account`, in insertion order
+    pub fn intents_by_account(&self, account: &Address) -> Vec<&T> {
+        self.intents(self.by_account.get(account))
+    }
+
+    fn intents(&self, structured_hashes: Option<&Vec<[u8; 32]>>) -> Vec<&T> {
+        structured_hashes
+            .into_iter()
+            .flatten()
+            .map(|structured_hash| &self.entries[structured_hash].intent)
+            .collect()
+    }
+
+    /// Validates a signed intent at time `now`, then inserts it, returning its structured hash
+    pub fn insert(
+        &mut self,
+        intent: T,
+        signature: RecoverableSignature,
+        now: u64,
+    ) -> Result<[u8; 32], PoolError> {
+        intent.pooled_order().check_amounts()?;
+        let structured_hash = intent.structured_hash();
+        let public_key = recover_signer(intent.signing_hash(&self.domain), &signature)
+            .map_err(|e| PoolError::InvalidSignature(e.to_string()))?;
+        let from = intent.get_from();
+        let signer = Address::from_public_key(&public_key);
+        if from != signer {
+            return Err(PoolError::WrongSigner { from, signer });
+        }
+        if self.contains(&structured_hash) {
+            return Err(PoolError::Duplicate);
+        }
+
+        let nonce = intent.nonce();
+        if self.cancelled_intents.contains(&(structured_hash, from)) {
+            return Err(ReplayError::Cancelled {
+                account: from,
+                nonce,
+            }
+            .into());
+        }
+        match self
+            .nonce_tracker
+            .check(&from, nonce, intent.deadline(), now)
+        {
+            Ok(()) | Err(ReplayError::NonceOutOfOrder { .. }) => {}
+            Err(e) => return Err(e.into()),
+        }
+        if self
+            .intents_by_account(&from)
+            .iter()
+            .any(|pending| pending.nonce() == nonce)
+        {
+            return Err(PoolError::NonceTaken {
+                account: from,
+                nonce,
+            });
+        }
+
+        let order = intent.pooled_order();
+        if let Some(balance) = self.balance_hints.get(&(from, order.sell_token)) {
+            let required = self
+                .intents_by_account(&from)
+                .iter()
+                .map(|pending| pending.pooled_order())
+                .filter(|pending| pending.sell_token == order.sell_token)
+                .fold(order.sell_amount.clone(), |required, pending| {
+                    required + pending.sell_amount
+                });
+            if &required > balance {
+                return Err(PoolError::InsufficientBalance {
+                    account: from,
+                    token: order.sell_token,
+                    required,
+                    balance: balance.clone(),
+                });
+            }
+        }
+
+        self.arrivals.push(structured_hash);
+        self.by_pair
+            .entry(token_pair(order.sell_token, order.buy_token))
+            .or_default()
+            .push(structured_hash);
+        self.by_account
+            .entry(from)
+            .or_default()
+            .push(structured_hash);
+        self.entries
+            .insert(structured_hash, PooledEntry { intent, signature });
+        Ok(structured_hash)
+    }
+
+    /// Removes an intent, returning it with its signature
+    pub fn remove(&mut self, structured_hash: &[u8; 32]) -> Option<(T, RecoverableSignature)> {
+        let entry = self.entries.remove(structured_hash)?;
+        self.arrivals.retain(|arrival| arrival != structured_hash);
+        for index in self
+            .by_pair
+            .values_mut()
+            .chain(self.by_account.values_mut())
+        {
+            index.retain(|indexed| indexed != structured_hash);
+        }
+        self.by_pair.retain(|_, index| !index.is_empty());
+        self.by_account.retain(|_, index| !index.is_empty());
+        Some((entry.intent, entry.signature))
+    }
+
+    /// Drops the intents expired at time `now`, returning how many were dropped
+    pub fn drop_expired(&mut self, now: u64) -> usize {
+        let len = self.len();
+        self.retain(|_, intent| intent.deadline() >= now);
+        len - self.len()
+    }
+
+    fn retain(&mut self, keep: impl Fn(&Self, &T) -> bool) {
+        let dropped = self
+            .arrivals
+            .iter()
+            .filter(|structured_hash| !keep(self, &self.entries[*structured_hash].intent))
+            .copied()
+            .collect::<Vec<_>>();
+        for structured_hash in dropped {
+            self.remove(&structured_hash);
+        }
+    }
+
+    /// Batch of exactly `size` signed intents routed to the `execute_runtime` solvers, and
+    /// not expired at time `now`, or `None` if the pool does not hold enough of them. Intents
+    /// are taken in insertion order, as long as their nonces follow their sender's nonces
+    pub fn batch(
+        &self,
+        execute_runtime: ExecuteRuntime,
+        size: usize,
+        now: u64,
+    ) -> Option<Vec<(T, RecoverableSignature)>> {
+        let mut candidates = self
+            .arrivals
+            .iter()
+            .map(|structured_hash| &self.entries[structured_hash])
+            .filter(|entry| {
+                entry.intent.get_runtime_execution() == execute_runtime
+                    && entry.intent.deadline() >= now
+            })
+            .collect::<Vec<_>>();
+        let mut next_nonces = HashMap::new();
+        let mut batch = vec![];
+
+        // an intent is taken once its sender's previous intents are, so that taking one
+        // may allow one which arrived before it
+        let mut progress = true;
+        while progress && batch.len() < size {
+            progress = false;
+            let mut index = 0;
+            while index < candidates.len() && batch.len() < size {
+                let intent = &candidates[index].intent;
+                let from = intent.get_from();
+                let next_nonce = next_nonces
+                    .entry(from)
+                    .or_insert_with(|| self.nonce_tracker.next_nonce(&from));
+                if intent.nonce() != *next_nonce {
+                    index += 1;
+                    continue;
+                }
+                *next_nonce += 1;
+                while self.nonce_tracker.is_cancelled(&from, *next_nonce) {
+                    *next_nonce += 1;
+                }
+                let entry = candidates.remove(index);
+                batch.push((entry.intent.clone(), entry.signature.clone()));
+                progress = true;
+            }
+        }
+
+        (batch.len() == size).then_some(batch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use types::types::{Message, PrivateKey, PublicKey};
+
+    use crate::{
+        error::IntentError,
+        swap_intent::SwapIntent,
+        test_utils::{domain, signed_swap_intent},
+    };
+
+    use super::*;
+
+    #[test]
+    fn it_works_intent_pool_insert() {
+        let private_key = PrivateKey::new();
+        let account = Address::from_public_key(&PublicKey::from_private_key(&private_key));
+        let base_token = Address::new([64; 20]);
+        let other_token = Address::new([65; 20]);
+        let mut pool = IntentPool::new().with_domain(domain());
+
+        let (intent, signature) = signed_swap_intent(&private_key, base_token, 1, 100);
+        let structured_hash = pool.insert(intent.clone(), signature.clone(), 0).unwrap();
+        assert!(pool.contains(&structured_hash));
+        assert_eq!(pool.insert(intent, signature, 0), Err(PoolError::Duplicate));
+        let (other_intent, other_signature) = signed_swap_intent(&private_key, other_token, 1, 100);
+        assert_eq!(
+            pool.insert(other_intent, other_signature, 0),
+            Err(PoolError::NonceTaken { account, nonce: 1 })
+        );
+        let (other_intent, other_signature) = signed_swap_intent(&private_key, other_token, 0, 100);
+        pool.insert(other_intent, other_signature, 0).unwrap();
+
+        assert_eq!(pool.len(), 2);
+        assert_eq!(
+            pool.intents_by_pair(base_token, Address::new([125; 20]))
+                .len(),
+            1
+        );
+        assert_eq!(
+            pool.intents_by_pair(Address::new([125; 20]), other_token)
+                .len(),
+            1
+        );
+        assert_eq!(pool.intents_by_account(&account).len(), 2);
+
+        // the signature should recover the sender
+        let (mut forged_intent, signature) = signed_swap_intent(&private_key, base_token, 2, 100);
+        forged_intent.inputs.from = Address::new([1; 20]);
+        assert!(matches!(
+            pool.insert(forged_intent, signature, 0),
+            Err(PoolError::WrongSigner { .. })
+        ));
+        // as well as the signature of the intent in another domain
+        let (intent, _) = signed_swap_intent(&private_key, base_token, 2, 100);
+        let other_domain = Eip712Domain {
+            chain_id: Some(BigUint::from(2_u8)),
+            ..domain()
+        };
+        let signature = private_key
+            .sign_message_recoverable(&Message::new_message(intent.signing_hash(&other_domain)));
+        assert!(matches!(
+            pool.insert(intent, signature, 0),
+            Err(PoolError::WrongSigner { .. })
+        ));
+        // amounts which do not fit into an uint256 are rejected before hashing the intent
+        let (mut overflowing_intent, signature) =
+            signed_swap_intent(&private_key, base_token, 2, 100);
+        overflowing_intent.inputs.quote_amount = BigUint::from(1_u8) << 256_u32;
+        assert!(matches!(
+            pool.insert(overflowing_intent, signature, 0),
+            Err(PoolError::Intent(IntentError::AmountOverflow { .. }))
+        ));
+
+        let (expired_intent, signature) = signed_swap_intent(&private_key, base_token, 2, 100);
+        assert_eq!(
+            pool.insert(expired_intent, signature, 101),
+            Err(PoolError::Replay(ReplayError::Expired {
+                deadline: 100,
+                now: 101
+            }))
+        );
+        assert_eq!(pool.drop_expired(101), 2);
+        assert!(pool.is_empty());
+        assert!(pool.intents_by_account(&account).is_empty());
+    }
+
+    #[test]
+    fn it_works_intent_pool_balance_hints() {
+        let private_key = PrivateKey::new();
+        let account = Address::from_public_key(&PublicKey::from_private_key(&private_key));
+        let quote_token = Address::new([125; 20]);
+        let mut pool = IntentPool::new().with_domain(domain());
+        pool.set_balance_hint(account, quote_token, BigUint::from(150_u8));
+
+        let (intent, signature) = signed_swap_intent(&private_key, Address::new([64; 20]), 0, 100);
+        pool.insert(intent, signature, 0).unwrap();
+        let (intent, signature) = signed_swap_intent(&private_key, Address::new([65; 20]), 1, 100);
+        assert_eq!(
+            pool.insert(intent, signature, 0),
+            Err(PoolError::InsufficientBalance {
+                account,
+                token: quote_token,
+                required: BigUint::from(200_u8),
+                balance: BigUint::from(150_u8),
+            })
+        );
+    }
+
+    #[test]
+    fn it_works_intent_pool_batch() {
+        let private_key = PrivateKey::new();
+        let other_private_key = PrivateKey::new();
+        let account = Address::from_public_key(&PublicKey::from_private_key(&private_key));
+        let base_token = Address::new([64; 20]);
+        let mut pool = IntentPool::new().with_domain(domain());
+
+        // nonces 1 and 0 of an account, then nonce 0 of another account
+        for (private_key, nonce) in [
+            (&private_key, 1),
+            (&private_key, 0),
+            (&other_private_key, 0),
+        ] {
+            let (intent, signature) = signed_swap_intent(private_key, base_token, nonce, 100);
+            pool.insert(intent, signature, 0).unwrap();
+        }
+
+        let nonces = |batch: Vec<(SwapIntent, RecoverableSignature)>| {
+            batch
+                .iter()
+                .map(|(intent, _)| (intent.inputs.from == account, intent.inputs.nonce))
+                .collect::<Vec<_>>()
+        };
+        let batch = pool.batch(ExecuteRuntime::Swap, 2, 0).unwrap();
+        assert_eq!(nonces(batch), vec![(true, 0), (false, 0)]);
+        let batch = pool.batch(ExecuteRuntime::Swap, 3, 0).unwrap();
+        assert_eq!(nonces(batch), vec![(true, 0), (false, 0), (true, 1)]);
+        assert!(pool.batch(ExecuteRuntime::Swap, 4, 0).is_none());
+        assert!(pool.batch(ExecuteRuntime::Swap, 1, 101).is_none());
+
+        // once the first nonce of the account is used, its intent leaves the pool
+        let mut nonce_tracker = NonceTracker::new();
+        nonce_tracker.use_nonce(&account, 0, 100, 0).unwrap();
+        pool.set_nonce_tracker(nonce_tracker);
+        assert_eq!(pool.len(), 2);
+        let batch = pool.batch(ExecuteRuntime::Swap, 2, 0).unwrap();
+        assert_eq!(nonces(batch), vec![(true, 1), (false, 0)]);
+    }
+
+    #[test]
+    fn it_works_intent_pool_cancel() {
+        let private_key = PrivateKey::new();
+        let account = Address::from_public_key(&PublicKey::from_private_key(&private_key));
+        let base_token = Address::new([64; 20]);
+        let mut pool = IntentPool::new().with_domain(domain());
+
+        let (by_hash, by_hash_signature) = signed_swap_intent(&private_key, base_token, 0, 100);
+        let (by_nonce, by_nonce_signature) = signed_swap_intent(&private_key, base_token, 1, 100);
+        let (kept, kept_signature) = signed_swap_intent(&private_key, base_token, 2, 100);
+        for (intent, signature) in [
+            (by_hash.clone(), by_hash_signature.clone()),
+            (by_nonce.clone(), by_nonce_signature.clone()),
+            (kept.clone(), kept_signature),
+        ] {
+            pool.insert(intent, signature, 0).unwrap();
+        }
+
+        // a cancellation of another account is rejected when signed by it, and cancels none
+        // of the sender's intents otherwise
+        let cancel_by_hash = CancelIntent::by_intent_hash(account, by_hash.structured_hash());
+        assert!(matches!(
+            pool.cancel(
+                &cancel_by_hash,
+                &cancel_by_hash.sign(PrivateKey::new(), &domain())
+            ),
+            Err(PoolError::WrongSigner { .. })
+        ));
+        let stranger_key = PrivateKey::new();
+        let stranger = Address::from_public_key(&PublicKey::from_private_key(&stranger_key));
+        let cancel_by_stranger = CancelIntent::by_intent_hash(stranger, kept.structured_hash());
+        let signature = stranger_key.sign_message_recoverable(&Message::new_message(
+            cancel_by_stranger.signing_hash(&domain()),
+        ));
+        assert_eq!(pool.cancel(&cancel_by_stranger, &signature), Ok(0));
+        assert_eq!(pool.len(), 3);
+
+        let signature = private_key.sign_message_recoverable(&Message::new_message(
+            cancel_by_hash.signing_hash(&domain()),
+        ));
+        assert_eq!(pool.cancel(&cancel_by_hash, &signature), Ok(1));
+        assert!(!pool.contains(&by_hash.structured_hash()));
+        assert_eq!(
+            pool.insert(by_hash, by_hash_signature, 0),
+            Err(PoolError::Replay(ReplayError::Cancelled {
+                account,
+                nonce: 0
+            }))
+        );
+
+        let cancel_by_nonce = CancelIntent::by_nonce(account, 1);
+        let signature = private_key.sign_message_recoverable(&Message::new_message(
+            cancel_by_nonce.signing_hash(&domain()),
+        ));
+        assert_eq!(pool.cancel(&cancel_by_nonce, &signature), Ok(1));
+        assert!(matches!(
+            pool.insert(by_nonce, by_nonce_signature, 0),
+            Err(PoolError::Replay(ReplayError::Cancelled { nonce: 1, .. }))
+        ));
+        assert_eq!(pool.len(), 1);
+        assert!(pool.contains(&kept.structured_hash()));
+    }
+}
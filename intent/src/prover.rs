@@ -0,0 +1,192 @@
+//! Prover of intent signatures, which builds the signature circuit once and proves any
+//! number of intents against it.
+//!
+//! Intents are signed in an EIP-712 domain, naming the chain and the contract which settles
+//! them. Its separator is a constant of the circuit, so that a proof only verifies against the
+//! verifier data of the prover built for its domain.
+//!
+//! The circuit's verifier data can be saved, for the verifiers to load (see
+//! [`crate::intent::IntentSignatureVerifier`]). Persisting the circuit itself, so that a
+//! prover does not build it again when it starts, is left open: plonky2 0.1.3 cannot
+//! serialize the prover data, nor the common circuit data (see [`crate::serialization`]).
+use std::{fs, path::Path};
+
+use plonky2::{
+    iop::witness::PartialWitness,
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::{
+            CircuitConfig, CircuitData, CommonCircuitData, VerifierCircuitData,
+            VerifierOnlyCircuitData,
+        },
+        config::GenericConfig,
+    },
+};
+use types::types::{Message, PublicKey, Signature};
+
+use crate::{
+    circuit::{ECDSAIntentCircuit, IntentCircuitInterface, IntentSignatureCircuitTargets},
+    error::VerificationError,
+    intent::{Intent, IntentSignatureVerifier, SignatureProofData},
+    public_inputs::PublicInputsOptions,
+    serialization::verifier_only_data_to_bytes,
+    structured_hash::{Eip712Domain, StructuredHashInterface},
+    witness::{ECDSAIntentWitness, ECDSASignatureWitnessTargets},
+    D, F,
+};
+
+pub struct IntentSignatureProver<C, I>
+where
+    C: GenericConfig<D, F = F>,
+    I: IntentCircuitInterface,
+{
+    circuit_data: CircuitData<F, C, D>,
+    domain: Eip712Domain,
+    public_inputs_options: PublicInputsOptions,
+    signature_targets: ECDSASignatureWitnessTargets,
+    intent_target: I::IntentTarget,
+}
+
+impl<C, I> IntentSignatureProver<C, I>
+where
+    C: GenericConfig<D, F = F>,
+    I: StructuredHashInterface + IntentCircuitInterface + Intent,
+{
+    /// Builds the signature circuit of intents signed in `domain`, exposing the public inputs
+    /// selected by `public_inputs_options`
+    pub fn new(domain: Eip712Domain, public_inputs_options: PublicInputsOptions) -> Self {
+        let config = CircuitConfig::standard_ecc_config();
+        let mut circuit_builder = CircuitBuilder::new(config);
+
+        let IntentSignatureCircuitTargets {
+            signature_targets,
+            intent_target,
+            structured_hash_target,
+        } = circuit_builder.verify_intent_fields_signature::<I>(&domain);
+        circuit_builder.register_intent_public_inputs(
+            &structured_hash_target,
+            &signature_targets.public_key_ecdsa_target,
+            &signature_targets.from_address_target,
+            &public_inputs_options,
+        );
+        let signature_targets =
+            ECDSASignatureWitnessTargets::new(&mut circuit_builder, signature_targets);
+
+        Self {
+            circuit_data: circuit_builder.build::<C>(),
+            domain,
+            public_inputs_options,
+            signature_targets,
+            intent_target,
+        }
+    }
+
+    /// Saves the circuit's verifier-only data at `path`
+    pub fn save_verifier_data(&self, path: impl AsRef<Path>) -> Result<(), anyhow::Error> {
+        fs::write(path, verifier_only_data_to_bytes(self.verifier_only_data()))?;
+        Ok(())
+    }
+
+    pub fn domain(&self) -> &Eip712Domain {
+        &self.domain
+    }
+
+    pub fn public_inputs_options(&self) -> PublicInputsOptions {
+        self.public_inputs_options
+    }
+
+    pub fn common_data(&self) -> &CommonCircuitData<F, D> {
+        &self.circuit_data.common
+    }
+
+    pub fn verifier_only_data(&self) -> &VerifierOnlyCircuitData<C, D> {
+        &self.circuit_data.verifier_only
+    }
+
+    pub fn verifier_data(&self) -> VerifierCircuitData<F, C, D> {
+        self.circuit_data.verifier_data()
+    }
+
+    /// Verifier of the prover's proofs, against its circuit's verifier data
+    pub fn verifier(&self) -> Result<IntentSignatureVerifier<C>, VerificationError> {
+        IntentSignatureVerifier::new(self.verifier_data(), self.public_inputs_options)
+    }
+
+    /// Proves that `signature` is a signature of `intent` by `public_key`, in the prover's domain
+    pub fn prove(
+        &self,
+        intent: &I,
+        public_key: PublicKey,
+        signature: Signature,
+    ) -> Result<SignatureProofData<C>, anyhow::Error> {
+        let mut partial_witness = PartialWitness::<F>::new();
+        intent.set_intent_target(&mut partial_witness, &self.intent_target);
+
+        let message = Message::from_slice(&intent.signing_hash(&self.domain))?;
+        partial_witness.set_signed_intent(
+            message.into_plonky2_message(),
+            public_key.into_plonky2_public_key(),
+            signature.into_plonky2_signature(),
+            intent.get_from(),
+            &self.signature_targets,
+        );
+
+        let proof_with_pis = self.circuit_data.prove(partial_witness)?;
+        Ok(SignatureProofData {
+            proof_with_pis,
+            common: self.circuit_data.common.clone(),
+            verifier_only: self.circuit_data.verifier_only.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        public_inputs::IntentPublicValues,
+        serialization::verifier_only_data_from_bytes,
+        test_utils::{proven_swap_intents, signature_prover, ProofConfig, ProvenSwapIntent},
+    };
+
+    use super::*;
+
+    #[test]
+    fn it_works_intent_signature_prover_round_trip() {
+        let prover = signature_prover();
+        let path = std::env::temp_dir().join(format!(
+            "intent-signature-verifier-{}.bin",
+            std::process::id()
+        ));
+        prover.save_verifier_data(&path).unwrap();
+        let verifier_only = verifier_only_data_from_bytes::<ProofConfig>(
+            fs::read(&path).unwrap(),
+            prover.common_data(),
+        );
+        fs::remove_file(&path).unwrap();
+        let verifier_data = VerifierCircuitData {
+            verifier_only: verifier_only.unwrap(),
+            common: prover.common_data().clone(),
+        };
+        assert_eq!(&verifier_data.verifier_only, prover.verifier_only_data());
+
+        for ProvenSwapIntent {
+            intent,
+            public_key,
+            signature_proof_data,
+        } in proven_swap_intents()
+        {
+            assert_eq!(
+                signature_proof_data.proof_with_pis.public_inputs,
+                IntentPublicValues::new(
+                    &prover.public_inputs_options(),
+                    intent.structured_hash(),
+                    public_key
+                )
+                .to_field_elements()
+            );
+            verifier_data
+                .verify(signature_proof_data.proof_with_pis.clone())
+                .unwrap();
+        }
+    }
+}
@@ -0,0 +1,211 @@
+//! Public inputs of the intent signature circuit.
+//!
+//! Callers choose which values a proof exposes with [`PublicInputsOptions`]. Selected values
+//! are registered in a fixed order: the message hash, the signer, then the intent commitment.
+use plonky2::{
+    field::types::{Field, PrimeField},
+    hash::{
+        hash_types::{HashOut, HashOutTarget},
+        poseidon::PoseidonHash,
+    },
+    iop::target::BoolTarget,
+    plonk::{circuit_builder::CircuitBuilder, config::Hasher},
+};
+use types::types::{Address, PublicKey};
+
+use crate::{keccak::CircuitBuilderKeccak, D, F};
+
+/// Number of 32-bit limbs of a secp256k1 public key coordinate
+pub const COORDINATE_LIMBS: usize = 8;
+
+/// Signer value registered as public inputs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SignerPublicInput {
+    #[default]
+    None,
+    /// The public key's `x` then `y` coordinates, as little-endian 32-bit limbs
+    PublicKey,
+    /// The signer's address, as big-endian 32-bit words
+    Address,
+}
+
+/// Values of the intent signature circuit which are registered as public inputs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PublicInputsOptions {
+    /// The intent's EIP-712 structured hash, as big-endian 32-bit words. The signed message is
+    /// its signing hash in the circuit's domain, whose separator is a constant of the circuit.
+    pub message_hash: bool,
+    pub signer: SignerPublicInput,
+    /// A Poseidon hash of the structured hash's words, which is cheaper
+    /// to consume in recursive circuits than the keccak256 hash itself
+    pub intent_commitment: bool,
+}
+
+impl PublicInputsOptions {
+    pub fn new(message_hash: bool, signer: SignerPublicInput, intent_commitment: bool) -> Self {
+        Self {
+            message_hash,
+            signer,
+            intent_commitment,
+        }
+    }
+
+    /// Exposes every value, with the signer's address
+    pub fn all() -> Self {
+        Self::new(true, SignerPublicInput::Address, true)
+    }
+}
+
+/// Signer value expected from a proof
+#[derive(Clone, Debug, PartialEq)]
+pub enum SignerPublicValue {
+    PublicKey(PublicKey),
+    Address(Address),
+}
+
+/// Values a proof is expected to expose, `None` for those not registered
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IntentPublicValues {
+    pub message_hash: Option<[u8; 32]>,
+    pub signer: Option<SignerPublicValue>,
+    pub intent_commitment: Option<HashOut<F>>,
+}
+
+impl IntentPublicValues {
+    /// Values exposed with `options` by a proof of a signature of `public_key`
+    /// over an intent with the given structured hash
+    pub fn new(
+        options: &PublicInputsOptions,
+        structured_hash: [u8; 32],
+        public_key: &PublicKey,
+    ) -> Self {
+        let signer = match options.signer {
+            SignerPublicInput::None => None,
+            SignerPublicInput::PublicKey => Some(SignerPublicValue::PublicKey(public_key.clone())),
+            SignerPublicInput::Address => Some(SignerPublicValue::Address(
+                Address::from_public_key(public_key),
+            )),
+        };
+        Self {
+            message_hash: options.message_hash.then_some(structured_hash),
+            signer,
+            intent_commitment: options
+                .intent_commitment
+                .then(|| intent_commitment(&structured_hash)),
+        }
+    }
+
+    pub fn options(&self) -> PublicInputsOptions {
+        let signer = match self.signer {
+            None => SignerPublicInput::None,
+            Some(SignerPublicValue::PublicKey(_)) => SignerPublicInput::PublicKey,
+            Some(SignerPublicValue::Address(_)) => SignerPublicInput::Address,
+        };
+        PublicInputsOptions::new(
+            self.message_hash.is_some(),
+            signer,
+            self.intent_commitment.is_some(),
+        )
+    }
+
+    /// Public inputs of a proof exposing these values, in registration order
+    pub fn to_field_elements(&self) -> Vec<F> {
+        self.labeled_field_elements()
+            .into_iter()
+            .flat_map(|(_, elements)| elements)
+            .collect()
+    }
+
+    /// Public inputs of each exposed value, with the value's name
+    pub(crate) fn labeled_field_elements(&self) -> Vec<(&'static str, Vec<F>)> {
+        let mut public_inputs = vec![];
+        if let Some(message_hash) = &self.message_hash {
+            public_inputs.push(("message hash", bytes_to_u32_words(message_hash)));
+        }
+        match &self.signer {
+            None => {}
+            Some(SignerPublicValue::PublicKey(public_key)) => {
+                let public_key = public_key.clone().into_plonky2_public_key().0;
+                let mut elements = vec![];
+                for coordinate in [public_key.x, public_key.y] {
+                    let mut limbs = coordinate.to_canonical_biguint().to_u32_digits();
+                    limbs.resize(COORDINATE_LIMBS, 0);
+                    elements.extend(limbs.into_iter().map(F::from_canonical_u32));
+                }
+                public_inputs.push(("signer public key", elements));
+            }
+            Some(SignerPublicValue::Address(address)) => {
+                public_inputs.push(("signer address", bytes_to_u32_words(address.as_bytes())));
+            }
+        }
+        if let Some(intent_commitment) = &self.intent_commitment {
+            public_inputs.push(("intent commitment", intent_commitment.elements.to_vec()));
+        }
+        public_inputs
+    }
+}
+
+/// Poseidon commitment to an intent, from its structured hash
+pub fn intent_commitment(structured_hash: &[u8; 32]) -> HashOut<F> {
+    PoseidonHash::hash_no_pad(&bytes_to_u32_words(structured_hash))
+}
+
+/// In-circuit [`intent_commitment`], from the 32-byte string of the structured hash
+pub(crate) fn intent_commitment_target(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    structured_hash: &[BoolTarget],
+) -> HashOutTarget {
+    let message_words = circuit_builder.bytes_to_u32_words_be(structured_hash);
+    circuit_builder.hash_n_to_hash_no_pad::<PoseidonHash>(message_words)
+}
+
+/// Packs a byte string, whose length is a multiple of 4, into big-endian 32-bit words
+pub(crate) fn bytes_to_u32_words(bytes: &[u8]) -> Vec<F> {
+    bytes
+        .chunks(4)
+        .map(|word| F::from_canonical_u32(u32::from_be_bytes(word.try_into().unwrap())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use types::types::PrivateKey;
+
+    use super::*;
+
+    #[test]
+    fn it_works_intent_public_values_layout() {
+        let public_key = PublicKey::from_private_key(&PrivateKey::new());
+        let structured_hash = [7u8; 32];
+
+        let none = IntentPublicValues::new(
+            &PublicInputsOptions::default(),
+            structured_hash,
+            &public_key,
+        );
+        assert!(none.to_field_elements().is_empty());
+
+        let all =
+            IntentPublicValues::new(&PublicInputsOptions::all(), structured_hash, &public_key);
+        assert_eq!(all.options(), PublicInputsOptions::all());
+        let public_inputs = all.to_field_elements();
+        assert_eq!(public_inputs.len(), 8 + 5 + 4);
+        assert_eq!(public_inputs[0], F::from_canonical_u32(0x07070707));
+        assert_eq!(
+            public_inputs[8..13],
+            bytes_to_u32_words(Address::from_public_key(&public_key).as_bytes())
+        );
+        assert_eq!(
+            public_inputs[13..],
+            intent_commitment(&structured_hash).elements
+        );
+
+        let options = PublicInputsOptions::new(false, SignerPublicInput::PublicKey, false);
+        let public_key_values = IntentPublicValues::new(&options, structured_hash, &public_key);
+        assert_eq!(public_key_values.options(), options);
+        assert_eq!(
+            public_key_values.to_field_elements().len(),
+            2 * COORDINATE_LIMBS
+        );
+    }
+}
@@ -0,0 +1,931 @@
+//! Ring trades, coincidences of wants across several token pairs.
+//!
+//! The orders of a batch form a token graph, where each order is an edge from the token it
+//! sells to the token it buys. A ring is a cycle of orders through distinct tokens, each one
+//! buying the token the next one sells, such as `A → B`, `B → C` and `C → A`. In a ring
+//! settlement, every order is paid with the tokens sold by the next order, so that no token
+//! is left over. Each order receives the least amount its limit price allows, except the
+//! last order of the ring, which receives the tokens sold by the first one, and so keeps
+//! the ring's surplus. A ring is settled for the most tokens its first order can sell.
+//!
+//! Rings have no uniform price per token pair, so that the [`Solution`] of a [`RingFinder`]
+//! holds fills without clearing prices, and its rings are proven by constraining their fills
+//! to the witnessed intents' limits (see [`constrain_rings`]) rather than by a
+//! [`SwapSettlement`](crate::swap_settlement::SwapSettlement). The circuit's shape depends on
+//! which intents the rings fill, so that a verifier rebuilds it from the rings (see
+//! [`verify_rings_proof`]).
+use anyhow::anyhow;
+use num_bigint::BigUint;
+use plonky2::{
+    field::types::Field,
+    hash::{
+        hash_types::{HashOut, HashOutTarget},
+        poseidon::PoseidonHash,
+    },
+    iop::witness::PartialWitness,
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::{
+            CircuitConfig, CircuitData, CommonCircuitData, VerifierCircuitData,
+            VerifierOnlyCircuitData,
+        },
+        config::{GenericConfig, Hasher},
+        proof::ProofWithPublicInputs,
+    },
+};
+use plonky2_ecdsa::gadgets::biguint::{BigUintTarget, CircuitBuilderBiguint, WitnessBigUint};
+use types::types::{PublicKey, RecoverableSignature};
+
+use crate::{
+    aggregation::intent_batch_commitment,
+    auction::{Fill, Solution},
+    circuit::{ECDSAIntentCircuit, IntentCircuitInterface},
+    error::FillError,
+    intent::IntentSignatureVerifier,
+    order::{CircuitBuilderOrder, Order},
+    solver::{verify_signature_proofs, IntentSignature, Solver},
+    swap_intent::{SwapIntent, SwapIntentTarget, UINT256_LIMBS},
+    swap_settlement::{add_virtual_batch_targets, add_virtual_uint256_target},
+    swap_solver::SwapSolver,
+    D, F,
+};
+
+/// Longest ring looked for by default
+pub const DEFAULT_MAX_RING_LENGTH: usize = 3;
+
+/// Settlement of a ring of intents
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ring {
+    /// fills of the ring's intents, in ring order, each intent buying the tokens the next
+    /// one sells
+    pub fills: Vec<Fill>,
+}
+
+impl Ring {
+    /// Returns the indices of the ring's intents, in ring order.
+    pub fn intents(&self) -> Vec<usize> {
+        self.fills.iter().map(|fill| fill.intent).collect()
+    }
+}
+
+/// Finds and settles rings of [`SwapIntent`]s. Replays, cancellations and fills across
+/// batches are tracked by its [`SwapSolver`].
+#[derive(Clone, Debug)]
+pub struct RingFinder {
+    max_ring_length: usize,
+    swap_solver: SwapSolver,
+}
+
+impl Default for RingFinder {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_RING_LENGTH)
+    }
+}
+
+impl RingFinder {
+    /// Finder of rings of at most `max_ring_length` intents
+    pub fn new(max_ring_length: usize) -> Self {
+        Self {
+            max_ring_length,
+            swap_solver: SwapSolver::new(),
+        }
+    }
+
+    /// Finder tracking replays, cancellations and fills with `swap_solver`
+    pub fn with_swap_solver(mut self, swap_solver: SwapSolver) -> Self {
+        self.swap_solver = swap_solver;
+        self
+    }
+
+    pub fn swap_solver(&self) -> &SwapSolver {
+        &self.swap_solver
+    }
+
+    pub fn swap_solver_mut(&mut self) -> &mut SwapSolver {
+        &mut self.swap_solver
+    }
+
+    /// Records the fills of a solution for `intents`, once it is settled, so that later
+    /// batches do not overfill its intents. Nothing is recorded if a fill is rejected.
+    pub fn record_solution(
+        &mut self,
+        intents: &[SwapIntent],
+        solution: &Solution,
+    ) -> Result<(), FillError> {
+        let fills = solution.settled_fills();
+        self.swap_solver.record_fills(fills.iter().map(|fill| {
+            (
+                &intents[fill.intent],
+                &fill.sold_amount,
+                &fill.bought_amount,
+            )
+        }))
+    }
+
+    /// Settles the rings of `intents`, in the order they are found. An intent joins
+    /// several rings as long as its remaining size allows, and cancelled intents join none
+    pub fn find_rings(&self, intents: &[SwapIntent]) -> Vec<Ring> {
+        let zero = BigUint::from(0_u8);
+        let orders = intents.iter().map(SwapIntent::order).collect::<Vec<_>>();
+        let mut remaining = intents
+            .iter()
+            .map(|intent| {
+                if self.swap_solver.is_cancelled(intent) {
+                    zero.clone()
+                } else {
+                    self.swap_solver.fill_tracker().remaining(intent)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut rings = vec![];
+        for cycle in self.cycles(&orders) {
+            if cycle.iter().any(|&intent| remaining[intent] == zero) {
+                continue;
+            }
+            let Some(amounts) = settle(&cycle, &orders, &remaining) else {
+                continue;
+            };
+
+            let fills = cycle
+                .iter()
+                .enumerate()
+                .map(|(position, &intent)| Fill {
+                    intent,
+                    sold_amount: amounts[position].clone(),
+                    bought_amount: amounts[(position + 1) % cycle.len()].clone(),
+                })
+                .collect::<Vec<_>>();
+            // an order which is not partially fillable is filled as a whole
+            for fill in &fills {
+                remaining[fill.intent] = if orders[fill.intent].partially_fillable {
+                    &remaining[fill.intent] - &fill.sold_amount
+                } else {
+                    zero.clone()
+                };
+            }
+            rings.push(Ring { fills });
+        }
+        rings
+    }
+
+    /// Cycles of orders through distinct tokens, of at least two orders, each listed once
+    /// from its order with the lowest index
+    fn cycles(&self, orders: &[Order]) -> Vec<Vec<usize>> {
+        let mut cycles = vec![];
+        for start in 0..orders.len() {
+            if orders[start].sell_amount != BigUint::from(0_u8) {
+                self.extend_cycle(orders, &mut vec![start], &mut cycles);
+            }
+        }
+        cycles
+    }
+
+    fn extend_cycle(&self, orders: &[Order], path: &mut Vec<usize>, cycles: &mut Vec<Vec<usize>>) {
+        let start = &orders[path[0]];
+        let last = &orders[path[path.len() - 1]];
+        if path.len() >= 2 && last.buy_token == start.sell_token {
+            cycles.push(path.clone());
+            return;
+        }
+        if path.len() == self.max_ring_length {
+            return;
+        }
+        for next in path[0] + 1..orders.len() {
+            let order = &orders[next];
+            let revisits = order.buy_token != start.sell_token
+                && path
+                    .iter()
+                    .any(|&intent| orders[intent].sell_token == order.buy_token);
+            if order.sell_token != last.buy_token
+                || order.sell_amount == BigUint::from(0_u8)
+                || revisits
+            {
+                continue;
+            }
+            path.push(next);
+            self.extend_cycle(orders, path, cycles);
+            path.pop();
+        }
+    }
+}
+
+/// Ring fills of a batch, in ring order, without clearing prices
+impl Solver<SwapIntent> for RingFinder {
+    type Output = Solution;
+
+    fn execute_runtime(&self, intents: Vec<SwapIntent>) -> Self::Output {
+        Solution {
+            fills: self
+                .find_rings(&intents)
+                .into_iter()
+                .flat_map(|ring| ring.fills)
+                .collect(),
+            ..Solution::default()
+        }
+    }
+
+    fn generate_execute_proof<C: GenericConfig<D, F = F>>(
+        &self,
+        circuit_builder: &mut CircuitBuilder<F, D>,
+        partial_witness: &mut PartialWitness<F>,
+        signature_verifier: &IntentSignatureVerifier<C>,
+        intents: Vec<IntentSignature<SwapIntent, C>>,
+    ) -> Result<(), anyhow::Error> {
+        verify_signature_proofs(signature_verifier, &intents)?;
+
+        let intents = intents
+            .into_iter()
+            .map(|intent_signature| intent_signature.intent)
+            .collect::<Vec<_>>();
+        let rings = self.find_rings(&intents);
+        constrain_rings(circuit_builder, partial_witness, &intents, &rings)
+    }
+
+    fn verify_intents_signatures(
+        &mut self,
+        intents: Vec<(SwapIntent, RecoverableSignature)>,
+    ) -> Result<Vec<PublicKey>, anyhow::Error> {
+        self.swap_solver.verify_intents_signatures(intents)
+    }
+}
+
+/// Checks that `rings` settle intents of the batch of `intents`: each ring has at least two
+/// fills, each within its intent's limits and paid with the tokens the next fill sells, an
+/// intent which is not partially fillable is filled once, and the fills of an intent sell at
+/// most its size
+pub fn check_rings(intents: &[SwapIntent], rings: &[Ring]) -> Result<(), anyhow::Error> {
+    check_rings_shape(intents.len(), rings)?;
+
+    let zero = BigUint::from(0_u8);
+    let max_amount = BigUint::from(1_u8) << (32 * UINT256_LIMBS);
+    let orders = intents.iter().map(SwapIntent::order).collect::<Vec<_>>();
+    let mut intent_sold = vec![zero; intents.len()];
+    let mut intent_fills = vec![0_usize; intents.len()];
+    for (index, ring) in rings.iter().enumerate() {
+        for (position, fill) in ring.fills.iter().enumerate() {
+            let next = &ring.fills[(position + 1) % ring.fills.len()];
+            let (order, next_order) = (&orders[fill.intent], &orders[next.intent]);
+            if fill.sold_amount >= max_amount || fill.bought_amount >= max_amount {
+                return Err(anyhow!("Ring {} overflows an uint256", index));
+            }
+            if order.buy_token != next_order.sell_token || fill.bought_amount != next.sold_amount {
+                return Err(anyhow!(
+                    "Intent {} is not paid by the next intent of ring {}",
+                    fill.intent,
+                    index
+                ));
+            }
+            if !order.accepts_fill(&fill.sold_amount, &fill.bought_amount) {
+                return Err(anyhow!(
+                    "Ring {} does not satisfy the constraints of intent {}",
+                    index,
+                    fill.intent
+                ));
+            }
+            intent_sold[fill.intent] += &fill.sold_amount;
+            intent_fills[fill.intent] += 1;
+        }
+    }
+    for (intent, order) in orders.iter().enumerate() {
+        if intent_fills[intent] > 1 && !order.partially_fillable {
+            return Err(anyhow!(
+                "Intent {} is not partially fillable, but is filled more than once",
+                intent
+            ));
+        }
+        if intent_sold[intent] > order.sell_amount {
+            return Err(anyhow!("Fills of intent {} exceed its size", intent));
+        }
+    }
+    Ok(())
+}
+
+/// Checks that `rings` refer to a batch of `num_intents` intents, each ring having at least
+/// two fills
+fn check_rings_shape(num_intents: usize, rings: &[Ring]) -> Result<(), anyhow::Error> {
+    for (index, ring) in rings.iter().enumerate() {
+        if ring.fills.len() < 2 {
+            return Err(anyhow!("Ring {} has less than two fills", index));
+        }
+        if ring.fills.iter().any(|fill| fill.intent >= num_intents) {
+            return Err(anyhow!("Ring {} refers to an invalid intent", index));
+        }
+    }
+    Ok(())
+}
+
+/// Poseidon commitment to `rings`, as exposed by their proof
+pub fn rings_commitment(rings: &[Ring]) -> HashOut<F> {
+    let uint256_limbs = |value: &BigUint| {
+        let mut limbs = value.to_u32_digits();
+        limbs.resize(UINT256_LIMBS, 0);
+        limbs.into_iter().map(F::from_canonical_u32)
+    };
+
+    let mut elements = vec![];
+    for ring in rings {
+        elements.push(F::from_canonical_usize(ring.fills.len()));
+        for fill in &ring.fills {
+            elements.push(F::from_canonical_usize(fill.intent));
+            elements.extend(uint256_limbs(&fill.sold_amount));
+            elements.extend(uint256_limbs(&fill.bought_amount));
+        }
+    }
+    PoseidonHash::hash_no_pad(&elements)
+}
+
+/// Proof that rings settle a batch's intents
+pub struct RingsProofData<C: GenericConfig<D, F = F>> {
+    pub proof_with_pis: ProofWithPublicInputs<F, C, D>,
+    pub common: CommonCircuitData<F, D>,
+    pub verifier_only: VerifierOnlyCircuitData<C, D>,
+}
+
+/// Rings witnessed in a circuit, and constrained to settle the intents the circuit witnesses
+pub struct RingsTargets {
+    /// indices of the intents of each ring, in ring order
+    shape: Vec<Vec<usize>>,
+    /// amounts sold by the fills of each ring, each fill buying the amount the next one sells
+    sold_targets: Vec<Vec<BigUintTarget>>,
+    /// Poseidon commitment to the rings, see [`rings_commitment`]
+    pub commitment: HashOutTarget,
+}
+
+impl RingsTargets {
+    /// Adds the targets of rings filling the same intents (but not amounts) as `rings`,
+    /// settling the batch of `intent_targets`, and constrains them as [`check_rings`] checks
+    /// them
+    pub fn new(
+        circuit_builder: &mut CircuitBuilder<F, D>,
+        intent_targets: &[SwapIntentTarget],
+        rings: &[Ring],
+    ) -> Result<Self, anyhow::Error> {
+        check_rings_shape(intent_targets.len(), rings)?;
+
+        let order_targets = intent_targets
+            .iter()
+            .map(|intent_target| intent_target.order(circuit_builder))
+            .collect::<Vec<_>>();
+        let zero = circuit_builder.zero_biguint();
+        let mut intent_sold = vec![zero; intent_targets.len()];
+        let mut intent_fills = vec![0_usize; intent_targets.len()];
+        let mut commitment_elements = vec![];
+        let mut sold_targets = vec![];
+        for ring in rings {
+            let ring_sold_targets = ring
+                .fills
+                .iter()
+                .map(|_| add_virtual_uint256_target(circuit_builder))
+                .collect::<Vec<_>>();
+            commitment_elements
+                .push(circuit_builder.constant(F::from_canonical_usize(ring.fills.len())));
+            for (position, fill) in ring.fills.iter().enumerate() {
+                let next = (position + 1) % ring.fills.len();
+                let order_target = &order_targets[fill.intent];
+                let next_order_target = &order_targets[ring.fills[next].intent];
+                circuit_builder
+                    .connect_address(&order_target.buy_token, &next_order_target.sell_token);
+                let (sold_target, bought_target) =
+                    (&ring_sold_targets[position], &ring_sold_targets[next]);
+                circuit_builder.assert_order_accepts_fill(order_target, sold_target, bought_target);
+                intent_sold[fill.intent] =
+                    circuit_builder.add_biguint(&intent_sold[fill.intent], sold_target);
+                intent_fills[fill.intent] += 1;
+
+                commitment_elements
+                    .push(circuit_builder.constant(F::from_canonical_usize(fill.intent)));
+                for amount in [sold_target, bought_target] {
+                    commitment_elements.extend(amount.limbs.iter().map(|limb| limb.0));
+                }
+            }
+            sold_targets.push(ring_sold_targets);
+        }
+        // an intent filled several times is partially fillable, and its fills sell at most
+        // its size
+        for (intent, order_target) in order_targets.iter().enumerate() {
+            if intent_fills[intent] > 1 {
+                circuit_builder.assert_one(order_target.partially_fillable.target);
+                let within_size =
+                    circuit_builder.cmp_biguint(&intent_sold[intent], &order_target.sell_amount);
+                circuit_builder.assert_one(within_size.target);
+            }
+        }
+        let commitment = circuit_builder.hash_n_to_hash_no_pad::<PoseidonHash>(commitment_elements);
+
+        Ok(Self {
+            shape: rings.iter().map(Ring::intents).collect(),
+            sold_targets,
+            commitment,
+        })
+    }
+
+    /// Witnesses `rings`, which should fill the intents the targets were added for
+    pub fn set_targets(
+        &self,
+        partial_witness: &mut PartialWitness<F>,
+        rings: &[Ring],
+    ) -> Result<(), anyhow::Error> {
+        if rings.iter().map(Ring::intents).collect::<Vec<_>>() != self.shape {
+            return Err(anyhow!("Rings do not have the circuit's shape"));
+        }
+        for (ring, ring_sold_targets) in rings.iter().zip(&self.sold_targets) {
+            for (fill, sold_target) in ring.fills.iter().zip(ring_sold_targets) {
+                partial_witness.set_biguint_target(sold_target, &fill.sold_amount);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Adds the targets of a batch of `num_intents` intents settled by rings like `rings`,
+/// exposing the batch commitment then the rings commitment
+fn add_batch_rings_targets(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    num_intents: usize,
+    rings: &[Ring],
+) -> Result<(Vec<SwapIntentTarget>, RingsTargets), anyhow::Error> {
+    let (intent_targets, batch_commitment_target) =
+        add_virtual_batch_targets(circuit_builder, num_intents);
+    let rings_targets = RingsTargets::new(circuit_builder, &intent_targets, rings)?;
+
+    circuit_builder.register_public_inputs(&batch_commitment_target.elements);
+    circuit_builder.register_public_inputs(&rings_targets.commitment.elements);
+    Ok((intent_targets, rings_targets))
+}
+
+fn set_batch_rings_targets(
+    partial_witness: &mut PartialWitness<F>,
+    intent_targets: &[SwapIntentTarget],
+    rings_targets: &RingsTargets,
+    intents: &[SwapIntent],
+    rings: &[Ring],
+) -> Result<(), anyhow::Error> {
+    for (intent, intent_target) in intents.iter().zip(intent_targets) {
+        intent.set_intent_target(partial_witness, intent_target);
+    }
+    rings_targets.set_targets(partial_witness, rings)
+}
+
+/// Constrains `rings` to settle the witnessed `intents`, in a circuit exposing their batch
+/// commitment (see [`crate::aggregation`]) then the [`rings_commitment`], as the rings
+/// circuit (see [`verify_rings_proof`])
+pub fn constrain_rings(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    partial_witness: &mut PartialWitness<F>,
+    intents: &[SwapIntent],
+    rings: &[Ring],
+) -> Result<(), anyhow::Error> {
+    if intents.is_empty() {
+        return Err(anyhow!("Batch should not be empty"));
+    }
+    check_rings(intents, rings)?;
+
+    let (intent_targets, rings_targets) =
+        add_batch_rings_targets(circuit_builder, intents.len(), rings)?;
+    set_batch_rings_targets(
+        partial_witness,
+        &intent_targets,
+        &rings_targets,
+        intents,
+        rings,
+    )
+}
+
+/// Rings circuit for batches of a given size, settled by rings filling given intents
+pub struct RingsCircuit<C: GenericConfig<D, F = F>> {
+    data: CircuitData<F, C, D>,
+    intent_targets: Vec<SwapIntentTarget>,
+    rings_targets: RingsTargets,
+}
+
+impl<C: GenericConfig<D, F = F>> RingsCircuit<C> {
+    /// Builds the circuit for a batch of `num_intents` intents, settled by rings filling the
+    /// same intents (but not amounts) as `rings`
+    pub fn new(num_intents: usize, rings: &[Ring]) -> Result<Self, anyhow::Error> {
+        if num_intents == 0 {
+            return Err(anyhow!("Batch should not be empty"));
+        }
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut circuit_builder = CircuitBuilder::<F, D>::new(config);
+        let (intent_targets, rings_targets) =
+            add_batch_rings_targets(&mut circuit_builder, num_intents, rings)?;
+
+        Ok(Self {
+            data: circuit_builder.build::<C>(),
+            intent_targets,
+            rings_targets,
+        })
+    }
+
+    /// Proves that `rings` settle `intents`
+    pub fn prove(
+        &self,
+        intents: &[SwapIntent],
+        rings: &[Ring],
+    ) -> Result<RingsProofData<C>, anyhow::Error> {
+        if intents.len() != self.intent_targets.len() {
+            return Err(anyhow!("Batch does not have the circuit's size"));
+        }
+        check_rings(intents, rings)?;
+
+        let mut partial_witness = PartialWitness::<F>::new();
+        set_batch_rings_targets(
+            &mut partial_witness,
+            &self.intent_targets,
+            &self.rings_targets,
+            intents,
+            rings,
+        )?;
+
+        let proof_with_pis = self.data.prove(partial_witness)?;
+        Ok(RingsProofData {
+            proof_with_pis,
+            common: self.data.common.clone(),
+            verifier_only: self.data.verifier_only.clone(),
+        })
+    }
+
+    pub fn verifier_data(&self) -> VerifierCircuitData<F, C, D> {
+        self.data.verifier_data()
+    }
+}
+
+/// Proves that `rings` settle `intents`
+pub fn prove_rings<C: GenericConfig<D, F = F>>(
+    intents: &[SwapIntent],
+    rings: &[Ring],
+) -> Result<RingsProofData<C>, anyhow::Error> {
+    RingsCircuit::<C>::new(intents.len(), rings)?.prove(intents, rings)
+}
+
+/// Verifies a rings proof for the batch of intents with the given commitments, against the
+/// circuit rebuilt from `rings`
+pub fn verify_rings_proof<C: GenericConfig<D, F = F>>(
+    rings_proof: &RingsProofData<C>,
+    intent_commitments: &[HashOut<F>],
+    rings: &[Ring],
+) -> Result<(), anyhow::Error> {
+    let expected_public_inputs = [
+        intent_batch_commitment(intent_commitments).elements,
+        rings_commitment(rings).elements,
+    ]
+    .concat();
+    if rings_proof.proof_with_pis.public_inputs != expected_public_inputs {
+        return Err(anyhow!(
+            "Rings proof does not cover the given intents and rings"
+        ));
+    }
+    let verifier_data = RingsCircuit::<C>::new(intent_commitments.len(), rings)?.verifier_data();
+    if rings_proof.verifier_only.circuit_digest != verifier_data.verifier_only.circuit_digest {
+        return Err(anyhow!("Rings proof is not from the rings circuit"));
+    }
+    verifier_data.verify(rings_proof.proof_with_pis.clone())
+}
+
+/// Least tokens `order` accepts to buy for `sold_amount` tokens
+fn min_bought(order: &Order, sold_amount: &BigUint) -> BigUint {
+    let limit = sold_amount * &order.min_buy_amount;
+    let bought = &limit / &order.sell_amount;
+    if &bought * &order.sell_amount == limit {
+        bought
+    } else {
+        bought + 1_u8
+    }
+}
+
+/// Amounts sold by the orders of a cycle when its first order sells `first_sold` tokens,
+/// each order being paid the least its limit allows, if no order exceeds its remaining size
+fn propagate(
+    cycle: &[usize],
+    orders: &[Order],
+    remaining: &[BigUint],
+    first_sold: BigUint,
+) -> Option<Vec<BigUint>> {
+    let mut amounts = vec![first_sold];
+    for (&intent, &next) in cycle.iter().zip(&cycle[1..]) {
+        let bought = min_bought(&orders[intent], &amounts[amounts.len() - 1]).max(1_u8.into());
+        if bought > remaining[next] {
+            return None;
+        }
+        // an order which is not partially fillable sells its whole size
+        amounts.push(if orders[next].partially_fillable {
+            bought
+        } else {
+            remaining[next].clone()
+        });
+    }
+    Some(amounts)
+}
+
+/// Amounts sold by the orders of a cycle, for the most tokens its first order can sell, if
+/// every order's limit is satisfied
+fn settle(cycle: &[usize], orders: &[Order], remaining: &[BigUint]) -> Option<Vec<BigUint>> {
+    let first = cycle[0];
+    let first_sold = if orders[first].partially_fillable {
+        // requirements grow with the first order's fill, search its largest possible fill
+        let (mut low, mut high) = (BigUint::from(1_u8), remaining[first].clone());
+        propagate(cycle, orders, remaining, low.clone())?;
+        while low < high {
+            let middle = (&low + &high + 1_u8) / 2_u8;
+            if propagate(cycle, orders, remaining, middle.clone()).is_some() {
+                low = middle;
+            } else {
+                high = middle - 1_u8;
+            }
+        }
+        low
+    } else {
+        remaining[first].clone()
+    };
+
+    let amounts = propagate(cycle, orders, remaining, first_sold)?;
+    cycle
+        .iter()
+        .enumerate()
+        .all(|(position, &intent)| {
+            let bought = &amounts[(position + 1) % cycle.len()];
+            orders[intent].accepts_fill(&amounts[position], bought)
+        })
+        .then_some(amounts)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        nonce::NonceTracker,
+        swap_settlement::swap_intent_commitments,
+        test_utils::{
+            proven_intent_signatures, proven_swap_intents, signature_prover, token,
+            token_swap_intent, ProofConfig,
+        },
+    };
+
+    use super::*;
+
+    fn fill(intent: usize, sold_amount: u64, bought_amount: u64) -> Fill {
+        Fill {
+            intent,
+            sold_amount: BigUint::from(sold_amount),
+            bought_amount: BigUint::from(bought_amount),
+        }
+    }
+
+    #[test]
+    fn it_works_ring_finder_three_tokens() {
+        let intents = vec![
+            token_swap_intent(1, 2, 100, 90, true),
+            token_swap_intent(2, 3, 100, 90, true),
+            // a dead end
+            token_swap_intent(3, 4, 10, 10, true),
+            token_swap_intent(3, 1, 100, 90, true),
+        ];
+
+        let rings = RingFinder::default().find_rings(&intents);
+        assert_eq!(
+            rings,
+            vec![Ring {
+                fills: vec![fill(0, 100, 90), fill(1, 90, 81), fill(3, 81, 100)]
+            }]
+        );
+        assert_eq!(rings[0].intents(), vec![0, 1, 3]);
+        assert!(RingFinder::new(2).find_rings(&intents).is_empty());
+
+        // the limits cannot all be satisfied
+        let mut intents = intents;
+        intents[3] = token_swap_intent(3, 1, 100, 130, true);
+        assert!(RingFinder::default().find_rings(&intents).is_empty());
+    }
+
+    #[test]
+    fn it_works_ring_finder_whole_intents() {
+        let intents = vec![
+            token_swap_intent(1, 2, 100, 90, false),
+            token_swap_intent(2, 3, 200, 180, true),
+            token_swap_intent(3, 1, 100, 90, false),
+            // a second ring for the remaining size of the partially fillable intent
+            token_swap_intent(3, 2, 50, 50, true),
+            // a ring with an intent already filled as a whole
+            token_swap_intent(1, 3, 100, 40, false),
+        ];
+
+        let rings = RingFinder::default().find_rings(&intents);
+        assert_eq!(
+            rings,
+            vec![
+                Ring {
+                    fills: vec![fill(0, 100, 90), fill(1, 90, 100), fill(2, 100, 100)]
+                },
+                Ring {
+                    fills: vec![fill(1, 55, 50), fill(3, 50, 55)]
+                },
+            ]
+        );
+        for fill in rings.iter().flat_map(|ring| &ring.fills) {
+            assert!(intents[fill.intent]
+                .order()
+                .accepts_fill(&fill.sold_amount, &fill.bought_amount));
+        }
+    }
+
+    #[test]
+    fn it_works_ring_finder_skips_cancelled_intents() {
+        let mut intents = vec![
+            token_swap_intent(1, 2, 100, 90, true),
+            token_swap_intent(2, 3, 100, 90, true),
+            token_swap_intent(3, 1, 100, 90, true),
+        ];
+        intents[1].inputs.nonce = 1;
+        let mut nonce_tracker = NonceTracker::new();
+        nonce_tracker.cancel_nonce(&token(255), 1);
+
+        let ring_finder = RingFinder::default()
+            .with_swap_solver(SwapSolver::new().with_nonce_tracker(nonce_tracker));
+        assert!(ring_finder.find_rings(&intents).is_empty());
+    }
+
+    #[test]
+    fn it_works_ring_finder_records_solution() {
+        let intents = vec![
+            token_swap_intent(1, 2, 100, 90, true),
+            token_swap_intent(2, 3, 100, 90, true),
+            token_swap_intent(3, 1, 200, 180, true),
+        ];
+        let mut ring_finder = RingFinder::default();
+
+        let solution = ring_finder.execute_runtime(intents.clone());
+        assert_eq!(
+            solution,
+            Solution {
+                fills: vec![fill(0, 100, 90), fill(1, 90, 81), fill(2, 81, 100)],
+                ..Solution::default()
+            }
+        );
+        ring_finder.record_solution(&intents, &solution).unwrap();
+        assert_eq!(
+            ring_finder
+                .swap_solver()
+                .fill_tracker()
+                .remaining(&intents[2]),
+            BigUint::from(119_u8)
+        );
+
+        // the first intent is filled, so that no ring is left
+        assert!(ring_finder.find_rings(&intents).is_empty());
+        assert!(ring_finder.record_solution(&intents, &solution).is_err());
+    }
+
+    #[test]
+    fn it_works_check_rings() {
+        let intents = vec![
+            token_swap_intent(1, 2, 100, 90, true),
+            token_swap_intent(2, 3, 100, 90, true),
+            token_swap_intent(3, 1, 100, 90, false),
+        ];
+        let ring = |fills| Ring { fills };
+        check_rings(
+            &intents,
+            &[ring(vec![
+                fill(0, 100, 90),
+                fill(1, 90, 100),
+                fill(2, 100, 100),
+            ])],
+        )
+        .unwrap();
+
+        // the second intent is paid less than the third one sells
+        let error = check_rings(
+            &intents,
+            &[ring(vec![
+                fill(0, 100, 90),
+                fill(1, 90, 99),
+                fill(2, 100, 100),
+            ])],
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("is not paid by the next intent"));
+        // the third intent receives less than its minimum
+        let error = check_rings(
+            &intents,
+            &[ring(vec![
+                fill(0, 80, 90),
+                fill(1, 90, 100),
+                fill(2, 100, 80),
+            ])],
+        )
+        .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("does not satisfy the constraints of intent 2"));
+        // the whole intent is filled twice, each time within its limits
+        let mut intents = intents;
+        intents[2].constraints.min_base_token_amount = BigUint::from(50_u8);
+        let error = check_rings(
+            &intents,
+            &[
+                ring(vec![fill(0, 50, 45), fill(1, 45, 100), fill(2, 100, 50)]),
+                ring(vec![fill(0, 50, 45), fill(1, 45, 100), fill(2, 100, 50)]),
+            ],
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("filled more than once"));
+    }
+
+    #[test]
+    fn it_works_rings_circuit() {
+        let intents = vec![
+            token_swap_intent(1, 2, 100, 90, true),
+            token_swap_intent(2, 3, 100, 90, true),
+            token_swap_intent(3, 1, 200, 180, true),
+        ];
+        let rings = RingFinder::default().find_rings(&intents);
+        let intent_commitments = swap_intent_commitments(&intents);
+
+        let rings_proof = prove_rings::<ProofConfig>(&intents, &rings).unwrap();
+        verify_rings_proof(&rings_proof, &intent_commitments, &rings).unwrap();
+
+        // the proof covers neither another batch nor other rings
+        assert!(verify_rings_proof(&rings_proof, &intent_commitments[..2], &rings).is_err());
+        let mut other_rings = rings.clone();
+        other_rings[0].fills[0].sold_amount = BigUint::from(90_u8);
+        other_rings[0].fills[2].bought_amount = BigUint::from(90_u8);
+        assert!(verify_rings_proof(&rings_proof, &intent_commitments, &other_rings).is_err());
+
+        // invalid rings are not proven
+        let mut overfilled = intents;
+        overfilled[0].inputs.quote_amount = BigUint::from(90_u8);
+        assert!(prove_rings::<ProofConfig>(&overfilled, &rings).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn it_fails_rings_circuit_whole_intent_filled_twice() {
+        let intents = vec![
+            token_swap_intent(1, 2, 100, 90, true),
+            token_swap_intent(2, 3, 100, 90, true),
+            token_swap_intent(3, 1, 100, 50, false),
+        ];
+        // each ring is within the limits, and the fills of each intent within its size
+        let ring = Ring {
+            fills: vec![fill(0, 50, 45), fill(1, 45, 50), fill(2, 50, 50)],
+        };
+        let rings = vec![ring.clone(), ring];
+
+        // witnessed without the native check, which rejects the rings
+        let mut circuit_builder =
+            CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let mut partial_witness = PartialWitness::new();
+        let (intent_targets, rings_targets) =
+            add_batch_rings_targets(&mut circuit_builder, intents.len(), &rings).unwrap();
+        set_batch_rings_targets(
+            &mut partial_witness,
+            &intent_targets,
+            &rings_targets,
+            &intents,
+            &rings,
+        )
+        .unwrap();
+        let _ = circuit_builder
+            .build::<ProofConfig>()
+            .prove(partial_witness);
+    }
+
+    #[test]
+    fn it_works_ring_finder_generate_execute_proof() {
+        let proven_intents = proven_swap_intents();
+        let intents = proven_intents
+            .iter()
+            .map(|proven_intent| proven_intent.intent.clone())
+            .collect::<Vec<_>>();
+        let intent_signatures = proven_intent_signatures();
+        let ring_finder = RingFinder::default();
+
+        let mut circuit_builder =
+            CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let mut partial_witness = PartialWitness::new();
+        ring_finder
+            .generate_execute_proof(
+                &mut circuit_builder,
+                &mut partial_witness,
+                &signature_prover().verifier().unwrap(),
+                intent_signatures,
+            )
+            .unwrap();
+        let circuit_data = circuit_builder.build::<ProofConfig>();
+        let proof_with_pis = circuit_data.prove(partial_witness).unwrap();
+
+        // the opposing intents form a ring of two
+        let rings = ring_finder.find_rings(&intents);
+        assert_eq!(rings.len(), 1);
+        assert_eq!(
+            proof_with_pis.public_inputs,
+            [
+                intent_batch_commitment(&swap_intent_commitments(&intents)).elements,
+                rings_commitment(&rings).elements,
+            ]
+            .concat()
+        );
+        circuit_data.verify(proof_with_pis).unwrap();
+    }
+}
@@ -0,0 +1,173 @@
+//! Metrics scoring solutions of a batch of swap intents, higher scores being better.
+//!
+//! Surplus and volume span several tokens, so that they are valued at fixed reference
+//! prices, as the batch's fixed price of the README. A token without a reference price
+//! counts for nothing.
+use std::collections::HashMap;
+
+use num_bigint::BigUint;
+use types::types::Address;
+
+use crate::{auction::Solution, swap_intent::SwapIntent};
+
+/// Metric scoring a solution of `intents`
+pub trait SolutionScorer {
+    /// Name of the metric, as reported in score breakdowns
+    fn metric(&self) -> &'static str;
+    fn score(&self, intents: &[SwapIntent], solution: &Solution) -> BigUint;
+}
+
+/// Number of intents filled, at least in part
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FilledIntentsScorer;
+
+impl SolutionScorer for FilledIntentsScorer {
+    fn metric(&self) -> &'static str {
+        "filled_intents"
+    }
+
+    fn score(&self, _intents: &[SwapIntent], solution: &Solution) -> BigUint {
+        BigUint::from(solution.filled_intents().len())
+    }
+}
+
+/// Reference price of every token, the value of one token unit
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReferencePrices {
+    prices: HashMap<Address, BigUint>,
+}
+
+impl ReferencePrices {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_price(mut self, token: Address, price: BigUint) -> Self {
+        self.prices.insert(token, price);
+        self
+    }
+
+    /// Value of `amount` tokens `token`
+    pub fn value(&self, token: Address, amount: &BigUint) -> BigUint {
+        self.prices
+            .get(&token)
+            .map_or_else(BigUint::default, |price| amount * price)
+    }
+}
+
+/// Total surplus of the users, the tokens their intents buy beyond their limit prices
+#[derive(Clone, Debug, Default)]
+pub struct SurplusScorer {
+    prices: ReferencePrices,
+}
+
+impl SurplusScorer {
+    pub fn new(prices: ReferencePrices) -> Self {
+        Self { prices }
+    }
+}
+
+impl SolutionScorer for SurplusScorer {
+    fn metric(&self) -> &'static str {
+        "surplus"
+    }
+
+    fn score(&self, intents: &[SwapIntent], solution: &Solution) -> BigUint {
+        solution
+            .settled_fills()
+            .iter()
+            .filter_map(|fill| {
+                let order = intents.get(fill.intent)?.order();
+                let value = &fill.bought_amount * &order.sell_amount;
+                let limit = &fill.sold_amount * &order.min_buy_amount;
+                // tokens bought beyond the limit, rounded down
+                let surplus = (value >= limit).then(|| (value - limit) / &order.sell_amount)?;
+                Some(self.prices.value(order.buy_token, &surplus))
+            })
+            .sum()
+    }
+}
+
+/// Total volume, the value of the tokens sold by the intents
+#[derive(Clone, Debug, Default)]
+pub struct VolumeScorer {
+    prices: ReferencePrices,
+}
+
+impl VolumeScorer {
+    pub fn new(prices: ReferencePrices) -> Self {
+        Self { prices }
+    }
+}
+
+impl SolutionScorer for VolumeScorer {
+    fn metric(&self) -> &'static str {
+        "volume"
+    }
+
+    fn score(&self, intents: &[SwapIntent], solution: &Solution) -> BigUint {
+        solution
+            .settled_fills()
+            .iter()
+            .filter_map(|fill| {
+                let order = intents.get(fill.intent)?.order();
+                Some(self.prices.value(order.sell_token, &fill.sold_amount))
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{auction::Fill, swap_intent::SwapDirection, test_utils::swap_intent};
+
+    use super::*;
+
+    #[test]
+    fn it_works_solution_scorers() {
+        let intents = vec![
+            swap_intent(SwapDirection::Buy, 100, 50, true),
+            swap_intent(SwapDirection::Sell, 60, 30, true),
+            swap_intent(SwapDirection::Sell, 10, 10, true),
+        ];
+        // the buyer sells 90 quote tokens for 50 base tokens, 5 beyond its limit, and the
+        // seller sells 30 base tokens for 90 quote tokens, 30 beyond its limit
+        let solution = Solution {
+            fills: vec![
+                Fill {
+                    intent: 0,
+                    sold_amount: BigUint::from(90_u8),
+                    bought_amount: BigUint::from(50_u8),
+                },
+                Fill {
+                    intent: 1,
+                    sold_amount: BigUint::from(30_u8),
+                    bought_amount: BigUint::from(90_u8),
+                },
+            ],
+            ..Solution::default()
+        };
+        let prices = ReferencePrices::new()
+            .with_price(Address::new([125; 20]), BigUint::from(1_u8))
+            .with_price(Address::new([64; 20]), BigUint::from(3_u8));
+
+        assert_eq!(
+            FilledIntentsScorer.score(&intents, &solution),
+            BigUint::from(2_u8)
+        );
+        // 5 base tokens and 30 quote tokens
+        assert_eq!(
+            SurplusScorer::new(prices.clone()).score(&intents, &solution),
+            BigUint::from(45_u8)
+        );
+        // 90 quote tokens and 30 base tokens
+        assert_eq!(
+            VolumeScorer::new(prices).score(&intents, &solution),
+            BigUint::from(180_u8)
+        );
+        assert_eq!(
+            VolumeScorer::default().score(&intents, &solution),
+            BigUint::default()
+        );
+    }
+}
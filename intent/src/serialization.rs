@@ -0,0 +1,308 @@
+//! Binary serialization of circuit data and of signature proofs.
+//!
+//! plonky2 0.1.3 only serializes proofs, as the gates and witness generators held by
+//! `CommonCircuitData` and `ProverOnlyCircuitData` are trait objects without serializers.
+//! The verifier only data, a Merkle cap and the circuit digest, is written with plonky2's
+//! own primitives, after a version byte.
+//!
+//! A [`SignatureProofData`] is written as a version byte, the length of its verifier only
+//! data as a little-endian `u32`, the verifier only data, then the proof with its public
+//! inputs. Its common data is not written: the deserializing side provides it, from its
+//! own build of the signature circuit (see [`crate::prover::IntentSignatureProver`]).
+use plonky2::{
+    plonk::{
+        circuit_data::{CommonCircuitData, VerifierOnlyCircuitData},
+        config::GenericConfig,
+        proof::ProofWithPublicInputs,
+    },
+    util::serialization::{Buffer, Read, Remaining, Write},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{error::SerializationError, intent::SignatureProofData, D, F};
+
+/// Version of the verifier only data format
+pub const VERIFIER_DATA_VERSION: u8 = 1;
+/// Version of the signature proof data format
+pub const SIGNATURE_PROOF_DATA_VERSION: u8 = 1;
+
+pub fn verifier_only_data_to_bytes<C: GenericConfig<D, F = F>>(
+    verifier_only: &VerifierOnlyCircuitData<C, D>,
+) -> Vec<u8> {
+    let cap_height = verifier_only.constants_sigmas_cap.height();
+    let mut bytes = vec![VERIFIER_DATA_VERSION];
+    bytes
+        .write_u8(cap_height as u8)
+        .and_then(|_| bytes.write_merkle_cap(&verifier_only.constants_sigmas_cap))
+        .and_then(|_| bytes.write_hash::<F, C::Hasher>(verifier_only.circuit_digest))
+        .expect("Writing to a byte-vector cannot fail.");
+    bytes
+}
+
+/// Reads the verifier only data of a circuit, whose `common` data decides the height of its
+/// Merkle cap
+pub fn verifier_only_data_from_bytes<C: GenericConfig<D, F = F>>(
+    bytes: Vec<u8>,
+    common: &CommonCircuitData<F, D>,
+) -> Result<VerifierOnlyCircuitData<C, D>, SerializationError> {
+    let mut buffer = Buffer::new(bytes);
+    let invalid = |_| SerializationError::InvalidEncoding("verifier data");
+    let version = buffer.read_u8().map_err(invalid)?;
+    if version != VERIFIER_DATA_VERSION {
+        return Err(SerializationError::UnsupportedVersion(
+            "verifier data",
+            version,
+        ));
+    }
+    let cap_height = buffer.read_u8().map_err(invalid)? as usize;
+    let expected_cap_height = common.config.fri_config.cap_height;
+    if cap_height != expected_cap_height {
+        return Err(SerializationError::CapHeightMismatch {
+            expected: expected_cap_height,
+            actual: cap_height,
+        });
+    }
+    let constants_sigmas_cap = buffer
+        .read_merkle_cap::<F, C::Hasher>(cap_height)
+        .map_err(invalid)?;
+    let circuit_digest = buffer.read_hash::<F, C::Hasher>().map_err(invalid)?;
+    if !buffer.is_empty() {
+        return Err(SerializationError::InvalidEncoding("verifier data"));
+    }
+    Ok(VerifierOnlyCircuitData {
+        constants_sigmas_cap,
+        circuit_digest,
+    })
+}
+
+/// Hex/JSON envelope of a [`SignatureProofData`], whose fields are `0x` prefixed hex strings
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignatureProofEnvelope {
+    pub version: u8,
+    /// verifier only data, in its binary format
+    pub verifier_data: String,
+    /// proof with its public inputs, in plonky2's binary format
+    pub proof: String,
+}
+
+impl SignatureProofEnvelope {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Serializing an envelope cannot fail.")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, SerializationError> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+fn from_hex(value: &str) -> Result<Vec<u8>, SerializationError> {
+    let value = value
+        .strip_prefix("0x")
+        .ok_or(SerializationError::MissingHexPrefix)?;
+    Ok(hex::decode(value)?)
+}
+
+fn proof_from_bytes<C: GenericConfig<D, F = F>>(
+    bytes: Vec<u8>,
+    common: &CommonCircuitData<F, D>,
+) -> Result<ProofWithPublicInputs<F, C, D>, SerializationError> {
+    let proof_with_pis = ProofWithPublicInputs::from_bytes(bytes, common)
+        .map_err(|_| SerializationError::InvalidEncoding("proof"))?;
+    if proof_with_pis.public_inputs.len() != common.num_public_inputs {
+        return Err(SerializationError::InvalidEncoding("proof"));
+    }
+    Ok(proof_with_pis)
+}
+
+impl<C: GenericConfig<D, F = F>> SignatureProofData<C> {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let verifier_data = verifier_only_data_to_bytes(&self.verifier_only);
+        let mut bytes = vec![SIGNATURE_PROOF_DATA_VERSION];
+        bytes.extend((verifier_data.len() as u32).to_le_bytes());
+        bytes.extend(verifier_data);
+        bytes.extend(self.proof_with_pis.to_bytes());
+        bytes
+    }
+
+    /// Reads a signature proof, whose `common` data is the one of the signature circuit
+    pub fn from_bytes(
+        bytes: &[u8],
+        common: &CommonCircuitData<F, D>,
+    ) -> Result<Self, SerializationError> {
+        let (&version, bytes) = bytes
+            .split_first()
+            .ok_or(SerializationError::InvalidEncoding("signature proof"))?;
+        if version != SIGNATURE_PROOF_DATA_VERSION {
+            return Err(SerializationError::UnsupportedVersion(
+                "signature proof",
+                version,
+            ));
+        }
+        if bytes.len() < 4 {
+            return Err(SerializationError::InvalidEncoding("signature proof"));
+        }
+        let (length, bytes) = bytes.split_at(4);
+        let length = u32::from_le_bytes(length.try_into().unwrap()) as usize;
+        if bytes.len() < length {
+            return Err(SerializationError::InvalidEncoding("signature proof"));
+        }
+        let (verifier_data, proof) = bytes.split_at(length);
+
+        Ok(Self {
+            proof_with_pis: proof_from_bytes(proof.to_vec(), common)?,
+            common: common.clone(),
+            verifier_only: verifier_only_data_from_bytes(verifier_data.to_vec(), common)?,
+        })
+    }
+
+    /// `0x` prefixed hex encoding of [`SignatureProofData::to_bytes`]
+    pub fn to_hex(&self) -> String {
+        to_hex(&self.to_bytes())
+    }
+
+    pub fn from_hex(
+        value: &str,
+        common: &CommonCircuitData<F, D>,
+    ) -> Result<Self, SerializationError> {
+        Self::from_bytes(&from_hex(value)?, common)
+    }
+
+    pub fn to_envelope(&self) -> SignatureProofEnvelope {
+        SignatureProofEnvelope {
+            version: SIGNATURE_PROOF_DATA_VERSION,
+            verifier_data: to_hex(&verifier_only_data_to_bytes(&self.verifier_only)),
+            proof: to_hex(&self.proof_with_pis.to_bytes()),
+        }
+    }
+
+    pub fn from_envelope(
+        envelope: &SignatureProofEnvelope,
+        common: &CommonCircuitData<F, D>,
+    ) -> Result<Self, SerializationError> {
+        if envelope.version != SIGNATURE_PROOF_DATA_VERSION {
+            return Err(SerializationError::UnsupportedVersion(
+                "signature proof",
+                envelope.version,
+            ));
+        }
+        Ok(Self {
+            proof_with_pis: proof_from_bytes(from_hex(&envelope.proof)?, common)?,
+            common: common.clone(),
+            verifier_only: verifier_only_data_from_bytes(
+                from_hex(&envelope.verifier_data)?,
+                common,
+            )?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::{
+        field::types::Field,
+        iop::witness::{PartialWitness, WitnessWrite},
+        plonk::{
+            circuit_builder::CircuitBuilder, circuit_data::CircuitConfig,
+            config::PoseidonGoldilocksConfig,
+        },
+    };
+
+    use super::*;
+
+    type C = PoseidonGoldilocksConfig;
+
+    /// Proof of a small circuit, standing in for the signature circuit
+    fn signature_proof_data() -> SignatureProofData<C> {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let target = builder.add_virtual_public_input();
+        builder.range_check(target, 8);
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::<F>::new();
+        pw.set_target(target, F::from_canonical_u8(42));
+        SignatureProofData {
+            proof_with_pis: data.prove(pw).unwrap(),
+            common: data.common,
+            verifier_only: data.verifier_only,
+        }
+    }
+
+    #[test]
+    fn it_works_verifier_only_data_serialization() {
+        let signature_proof_data = signature_proof_data();
+        let common = &signature_proof_data.common;
+
+        let bytes = verifier_only_data_to_bytes(&signature_proof_data.verifier_only);
+        assert_eq!(
+            verifier_only_data_from_bytes::<C>(bytes.clone(), common).unwrap(),
+            signature_proof_data.verifier_only
+        );
+
+        let mut other_version = bytes.clone();
+        other_version[0] = VERIFIER_DATA_VERSION + 1;
+        assert!(matches!(
+            verifier_only_data_from_bytes::<C>(other_version, common),
+            Err(SerializationError::UnsupportedVersion(_, _))
+        ));
+        // the cap height is the circuit's, rather than an untrusted one
+        let mut other_cap_height = bytes.clone();
+        other_cap_height[1] = u8::MAX;
+        assert!(matches!(
+            verifier_only_data_from_bytes::<C>(other_cap_height, common),
+            Err(SerializationError::CapHeightMismatch { actual: 255, .. })
+        ));
+        assert!(
+            verifier_only_data_from_bytes::<C>(bytes[..bytes.len() - 1].to_vec(), common).is_err()
+        );
+    }
+
+    #[test]
+    fn it_works_signature_proof_data_serialization() {
+        let signature_proof_data = signature_proof_data();
+        let common = &signature_proof_data.common;
+
+        let decoded =
+            SignatureProofData::<C>::from_bytes(&signature_proof_data.to_bytes(), common).unwrap();
+        assert_eq!(decoded.proof_with_pis, signature_proof_data.proof_with_pis);
+        assert_eq!(decoded.verifier_only, signature_proof_data.verifier_only);
+
+        let decoded =
+            SignatureProofData::<C>::from_hex(&signature_proof_data.to_hex(), common).unwrap();
+        assert_eq!(decoded.proof_with_pis, signature_proof_data.proof_with_pis);
+
+        let json = signature_proof_data.to_envelope().to_json();
+        let envelope = SignatureProofEnvelope::from_json(&json).unwrap();
+        assert_eq!(envelope, signature_proof_data.to_envelope());
+        let decoded = SignatureProofData::<C>::from_envelope(&envelope, common).unwrap();
+        assert_eq!(decoded.proof_with_pis, signature_proof_data.proof_with_pis);
+        assert_eq!(decoded.verifier_only, signature_proof_data.verifier_only);
+    }
+
+    #[test]
+    fn it_fails_signature_proof_data_invalid_encodings() {
+        let signature_proof_data = signature_proof_data();
+        let common = &signature_proof_data.common;
+        let bytes = signature_proof_data.to_bytes();
+
+        let mut other_version = bytes.clone();
+        other_version[0] = SIGNATURE_PROOF_DATA_VERSION + 1;
+        assert!(matches!(
+            SignatureProofData::<C>::from_bytes(&other_version, common),
+            Err(SerializationError::UnsupportedVersion(_, _))
+        ));
+        assert!(SignatureProofData::<C>::from_bytes(&bytes[..bytes.len() / 2], common).is_err());
+        assert!(SignatureProofData::<C>::from_bytes(&[], common).is_err());
+        assert!(matches!(
+            SignatureProofData::<C>::from_hex(&hex::encode(&bytes), common),
+            Err(SerializationError::MissingHexPrefix)
+        ));
+        assert!(matches!(
+            SignatureProofEnvelope::from_json("{\"version\": 1}"),
+            Err(SerializationError::InvalidJson(_))
+        ));
+    }
+}
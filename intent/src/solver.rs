@@ -1,18 +1,36 @@
-use plonky2::plonk::{circuit_builder::CircuitBuilder, config::GenericConfig};
+use anyhow::anyhow;
+use plonky2::{
+    iop::witness::PartialWitness,
+    plonk::{circuit_builder::CircuitBuilder, config::GenericConfig},
+};
+use types::types::{PublicKey, RecoverableSignature};
 
 use crate::{
-    intent::{Intent, SignatureProofData},
+    intent::{Intent, IntentSignatureVerifier, SignatureProofData},
+    structured_hash::StructuredHashInterface,
     D, F,
 };
 
-#[allow(dead_code)]
 pub struct IntentSignature<T, C>
 where
     T: Intent,
     C: GenericConfig<D, F = F>,
 {
-    intent: T,
-    signature_proof_data: SignatureProofData<C>,
+    pub intent: T,
+    pub signature_proof_data: SignatureProofData<C>,
+}
+
+impl<T, C> IntentSignature<T, C>
+where
+    T: Intent,
+    C: GenericConfig<D, F = F>,
+{
+    pub fn new(intent: T, signature_proof_data: SignatureProofData<C>) -> Self {
+        Self {
+            intent,
+            signature_proof_data,
+        }
+    }
 }
 
 pub trait Solver<T>
@@ -22,10 +40,43 @@ where
     type Output;
 
     fn execute_runtime(&self, intents: Vec<T>) -> Self::Output;
+    /// Constrains the execution of the intents in `circuit_builder`, setting its witness in
+    /// `partial_witness`. Each intent's signature proof is first verified by
+    /// `signature_verifier`, against the intent.
     fn generate_execute_proof<C: GenericConfig<D, F = F>>(
         &self,
         circuit_builder: &mut CircuitBuilder<F, D>,
+        partial_witness: &mut PartialWitness<F>,
+        signature_verifier: &IntentSignatureVerifier<C>,
         intents: Vec<IntentSignature<T, C>>,
     ) -> Result<(), anyhow::Error>;
-    fn verify_intents_signatures(&self, intents: Vec<T>) -> Result<(), anyhow::Error>;
+    /// Verifies the intents' signatures, of their EIP-712 signing hashes in the solver's domain,
+    /// returning the public key of each intent's signer.
+    /// Intents whose sender account does not match their signer are rejected, as well as
+    /// expired or replayed intents. The intents' nonces are used once the whole batch is valid.
+    fn verify_intents_signatures(
+        &mut self,
+        intents: Vec<(T, RecoverableSignature)>,
+    ) -> Result<Vec<PublicKey>, anyhow::Error>;
+}
+
+/// Verifies that the signature proof of every intent of a batch proves a signature of the
+/// intent by its sender, with `signature_verifier`
+pub(crate) fn verify_signature_proofs<T, C>(
+    signature_verifier: &IntentSignatureVerifier<C>,
+    intents: &[IntentSignature<T, C>],
+) -> Result<(), anyhow::Error>
+where
+    T: StructuredHashInterface + Intent,
+    C: GenericConfig<D, F = F>,
+{
+    for (index, intent_signature) in intents.iter().enumerate() {
+        signature_verifier
+            .verify(
+                &intent_signature.intent,
+                &intent_signature.signature_proof_data.proof_with_pis,
+            )
+            .map_err(|e| anyhow!("Invalid signature proof for intent {}: {}", index, e))?;
+    }
+    Ok(())
 }
@@ -1,14 +1,463 @@
+use std::collections::BTreeMap;
+
 use keccak_hash::keccak;
+use num_bigint::BigUint;
+pub use structured_hash_derive::StructuredHash;
+use types::types::Address;
 
 use crate::StructuredHash;
 
+/// Prefix of an EIP-712 signing hash, see https://eips.ethereum.org/EIPS/eip-712#specification
+pub const EIP712_PREFIX: [u8; 2] = [0x19, 0x01];
+
 pub trait StructuredHashInterface {
     fn type_encode() -> String;
     fn data_encode(&self) -> Vec<u8>;
+    /// Computes EIP-712's `hashStruct(s) = keccak256(typeHash ‖ encodeData(s))`
     fn structured_hash(&self) -> StructuredHash {
-        let type_encoding = keccak(&Self::type_encode()).to_fixed_bytes();
-        let data_encoding = keccak(&self.data_encode()).to_fixed_bytes();
-        let output = keccak(&[type_encoding, data_encoding].concat());
+        let type_hash = keccak(Self::type_encode()).to_fixed_bytes();
+        let output = keccak([type_hash.to_vec(), self.data_encode()].concat());
+        output.to_fixed_bytes()
+    }
+    /// Computes the digest signed by an Ethereum wallet (through `eth_signTypedData_v4`), that is,
+    /// `keccak256("\x19\x01" ‖ domainSeparator ‖ hashStruct(message))`
+    fn signing_hash(&self, domain: &Eip712Domain) -> StructuredHash {
+        let output = keccak(
+            [
+                EIP712_PREFIX.as_slice(),
+                domain.separator().as_slice(),
+                self.structured_hash().as_slice(),
+            ]
+            .concat(),
+        );
+        output.to_fixed_bytes()
+    }
+}
+
+/// Member of an EIP-712 struct type. Struct members are
+/// implemented by `#[derive(StructuredHash)]`.
+pub trait Eip712Member {
+    /// EIP-712 type of the member, e.g. `uint256` or the name of a struct type
+    fn member_type() -> String;
+    /// Name and type encoding of every struct type referenced by the member,
+    /// starting with the member's type itself, if it is a struct
+    fn referenced_types() -> Vec<(String, String)> {
+        vec![]
+    }
+    /// 32-byte `encodeData` value of the member
+    fn encode_member(&self) -> [u8; 32];
+    /// Whether the member can be encoded as `member_type`, as `#[eip712(type = "...")]` encodes
+    /// [`Eip712Uint`] members
+    fn accepts_member_type(member_type: &str) -> bool {
+        member_type == Self::member_type()
+    }
+    /// 32-byte `encodeData` value of the member encoded as `member_type`,
+    /// or `None` if the member is not accepted as, or does not fit into, that type
+    fn encode_member_as(&self, member_type: &str) -> Option<[u8; 32]> {
+        Self::accepts_member_type(member_type).then(|| self.encode_member())
+    }
+}
+
+/// Integer member, which can also be encoded as any `uint<N>` type through
+/// `#[eip712(type = "uint<N>")]`, as long as its value fits into `N` bits
+pub trait Eip712Uint: Eip712Member {}
+
+impl Eip712Uint for BigUint {}
+
+/// Assembles `encodeType` from the output of [`Eip712Member::referenced_types`], that is,
+/// the primary type encoding followed by the referenced struct types, sorted by name.
+pub fn encode_type(referenced_types: Vec<(String, String)>) -> String {
+    let mut referenced_types = referenced_types.into_iter();
+    let (primary_type, primary_type_encoding) = referenced_types
+        .next()
+        .expect("referenced types should start with the primary type");
+    let referenced_types = referenced_types
+        .filter(|(name, _)| *name != primary_type)
+        .collect::<BTreeMap<_, _>>();
+    format!(
+        "{}{}",
+        primary_type_encoding,
+        referenced_types.into_values().collect::<String>()
+    )
+}
+
+impl Eip712Member for [u8; 20] {
+    fn member_type() -> String {
+        "address".to_string()
+    }
+    fn encode_member(&self) -> [u8; 32] {
+        encode_address(self)
+    }
+}
+
+impl Eip712Member for Address {
+    fn member_type() -> String {
+        "address".to_string()
+    }
+    fn encode_member(&self) -> [u8; 32] {
+        self.to_eip712_word()
+    }
+}
+
+impl Eip712Member for [u8; 32] {
+    fn member_type() -> String {
+        "bytes32".to_string()
+    }
+    fn encode_member(&self) -> [u8; 32] {
+        *self
+    }
+}
+
+impl Eip712Member for BigUint {
+    fn member_type() -> String {
+        "uint256".to_string()
+    }
+    fn encode_member(&self) -> [u8; 32] {
+        encode_uint256(self)
+    }
+    fn accepts_member_type(member_type: &str) -> bool {
+        uint_bits(member_type).is_some()
+    }
+    fn encode_member_as(&self, member_type: &str) -> Option<[u8; 32]> {
+        encode_uint(self, uint_bits(member_type)?)
+    }
+}
+
+impl Eip712Member for String {
+    fn member_type() -> String {
+        "string".to_string()
+    }
+    fn encode_member(&self) -> [u8; 32] {
+        encode_string(self)
+    }
+}
+
+impl Eip712Member for bool {
+    fn member_type() -> String {
+        "bool".to_string()
+    }
+    fn encode_member(&self) -> [u8; 32] {
+        encode_uint8(*self as u8)
+    }
+}
+
+macro_rules! impl_eip712_member_for_uint {
+    ($($uint:ty => $member_type:literal),*) => {
+        $(
+            impl Eip712Uint for $uint {}
+
+            impl Eip712Member for $uint {
+                fn member_type() -> String {
+                    $member_type.to_string()
+                }
+                fn encode_member(&self) -> [u8; 32] {
+                    encode_uint256(&BigUint::from(*self))
+                }
+                fn accepts_member_type(member_type: &str) -> bool {
+                    uint_bits(member_type).is_some()
+                }
+                fn encode_member_as(&self, member_type: &str) -> Option<[u8; 32]> {
+                    encode_uint(&BigUint::from(*self), uint_bits(member_type)?)
+                }
+            }
+        )*
+    };
+}
+
+impl_eip712_member_for_uint!(u8 => "uint8", u16 => "uint16", u32 => "uint32", u64 => "uint64", u128 => "uint128");
+
+/// EIP-712 domain, every field is optional and only the ones
+/// which are set take part in the domain type and separator.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Eip712Domain {
+    /// user readable name of the signing domain
+    pub name: Option<String>,
+    /// current major version of the signing domain
+    pub version: Option<String>,
+    /// EIP-155 chain id
+    pub chain_id: Option<BigUint>,
+    /// address of the contract that will verify the signature
+    pub verifying_contract: Option<Address>,
+    /// disambiguating salt for the protocol
+    pub salt: Option<[u8; 32]>,
+}
+
+impl Eip712Domain {
+    pub fn new(
+        name: Option<String>,
+        version: Option<String>,
+        chain_id: Option<BigUint>,
+        verifying_contract: Option<Address>,
+        salt: Option<[u8; 32]>,
+    ) -> Self {
+        Self {
+            name,
+            version,
+            chain_id,
+            verifying_contract,
+            salt,
+        }
+    }
+
+    /// Type encoding of the domain, for instance
+    /// `EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)`
+    pub fn type_encode(&self) -> String {
+        let fields = [
+            self.name.as_ref().map(|_| "string name"),
+            self.version.as_ref().map(|_| "string version"),
+            self.chain_id.as_ref().map(|_| "uint256 chainId"),
+            self.verifying_contract
+                .as_ref()
+                .map(|_| "address verifyingContract"),
+            self.salt.as_ref().map(|_| "bytes32 salt"),
+        ];
+        let fields = fields.into_iter().flatten().collect::<Vec<_>>();
+        format!("EIP712Domain({})", fields.join(","))
+    }
+
+    pub fn data_encode(&self) -> Vec<u8> {
+        let encoded_fields = [
+            self.name.as_ref().map(|name| encode_string(name)),
+            self.version.as_ref().map(|version| encode_string(version)),
+            self.chain_id.as_ref().map(encode_uint256),
+            self.verifying_contract
+                .as_ref()
+                .map(Address::to_eip712_word),
+            self.salt,
+        ];
+        encoded_fields
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .concat()
+    }
+
+    /// Computes the domain separator `hashStruct(eip712Domain)`
+    pub fn separator(&self) -> StructuredHash {
+        let type_hash = keccak(self.type_encode()).to_fixed_bytes();
+        let output = keccak([type_hash.to_vec(), self.data_encode()].concat());
         output.to_fixed_bytes()
     }
 }
+
+/// Encodes a dynamic `string` value, as the keccak256 hash of its contents.
+pub fn encode_string(value: &str) -> [u8; 32] {
+    keccak(value.as_bytes()).to_fixed_bytes()
+}
+
+/// Encodes a `uint256` value, as a 32-byte big endian word.
+///
+/// # Panics
+///
+/// Panics if `value` does not fit into 256 bits, use [`encode_uint`] to check it instead.
+/// Swap intents check their amounts when built, and when they are received, with
+/// [`Order::check_amounts`], so that hashing them does not panic.
+///
+/// [`Order::check_amounts`]: crate::order::Order::check_amounts
+pub fn encode_uint256(value: &BigUint) -> [u8; 32] {
+    encode_uint(value, 256).expect("uint256 value should fit into 256 bits")
+}
+
+/// Encodes an `uint<bits>` value, as a 32-byte big endian word,
+/// or returns `None` if `value` does not fit into `bits` bits.
+pub fn encode_uint(value: &BigUint, bits: u64) -> Option<[u8; 32]> {
+    if bits > 256 || value.bits() > bits {
+        return None;
+    }
+    let bytes = value.to_bytes_be();
+    let mut output = [0u8; 32];
+    output[32 - bytes.len()..].copy_from_slice(&bytes);
+    Some(output)
+}
+
+/// Width of an `uint<bits>` type, with `bits` a multiple of 8 between 8 and 256
+fn uint_bits(member_type: &str) -> Option<u64> {
+    let bits = member_type.strip_prefix("uint")?.parse::<u64>().ok()?;
+    (bits % 8 == 0 && (8..=256).contains(&bits)).then_some(bits)
+}
+
+/// Encodes an `uint8` value, as a 32-byte big endian word.
+pub fn encode_uint8(value: u8) -> [u8; 32] {
+    let mut output = [0u8; 32];
+    output[31] = value;
+    output
+}
+
+/// Encodes an `address` value, as a 32-byte word, left padded with zeros.
+pub fn encode_address(value: &[u8; 20]) -> [u8; 32] {
+    let mut output = [0u8; 32];
+    output[12..].copy_from_slice(value);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use hex_literal::hex;
+
+    use super::*;
+
+    #[derive(StructuredHash)]
+    #[eip712(crate = "crate")]
+    struct Person {
+        name: String,
+        wallet: [u8; 20],
+    }
+
+    #[derive(StructuredHash)]
+    #[eip712(crate = "crate")]
+    struct Mail {
+        from: Person,
+        to: Person,
+        contents: String,
+    }
+
+    #[derive(StructuredHash)]
+    #[eip712(name = "Group", crate = "crate")]
+    struct MailingList {
+        #[eip712(rename = "owner")]
+        admin: Person,
+        #[eip712(type = "uint32")]
+        size: BigUint,
+        last_mail: Mail,
+    }
+
+    fn mail_domain() -> Eip712Domain {
+        Eip712Domain::new(
+            Some("Ether Mail".to_string()),
+            Some("1".to_string()),
+            Some(BigUint::from(1_u8)),
+            Some(Address::from_str("0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC").unwrap()),
+            None,
+        )
+    }
+
+    fn mail() -> Mail {
+        Mail {
+            from: Person {
+                name: "Cow".to_string(),
+                wallet: hex!("CD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826"),
+            },
+            to: Person {
+                name: "Bob".to_string(),
+                wallet: hex!("bBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB"),
+            },
+            contents: "Hello, Bob!".to_string(),
+        }
+    }
+
+    #[test]
+    fn it_works_eip712_domain_type_encoding() {
+        assert_eq!(
+            mail_domain().type_encode(),
+            "EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)"
+        );
+        assert_eq!(Eip712Domain::default().type_encode(), "EIP712Domain()");
+    }
+
+    #[test]
+    fn it_works_derive_eip712_type_encoding() {
+        assert_eq!(
+            Mail::type_encode(),
+            "Mail(Person from,Person to,string contents)Person(string name,address wallet)"
+        );
+        // referenced types are sorted by name, and appear only once
+        assert_eq!(
+            MailingList::type_encode(),
+            "Group(Person owner,uint32 size,Mail last_mail)\
+             Mail(Person from,Person to,string contents)\
+             Person(string name,address wallet)"
+        );
+    }
+
+    #[test]
+    fn it_works_eip712_member_type_override() {
+        let mailing_list = |size: u64| MailingList {
+            admin: Person {
+                name: "Cow".to_string(),
+                wallet: [1; 20],
+            },
+            size: BigUint::from(size),
+            last_mail: mail(),
+        };
+        let data = mailing_list(u32::MAX as u64).data_encode();
+        assert_eq!(data[32..64], encode_uint256(&BigUint::from(u32::MAX)));
+        assert!(u64::accepts_member_type("uint8"));
+        assert!(!BigUint::accepts_member_type("uint7"));
+        assert!(!<[u8; 32]>::accepts_member_type("string"));
+        assert_eq!(300_u64.encode_member_as("uint8"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not fit into uint32")]
+    fn it_fails_eip712_member_type_override_overflow() {
+        MailingList {
+            admin: Person {
+                name: "Cow".to_string(),
+                wallet: [1; 20],
+            },
+            size: BigUint::from(u32::MAX) + 1_u8,
+            last_mail: mail(),
+        }
+        .structured_hash();
+    }
+
+    #[test]
+    fn it_works_eip712_mail_type_hash() {
+        assert_eq!(
+            keccak(Mail::type_encode()).to_fixed_bytes(),
+            hex!("a0cedeb2dc280ba39b857546d74f5549c3a1d7bdc2dd96bf881f76108e23dac2")
+        );
+    }
+
+    #[test]
+    fn it_works_eip712_mail_domain_separator() {
+        assert_eq!(
+            mail_domain().separator(),
+            hex!("f2cee375fa42b42143804025fc449deafd50cc031ca257e0b194a650a912090f")
+        );
+    }
+
+    #[test]
+    fn it_works_eip712_mail_struct_hash() {
+        assert_eq!(
+            mail().structured_hash(),
+            hex!("c52c0ee5d84264471806290a3f2c4cecfc5490626bf912d01f240d7a274b371e")
+        );
+    }
+
+    #[test]
+    fn it_works_eip712_mail_signing_hash() {
+        assert_eq!(
+            mail().signing_hash(&mail_domain()),
+            hex!("be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2")
+        );
+    }
+
+    #[test]
+    fn it_works_encode_uint256() {
+        let mut expected = [0u8; 32];
+        expected[30..].copy_from_slice(&[1, 0]);
+        assert_eq!(encode_uint256(&BigUint::from(256_u16)), expected);
+        assert_eq!(encode_uint256(&BigUint::from(0_u8)), [0u8; 32]);
+        let max = (BigUint::from(1_u8) << 256_u32) - 1_u8;
+        assert_eq!(encode_uint256(&max), [u8::MAX; 32]);
+    }
+
+    #[test]
+    fn it_works_encode_uint() {
+        assert_eq!(
+            encode_uint(&BigUint::from(u32::MAX), 32),
+            Some(encode_uint256(&BigUint::from(u32::MAX)))
+        );
+        assert_eq!(encode_uint(&(BigUint::from(u32::MAX) + 1_u8), 32), None);
+        assert_eq!(encode_uint(&(BigUint::from(1_u8) << 256_u32), 256), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "uint256 value should fit into 256 bits")]
+    fn it_fails_encode_uint256_overflow() {
+        encode_uint256(&(BigUint::from(1_u8) << 256_u32));
+    }
+}
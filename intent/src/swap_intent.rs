@@ -1,77 +1,145 @@
 use crate::{
+    circuit::{AddressTarget, CircuitBuilderEip712, ECDSAIntentCircuit, IntentCircuitInterface},
+    error::IntentError,
     intent::{ExecuteRuntime, Intent},
-    structured_hash::StructuredHashInterface,
+    keccak::WitnessKeccak,
+    order::{CircuitBuilderOrder, Order, OrderTarget},
+    pool::PooledIntent,
+    structured_hash::{
+        encode_uint, encode_uint8, Eip712Domain, Eip712Member, StructuredHash,
+        StructuredHashInterface,
+    },
+    D, F,
 };
-use keccak_hash::keccak;
 use num_bigint::BigUint;
-use types::types::{Message, PrivateKey, Signature};
+use plonky2::{
+    iop::{
+        target::BoolTarget,
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::circuit_builder::CircuitBuilder,
+};
+use plonky2_ecdsa::gadgets::biguint::{BigUintTarget, CircuitBuilderBiguint, WitnessBigUint};
+use types::types::{Address, Message, PrivateKey, RecoverableSignature};
+
+/// Number of 32-bit limbs of an `uint256` target
+pub(crate) const UINT256_LIMBS: usize = 8;
+/// Number of 32-bit limbs of an `uint64` target
+pub(crate) const UINT64_LIMBS: usize = 2;
 
-#[derive(Clone, Copy, Debug)]
-#[allow(dead_code)]
-pub(crate) enum SwapDirection {
-    Buy,
-    Sell,
+/// Trade direction, EIP-712 encoded as an `uint8`, which decides the token an intent pays.
+///
+/// - `Buy` is exact-in on the quote token: the intent sells `quote_amount` quote tokens for
+///   at least `min_base_token_amount` base tokens
+/// - `Sell` is exact-in on the base token: the intent sells `min_base_token_amount` base tokens
+///   for at least `quote_amount` quote tokens
+///
+/// Both directions are exact-in, [`SwapIntent::order`] normalizes them into an [`Order`] on
+/// the token the intent pays. An intent which is not partially fillable accepts exactly the
+/// fills of the exact-out order buying at least its bought amount for at most its sold
+/// amount, so that such an exact-out order is expressed by the exact-in intent with the same
+/// amounts.
+///
+/// todo: partially fillable exact-out intents, sized in the tokens they buy, are deferred.
+/// A partially fillable intent is sized in the tokens it sells, and may be filled by selling
+/// less than its size at its limit price, so that expressing them takes an order sized in its
+/// buy token, with its own fill limits in [`Order`] and its circuit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SwapDirection {
+    Buy = 0,
+    Sell = 1,
+}
+
+impl Eip712Member for SwapDirection {
+    fn member_type() -> String {
+        "uint8".to_string()
+    }
+    fn encode_member(&self) -> [u8; 32] {
+        encode_uint8(*self as u8)
+    }
 }
 
 /// Inputs for a swap
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, StructuredHash)]
+#[eip712(crate = "crate")]
 pub struct SwapInputs {
     /// address
-    from: BigUint,
+    pub(crate) from: Address,
     /// quote token
-    quote_token: BigUint,
+    pub(crate) quote_token: Address,
     /// base token
-    base_token: BigUint,
-    /// quote amount
-    quote_amount: BigUint,
+    pub(crate) base_token: Address,
+    /// quote tokens sold by a `Buy` intent, which are its size, or least quote tokens bought
+    /// by a `Sell` intent
+    pub(crate) quote_amount: BigUint,
     /// trade direction
-    direction: SwapDirection,
-}
-
-impl StructuredHashInterface for SwapInputs {
-    fn type_encode() -> String {
-        "SwapInputs(BigUint from,BigUint quote_token,BigUint base_token,BigUint quote_amount)"
-            .to_string()
-    }
-    fn data_encode(&self) -> Vec<u8> {
-        let from_hash = keccak(&self.from.to_bytes_be()).to_fixed_bytes();
-        let quote_token_hash = keccak(&self.quote_token.to_bytes_be()).to_fixed_bytes();
-        let base_token_hash = keccak(&self.base_token.to_bytes_be()).to_fixed_bytes();
-        let quote_amount_hash = keccak(&self.quote_amount.to_bytes_be()).to_fixed_bytes();
-        let direction = keccak(&[self.direction as u8]).to_fixed_bytes();
-
-        [
-            from_hash,
-            quote_token_hash,
-            base_token_hash,
-            quote_amount_hash,
+    pub(crate) direction: SwapDirection,
+    /// sequence number of the intent among its sender's intents
+    pub(crate) nonce: u64,
+}
+
+impl SwapInputs {
+    /// Swap inputs, or an error if `quote_amount` does not fit into an `uint256`
+    pub fn new(
+        from: Address,
+        quote_token: Address,
+        base_token: Address,
+        quote_amount: BigUint,
+        direction: SwapDirection,
+        nonce: u64,
+    ) -> Result<Self, IntentError> {
+        Ok(Self {
+            from,
+            quote_token,
+            base_token,
+            quote_amount: uint256_amount("quote_amount", quote_amount)?,
             direction,
-        ]
-        .concat()
+            nonce,
+        })
     }
 }
 
 /// Constraints for a swap
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, StructuredHash)]
+#[eip712(crate = "crate")]
 pub struct SwapConstraints {
-    /// max slippage amount
-    min_base_token_amount: BigUint,
+    /// least base tokens bought by a `Buy` intent, or base tokens sold by a `Sell` intent,
+    /// which are its size. The member keeps its name for either direction, so that the
+    /// EIP-712 type of swap intents does not change.
+    pub(crate) min_base_token_amount: BigUint,
+    /// unix timestamp, in seconds, after which the intent expires
+    pub(crate) deadline: u64,
+    /// whether any fraction of the intent can be filled, at its limit price or better
+    pub(crate) partially_fillable: bool,
 }
 
-impl StructuredHashInterface for SwapConstraints {
-    fn type_encode() -> String {
-        "SwapConstraints(BigUint min_base_token_amount)".to_string()
+impl SwapConstraints {
+    /// Swap constraints, or an error if `min_base_token_amount` does not fit into an `uint256`
+    pub fn new(
+        min_base_token_amount: BigUint,
+        deadline: u64,
+        partially_fillable: bool,
+    ) -> Result<Self, IntentError> {
+        Ok(Self {
+            min_base_token_amount: uint256_amount("min_base_token_amount", min_base_token_amount)?,
+            deadline,
+            partially_fillable,
+        })
     }
-    fn data_encode(&self) -> Vec<u8> {
-        keccak(&self.min_base_token_amount.to_bytes_be())
-            .as_fixed_bytes()
-            .to_vec()
+}
+
+/// `amount` if it fits into the `uint256` of its EIP-712 encoding
+fn uint256_amount(field: &'static str, amount: BigUint) -> Result<BigUint, IntentError> {
+    match encode_uint(&amount, 256) {
+        Some(_) => Ok(amount),
+        None => Err(IntentError::AmountOverflow { field, amount }),
     }
 }
 
 /// Intent to swap tokens
 /// todo[ben]: this is incomplete, but let's focus on the pathways first
-#[allow(dead_code)]
+#[derive(Clone, Debug, StructuredHash)]
+#[eip712(crate = "crate")]
 pub struct SwapIntent {
     pub inputs: SwapInputs,
     pub constraints: SwapConstraints,
@@ -100,43 +168,295 @@ impl Intent for SwapIntent {
         self.inputs.clone()
     }
 
+    fn get_from(&self) -> Address {
+        self.inputs.from
+    }
+
     fn get_runtime_execution(&self) -> ExecuteRuntime {
         ExecuteRuntime::Swap
     }
 
-    fn sign_intent(&self, private_key: PrivateKey) -> Signature {
-        let message = <Self as StructuredHashInterface>::structured_hash(&self);
-        let message = Message::new_message(message);
-        private_key.sign_message(&message)
+    fn sign_intent(&self, private_key: PrivateKey, domain: &Eip712Domain) -> RecoverableSignature {
+        let message = Message::new_message(self.signing_hash(domain));
+        private_key.sign_message_recoverable(&message)
     }
 }
 
-impl StructuredHashInterface for SwapIntent {
-    fn type_encode() -> String {
-        let input_type_encoding = SwapInputs::type_encode();
-        let constraints_type_encoding = SwapConstraints::type_encode();
-        format!(
-            "SwapIntent(SwapInputs inputs,SwapConstraints constraints){}{}",
-            constraints_type_encoding, input_type_encoding
-        )
+impl PooledIntent for SwapIntent {
+    fn nonce(&self) -> u64 {
+        self.inputs.nonce
     }
 
-    fn data_encode(&self) -> Vec<u8> {
-        let input_data_encoding = self.inputs.structured_hash();
-        let constraints_data_encoding = self.constraints.structured_hash();
-        [input_data_encoding, constraints_data_encoding].concat()
+    fn deadline(&self) -> u64 {
+        self.constraints.deadline
+    }
+
+    fn pooled_order(&self) -> Order {
+        self.order()
+    }
+}
+
+impl SwapIntent {
+    /// Normalized order of the intent, selling the token it pays
+    pub fn order(&self) -> Order {
+        let (sell_token, buy_token, sell_amount, min_buy_amount) = match self.inputs.direction {
+            SwapDirection::Buy => (
+                self.inputs.quote_token,
+                self.inputs.base_token,
+                &self.inputs.quote_amount,
+                &self.constraints.min_base_token_amount,
+            ),
+            SwapDirection::Sell => (
+                self.inputs.base_token,
+                self.inputs.quote_token,
+                &self.constraints.min_base_token_amount,
+                &self.inputs.quote_amount,
+            ),
+        };
+        Order {
+            sell_token,
+            buy_token,
+            sell_amount: sell_amount.clone(),
+            min_buy_amount: min_buy_amount.clone(),
+            partially_fillable: self.constraints.partially_fillable,
+        }
+    }
+
+    /// Amounts sold and bought by the intent, when it exchanges `quote_amount` quote tokens
+    /// for `base_amount` base tokens
+    pub fn sold_and_bought<'a>(
+        &self,
+        quote_amount: &'a BigUint,
+        base_amount: &'a BigUint,
+    ) -> (&'a BigUint, &'a BigUint) {
+        match self.inputs.direction {
+            SwapDirection::Buy => (quote_amount, base_amount),
+            SwapDirection::Sell => (base_amount, quote_amount),
+        }
+    }
+
+    /// Size of the intent, its order's `sell_amount`
+    pub fn size(&self) -> &BigUint {
+        match self.inputs.direction {
+            SwapDirection::Buy => &self.inputs.quote_amount,
+            SwapDirection::Sell => &self.constraints.min_base_token_amount,
+        }
+    }
+
+    /// Whether the intent's order accepts a fill exchanging `quote_amount` quote tokens
+    /// and `base_amount` base tokens
+    pub fn accepts_fill(&self, quote_amount: &BigUint, base_amount: &BigUint) -> bool {
+        let (sold_amount, bought_amount) = self.sold_and_bought(quote_amount, base_amount);
+        self.order().accepts_fill(sold_amount, bought_amount)
+    }
+}
+
+/// In-circuit [`SwapInputs`]
+pub struct SwapInputsTarget {
+    pub from: AddressTarget,
+    pub quote_token: AddressTarget,
+    pub base_token: AddressTarget,
+    pub quote_amount: BigUintTarget,
+    /// set for a `Sell` intent
+    pub direction: BoolTarget,
+    pub nonce: BigUintTarget,
+}
+
+/// In-circuit [`SwapConstraints`]
+pub struct SwapConstraintsTarget {
+    pub min_base_token_amount: BigUintTarget,
+    pub deadline: BigUintTarget,
+    pub partially_fillable: BoolTarget,
+}
+
+/// In-circuit [`SwapIntent`], whose fields are the ones covered by the intent's signature
+pub struct SwapIntentTarget {
+    pub inputs: SwapInputsTarget,
+    pub constraints: SwapConstraintsTarget,
+}
+
+impl SwapIntentTarget {
+    /// In-circuit [`SwapIntent::order`], selecting the token the intent pays by its direction
+    pub fn order(&self, circuit_builder: &mut CircuitBuilder<F, D>) -> OrderTarget {
+        let is_sell = self.inputs.direction;
+        let (quote_token, base_token) = (&self.inputs.quote_token, &self.inputs.base_token);
+        let (quote_amount, min_base_token_amount) = (
+            &self.inputs.quote_amount,
+            &self.constraints.min_base_token_amount,
+        );
+        OrderTarget {
+            sell_token: circuit_builder.select_address(is_sell, base_token, quote_token),
+            buy_token: circuit_builder.select_address(is_sell, quote_token, base_token),
+            sell_amount: circuit_builder.select_biguint(
+                is_sell,
+                min_base_token_amount,
+                quote_amount,
+            ),
+            min_buy_amount: circuit_builder.select_biguint(
+                is_sell,
+                quote_amount,
+                min_base_token_amount,
+            ),
+            partially_fillable: self.constraints.partially_fillable,
+        }
+    }
+}
+
+impl IntentCircuitInterface for SwapIntent {
+    type IntentTarget = SwapIntentTarget;
+
+    fn add_virtual_intent_target(circuit_builder: &mut CircuitBuilder<F, D>) -> Self::IntentTarget {
+        let inputs = SwapInputsTarget {
+            from: circuit_builder.add_virtual_address_target(),
+            quote_token: circuit_builder.add_virtual_address_target(),
+            base_token: circuit_builder.add_virtual_address_target(),
+            quote_amount: circuit_builder.add_virtual_biguint_target(UINT256_LIMBS),
+            direction: circuit_builder.add_virtual_bool_target_safe(),
+            nonce: circuit_builder.add_virtual_biguint_target(UINT64_LIMBS),
+        };
+        let constraints = SwapConstraintsTarget {
+            min_base_token_amount: circuit_builder.add_virtual_biguint_target(UINT256_LIMBS),
+            deadline: circuit_builder.add_virtual_biguint_target(UINT64_LIMBS),
+            partially_fillable: circuit_builder.add_virtual_bool_target_safe(),
+        };
+        SwapIntentTarget {
+            inputs,
+            constraints,
+        }
+    }
+
+    fn structured_hash_target(
+        circuit_builder: &mut CircuitBuilder<F, D>,
+        intent_target: &Self::IntentTarget,
+    ) -> Vec<BoolTarget> {
+        let SwapIntentTarget {
+            inputs,
+            constraints,
+        } = intent_target;
+
+        let encoded_inputs = vec![
+            circuit_builder.encode_address_target(&inputs.from),
+            circuit_builder.encode_address_target(&inputs.quote_token),
+            circuit_builder.encode_address_target(&inputs.base_token),
+            circuit_builder.encode_uint256_target(&inputs.quote_amount),
+            circuit_builder.encode_bool_target(inputs.direction),
+            circuit_builder.encode_uint256_target(&inputs.nonce),
+        ];
+        let inputs_hash =
+            circuit_builder.hash_struct_target(&SwapInputs::type_encode(), &encoded_inputs);
+
+        let encoded_constraints = vec![
+            circuit_builder.encode_uint256_target(&constraints.min_base_token_amount),
+            circuit_builder.encode_uint256_target(&constraints.deadline),
+            circuit_builder.encode_bool_target(constraints.partially_fillable),
+        ];
+        let constraints_hash = circuit_builder
+            .hash_struct_target(&SwapConstraints::type_encode(), &encoded_constraints);
+
+        circuit_builder
+            .hash_struct_target(&SwapIntent::type_encode(), &[inputs_hash, constraints_hash])
+    }
+
+    fn from_target(intent_target: &Self::IntentTarget) -> &AddressTarget {
+        &intent_target.inputs.from
+    }
+
+    fn nonce_target(intent_target: &Self::IntentTarget) -> &BigUintTarget {
+        &intent_target.inputs.nonce
+    }
+
+    fn set_intent_target(
+        &self,
+        partial_witness: &mut PartialWitness<F>,
+        intent_target: &Self::IntentTarget,
+    ) {
+        let SwapIntentTarget {
+            inputs,
+            constraints,
+        } = intent_target;
+
+        partial_witness.set_bytes_target(&inputs.from.0, self.inputs.from.as_bytes());
+        partial_witness.set_bytes_target(&inputs.quote_token.0, self.inputs.quote_token.as_bytes());
+        partial_witness.set_bytes_target(&inputs.base_token.0, self.inputs.base_token.as_bytes());
+        partial_witness.set_biguint_target(&inputs.quote_amount, &self.inputs.quote_amount);
+        partial_witness.set_bool_target(
+            inputs.direction,
+            self.inputs.direction == SwapDirection::Sell,
+        );
+        partial_witness.set_biguint_target(&inputs.nonce, &BigUint::from(self.inputs.nonce));
+        partial_witness.set_biguint_target(
+            &constraints.min_base_token_amount,
+            &self.constraints.min_base_token_amount,
+        );
+        partial_witness.set_biguint_target(
+            &constraints.deadline,
+            &BigUint::from(self.constraints.deadline),
+        );
+        partial_witness.set_bool_target(
+            constraints.partially_fillable,
+            self.constraints.partially_fillable,
+        );
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use plonky2::plonk::{circuit_data::CircuitConfig, config::PoseidonGoldilocksConfig};
+
+    use crate::{
+        keccak::CircuitBuilderKeccak,
+        test_utils::{domain, swap_intent},
+    };
+
     use super::*;
 
+    #[test]
+    fn it_works_swap_intent_exact_out_semantics() {
+        let amount = <BigUint as From<u64>>::from;
+        // sells at most 100 quote tokens, and buys at least 10 base tokens
+        let whole = swap_intent(SwapDirection::Buy, 100, 10, false);
+        // the fills of the exact-out order buying at least 10 base tokens for at most 100
+        assert!(whole.accepts_fill(&amount(100), &amount(10)));
+        assert!(whole.accepts_fill(&amount(90), &amount(12)));
+        assert!(!whole.accepts_fill(&amount(101), &amount(10)));
+        assert!(!whole.accepts_fill(&amount(100), &amount(9)));
+
+        // sized in sold quote tokens: it may buy more than 10 base tokens, while a partially
+        // fillable exact-out order of 10 base tokens would not
+        let partial = swap_intent(SwapDirection::Buy, 100, 10, true);
+        assert!(partial.accepts_fill(&amount(50), &amount(5)));
+        assert!(partial.accepts_fill(&amount(100), &amount(12)));
+        assert!(!partial.accepts_fill(&amount(50), &amount(4)));
+        assert_eq!(partial.size(), &amount(100));
+    }
+
+    #[test]
+    fn it_fails_swap_intent_amount_overflow() {
+        let overflowing = BigUint::from(1_u8) << 256_u32;
+        let inputs = SwapInputs::new(
+            Address::new([255; 20]),
+            Address::new([125; 20]),
+            Address::new([64; 20]),
+            overflowing.clone(),
+            SwapDirection::Buy,
+            0,
+        );
+        assert!(matches!(
+            inputs,
+            Err(IntentError::AmountOverflow {
+                field: "quote_amount",
+                ..
+            })
+        ));
+        let constraints = SwapConstraints::new(overflowing - 1_u8, 0, false);
+        assert_eq!(constraints.unwrap().min_base_token_amount.bits(), 256);
+    }
+
     #[test]
     fn it_works_swap_inputs_type_encoding() {
         assert_eq!(
             SwapInputs::type_encode().as_str(),
-            "SwapInputs(BigUint from,BigUint quote_token,BigUint base_token,BigUint quote_amount)"
+            "SwapInputs(address from,address quote_token,address base_token,uint256 quote_amount,uint8 direction,uint64 nonce)"
         );
     }
 
@@ -144,7 +464,7 @@ mod tests {
     fn it_works_swap_constraints_type_encoding() {
         assert_eq!(
             SwapConstraints::type_encode().as_str(),
-            "SwapConstraints(BigUint min_base_token_amount)"
+            "SwapConstraints(uint256 min_base_token_amount,uint64 deadline,bool partially_fillable)"
         );
     }
 
@@ -160,22 +480,61 @@ mod tests {
         );
     }
 
+    /// Number of members of the primary type of an EIP-712 type encoding
+    fn num_type_members(type_encoding: &str) -> usize {
+        let start = type_encoding.find('(').unwrap();
+        let end = type_encoding.find(')').unwrap();
+        type_encoding[start + 1..end].split(',').count()
+    }
+
+    #[test]
+    fn it_works_swap_type_and_data_encodings_cover_same_fields() {
+        let inputs = SwapInputs::new(
+            Address::new([255; 20]),
+            Address::new([125; 20]),
+            Address::new([64; 20]),
+            BigUint::from(1_000_000_000_000_u64),
+            SwapDirection::Sell,
+            7,
+        )
+        .unwrap();
+        let constraints = SwapConstraints::new(BigUint::from(64_u8), 1_700_000_000, true).unwrap();
+        assert_eq!(
+            inputs.data_encode().len(),
+            32 * num_type_members(&SwapInputs::type_encode())
+        );
+        assert_eq!(
+            constraints.data_encode().len(),
+            32 * num_type_members(&SwapConstraints::type_encode())
+        );
+
+        let intent = SwapIntent {
+            inputs,
+            constraints,
+        };
+        assert_eq!(
+            intent.data_encode().len(),
+            32 * num_type_members(&SwapIntent::type_encode())
+        );
+    }
+
     #[test]
     fn it_works_swap_inputs_struct_hash() {
         let inputs = SwapInputs {
-            from: BigUint::from(255_u8),
+            from: Address::new([255; 20]),
             quote_amount: BigUint::from(1_000_000_000_000_u64),
-            quote_token: BigUint::from(125_u8),
-            base_token: BigUint::from(64_u8),
+            quote_token: Address::new([125; 20]),
+            base_token: Address::new([64; 20]),
             direction: SwapDirection::Buy,
+            nonce: 7,
         };
 
         let hash = inputs.structured_hash();
         assert_eq!(
             hash,
             [
-                186, 101, 125, 97, 49, 232, 81, 6, 161, 29, 40, 233, 194, 228, 236, 187, 13, 240,
-                22, 165, 28, 19, 253, 103, 191, 74, 123, 112, 246, 125, 183, 139
+                112, 199, 5, 195, 230, 142, 188, 79, 245, 39, 152, 80, 54, 80, 38, 227, 128, 191,
+                17, 11, 73, 222, 219, 73, 84, 121, 232, 117, 72, 239, 177, 166
             ]
         );
     }
@@ -184,14 +543,16 @@ mod tests {
     fn it_works_swap_constraints_struct_hash() {
         let constraints = SwapConstraints {
             min_base_token_amount: BigUint::from(64_u8),
+            deadline: 1_700_000_000,
+            partially_fillable: true,
         };
 
         let hash = constraints.structured_hash();
         assert_eq!(
             hash,
             [
-                234, 72, 17, 64, 171, 119, 130, 160, 214, 239, 34, 138, 193, 55, 126, 70, 99, 51,
-                17, 186, 102, 158, 246, 83, 37, 222, 43, 39, 99, 185, 141, 28
+                229, 175, 229, 95, 133, 255, 250, 250, 131, 125, 123, 117, 185, 146, 100, 84, 202,
+                93, 198, 24, 203, 62, 200, 27, 213, 79, 241, 244, 92, 45, 122, 8
             ]
         );
     }
@@ -200,14 +561,17 @@ mod tests {
     fn it_works_swap_intent_struct_hash() {
         let intent = SwapIntent {
             inputs: SwapInputs {
-                from: BigUint::from(255_u8),
+                from: Address::new([255; 20]),
                 quote_amount: BigUint::from(1_000_000_000_000_u64),
-                quote_token: BigUint::from(125_u8),
-                base_token: BigUint::from(64_u8),
+                quote_token: Address::new([125; 20]),
+                base_token: Address::new([64; 20]),
                 direction: SwapDirection::Buy,
+                nonce: 7,
             },
             constraints: SwapConstraints {
                 min_base_token_amount: BigUint::from(64_u8),
+                deadline: 1_700_000_000,
+                partially_fillable: true,
             },
         };
 
@@ -215,9 +579,48 @@ mod tests {
         assert_eq!(
             hash,
             [
-                189, 82, 148, 152, 172, 104, 0, 13, 64, 1, 22, 117, 178, 200, 200, 168, 243, 142,
-                13, 152, 208, 63, 125, 179, 210, 98, 124, 23, 39, 58, 92, 19
+                245, 160, 21, 191, 228, 232, 59, 78, 33, 55, 58, 206, 85, 81, 166, 106, 177, 243,
+                39, 227, 197, 175, 55, 50, 125, 178, 31, 81, 75, 157, 166, 199
             ]
         );
     }
+
+    #[test]
+    fn it_works_swap_intent_structured_hash_circuit() {
+        type C = PoseidonGoldilocksConfig;
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let mut pw = PartialWitness::<F>::new();
+
+        let swap_intent = SwapIntent {
+            inputs: SwapInputs::new(
+                Address::new([255; 20]),
+                Address::new([125; 20]),
+                Address::new([64; 20]),
+                BigUint::from(100_000_000_000_u64),
+                SwapDirection::Sell,
+                u64::MAX,
+            )
+            .unwrap(),
+            constraints: SwapConstraints::new(BigUint::from(42_u8) << 200_u32, 1_700_000_000, true)
+                .unwrap(),
+        };
+
+        let intent_target = SwapIntent::add_virtual_intent_target(&mut builder);
+        swap_intent.set_intent_target(&mut pw, &intent_target);
+        let structured_hash_target =
+            SwapIntent::structured_hash_target(&mut builder, &intent_target);
+        let expected_structured_hash_target =
+            builder.constant_bytes(&swap_intent.structured_hash());
+        builder.connect_bytes(&structured_hash_target, &expected_structured_hash_target);
+        let signing_hash_target = builder.signing_hash_target(&domain(), &structured_hash_target);
+        let expected_signing_hash_target =
+            builder.constant_bytes(&swap_intent.signing_hash(&domain()));
+        builder.connect_bytes(&signing_hash_target, &expected_signing_hash_target);
+
+        let data = builder.build::<C>();
+        let proof = data.prove(pw).unwrap();
+        data.verify(proof).expect("Failed to verify proof data")
+    }
 }
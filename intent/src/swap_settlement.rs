@@ -0,0 +1,1200 @@
+//! Settlement of a batch of [`SwapIntent`]s, and a circuit proving that it satisfies
+//! every intent's constraints.
+//!
+//! A settlement fills intents of the batch at clearing prices of their token pair. The prices
+//! of a batch auction's settlement are uniform, a single one per pair, which the circuit also
+//! enforces (see [`SwapSettlement::with_uniform_prices`]), while a solver matching intents
+//! pairwise prices each match.
+//! An intent trades the pair of its clearing price in either direction: whatever the quote and
+//! base tokens of the intent, its fills are given in the price's quote and base tokens, and it
+//! sells either of them (see [`SwapIntent::order`]). A partially fillable intent may be filled
+//! several times, up to its size, while an intent which is not partially fillable is filled at
+//! most once. Intents may also be filled through AMM pools by [`RoutedFill`]s, whose routes,
+//! pools and amounts are part of the settlement, within their intents' limits but outside of
+//! the clearing prices. The circuit does not simulate the pools, whose reserves are not known
+//! to it: the routes are committed, so that the settlement executes exactly the proven
+//! routes. The circuit witnesses the batch's intents, so that it exposes the same batch
+//! commitment as the batch's aggregated signature proof (see [`crate::aggregation`]), and the
+//! settlement is exposed through a Poseidon commitment. The circuit's shape depends on which
+//! intents are filled at which price, so that a verifier rebuilds it from the settlement.
+use std::cmp::Ordering;
+
+use anyhow::anyhow;
+use num_bigint::BigUint;
+use plonky2::{
+    field::types::Field,
+    hash::{
+        hash_types::{HashOut, HashOutTarget},
+        poseidon::PoseidonHash,
+    },
+    iop::{
+        target::Target,
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::{
+            CircuitConfig, CircuitData, CommonCircuitData, VerifierCircuitData,
+            VerifierOnlyCircuitData,
+        },
+        config::{GenericConfig, Hasher},
+        proof::ProofWithPublicInputs,
+    },
+};
+use plonky2_ecdsa::gadgets::biguint::{BigUintTarget, CircuitBuilderBiguint, WitnessBigUint};
+use types::types::Address;
+
+use crate::{
+    aggregation::{intent_batch_commitment, intent_batch_commitment_target},
+    auction::RoutedFill,
+    circuit::{AddressTarget, ECDSAIntentCircuit, IntentCircuitInterface},
+    keccak::{CircuitBuilderKeccak, WitnessKeccak},
+    order::{gcd, CircuitBuilderOrder, Order},
+    pool::token_pair,
+    public_inputs::{bytes_to_u32_words, intent_commitment, intent_commitment_target},
+    structured_hash::StructuredHashInterface,
+    swap_intent::{SwapIntent, SwapIntentTarget, UINT256_LIMBS},
+    D, F,
+};
+
+/// Uniform price of a token pair, `quote_amount` quote tokens for `base_amount` base tokens.
+///
+/// The quote and base tokens of a price are those of its pair's fills, and do not have to be
+/// the quote and base tokens of the filled intents.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClearingPrice {
+    pub quote_token: Address,
+    pub base_token: Address,
+    pub quote_amount: BigUint,
+    pub base_amount: BigUint,
+}
+
+impl ClearingPrice {
+    /// Price of the same ratio, with coprime amounts
+    pub fn reduced(
+        quote_token: Address,
+        base_token: Address,
+        quote_amount: BigUint,
+        base_amount: BigUint,
+    ) -> Self {
+        let divisor = gcd(&quote_amount, &base_amount);
+        Self {
+            quote_token,
+            base_token,
+            quote_amount: quote_amount / &divisor,
+            base_amount: base_amount / divisor,
+        }
+    }
+
+    /// Whether `order` sells the quote token for the base token, or the base token for the
+    /// quote token, `None` if it does not trade the price's pair
+    pub fn sells_quote(&self, order: &Order) -> Option<bool> {
+        if order.sell_token == self.quote_token && order.buy_token == self.base_token {
+            Some(true)
+        } else if order.sell_token == self.base_token && order.buy_token == self.quote_token {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    /// Amounts sold and bought by one unit of a fill of `order`, which trades the price's pair
+    pub(crate) fn unit(&self, order: &Order) -> (&BigUint, &BigUint) {
+        if order.sell_token == self.base_token {
+            (&self.base_amount, &self.quote_amount)
+        } else {
+            (&self.quote_amount, &self.base_amount)
+        }
+    }
+
+    /// Orders prices of the same pair by quote tokens per base token
+    pub(crate) fn cmp_price(&self, other: &ClearingPrice) -> Ordering {
+        (&self.quote_amount * &other.base_amount).cmp(&(&other.quote_amount * &self.base_amount))
+    }
+}
+
+/// Amounts exchanged by an intent of the batch, at one of the settlement's clearing prices.
+///
+/// The intent either sells `quote_amount` of the price's quote token for `base_amount` of its
+/// base token, or sells `base_amount` base tokens for `quote_amount` quote tokens.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SwapFill {
+    /// index of the intent in the batch
+    pub intent: usize,
+    /// index of the clearing price in the settlement
+    pub price: usize,
+    pub quote_amount: BigUint,
+    pub base_amount: BigUint,
+}
+
+/// Solver's proposed settlement of a batch of [`SwapIntent`]s
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SwapSettlement {
+    pub clearing_prices: Vec<ClearingPrice>,
+    pub fills: Vec<SwapFill>,
+    /// fills through AMM pools, outside of the clearing prices
+    pub routed_fills: Vec<RoutedFill>,
+    /// whether each token pair has a single clearing price, as in a batch auction, rather than
+    /// a price per match
+    pub uniform_prices: bool,
+}
+
+/// Shape of a settlement, which decides its circuit
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct SettlementShape {
+    /// index of the filled intent, and of its clearing price, for each fill
+    fills: Vec<(usize, usize)>,
+    /// index of the filled intent, and number of pools of each of its routes, for each
+    /// routed fill
+    routed_fills: Vec<(usize, Vec<usize>)>,
+    /// whether the clearing prices are for distinct token pairs
+    uniform_prices: bool,
+}
+
+impl SwapSettlement {
+    pub fn new(clearing_prices: Vec<ClearingPrice>, fills: Vec<SwapFill>) -> Self {
+        Self {
+            clearing_prices,
+            fills,
+            routed_fills: vec![],
+            uniform_prices: false,
+        }
+    }
+
+    /// Settlement at uniform prices, with at most one clearing price per token pair
+    pub fn with_uniform_prices(mut self) -> Self {
+        self.uniform_prices = true;
+        self
+    }
+
+    /// Settlement also filling intents through AMM pools
+    pub fn with_routed_fills(mut self, routed_fills: Vec<RoutedFill>) -> Self {
+        self.routed_fills = routed_fills;
+        self
+    }
+
+    fn shape(&self) -> SettlementShape {
+        SettlementShape {
+            fills: self
+                .fills
+                .iter()
+                .map(|fill| (fill.intent, fill.price))
+                .collect(),
+            routed_fills: self
+                .routed_fills
+                .iter()
+                .map(|routed_fill| {
+                    let pools = routed_fill
+                        .routes
+                        .iter()
+                        .map(|route| route.pools.len())
+                        .collect();
+                    (routed_fill.intent, pools)
+                })
+                .collect(),
+            uniform_prices: self.uniform_prices,
+        }
+    }
+
+    /// Checks the settlement's shape: each fill refers to an intent of a batch of
+    /// `num_intents`, and to one of the clearing prices, and each route goes through at least
+    /// one pool, trading one more token than its number of pools
+    fn check_shape(&self, num_intents: usize) -> Result<(), anyhow::Error> {
+        for (index, fill) in self.fills.iter().enumerate() {
+            if fill.intent >= num_intents {
+                return Err(anyhow!("Fill {} refers to an invalid intent", index));
+            }
+            if fill.price >= self.clearing_prices.len() {
+                return Err(anyhow!(
+                    "Fill {} refers to an invalid clearing price",
+                    index
+                ));
+            }
+        }
+        for (index, routed_fill) in self.routed_fills.iter().enumerate() {
+            if routed_fill.intent >= num_intents {
+                return Err(anyhow!("Routed fill {} refers to an invalid intent", index));
+            }
+            if routed_fill.routes.is_empty()
+                || routed_fill.routes.iter().any(|route| {
+                    route.pools.is_empty() || route.tokens.len() != route.pools.len() + 1
+                })
+            {
+                return Err(anyhow!("Routed fill {} has an invalid route", index));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the settlement satisfies the batch's intents, as proven by the
+    /// settlement circuit:
+    /// - at uniform prices, each token pair has at most one clearing price
+    /// - each fill is at the clearing price of its intent's token pair
+    /// - each fill is within its intent's limits (see [`Order::accepts_fill`]): an intent
+    ///   which is not partially fillable is filled once, selling at most its size for at least
+    ///   its minimum, while a partially fillable intent is filled at its limit price or better,
+    ///   and its fills sell at most its size
+    /// - each routed fill is within its intent's limits, summing its routes, which trade the
+    ///   intent's sold token for its bought token
+    /// - for each token pair, the tokens sold by the filled intents at its clearing price are
+    ///   the tokens they buy
+    pub fn check(&self, intents: &[SwapIntent]) -> Result<(), anyhow::Error> {
+        self.check_shape(intents.len())?;
+
+        let zero = BigUint::from(0_u8);
+        let max_amount = BigUint::from(1_u8) << (32 * UINT256_LIMBS);
+        for (index, price) in self.clearing_prices.iter().enumerate() {
+            if price.quote_amount == zero || price.base_amount == zero {
+                return Err(anyhow!("Clearing price {} is zero", index));
+            }
+            if price.quote_amount >= max_amount || price.base_amount >= max_amount {
+                return Err(anyhow!("Clearing price {} overflows an uint256", index));
+            }
+            if price.quote_token == price.base_token {
+                return Err(anyhow!(
+                    "Clearing price {} trades a token for itself",
+                    index
+                ));
+            }
+            if self.uniform_prices {
+                let pair = token_pair(price.quote_token, price.base_token);
+                if let Some(other) = self.clearing_prices[..index]
+                    .iter()
+                    .position(|other| token_pair(other.quote_token, other.base_token) == pair)
+                {
+                    return Err(anyhow!(
+                        "Clearing prices {} and {} are for the same token pair",
+                        other,
+                        index
+                    ));
+                }
+            }
+        }
+
+        let orders = intents.iter().map(SwapIntent::order).collect::<Vec<_>>();
+        let mut quote_sold = vec![zero.clone(); self.clearing_prices.len()];
+        let mut quote_bought = quote_sold.clone();
+        let mut base_sold = quote_sold.clone();
+        let mut base_bought = quote_sold.clone();
+        let mut intent_sold = vec![zero; intents.len()];
+        let mut intent_fills = vec![0_usize; intents.len()];
+        for (index, fill) in self.fills.iter().enumerate() {
+            let order = &orders[fill.intent];
+            let price = &self.clearing_prices[fill.price];
+            if fill.quote_amount >= max_amount || fill.base_amount >= max_amount {
+                return Err(anyhow!("Fill {} overflows an uint256", index));
+            }
+            let Some(sells_quote) = price.sells_quote(order) else {
+                return Err(anyhow!(
+                    "Fill {} is priced for another token pair than its intent's",
+                    index
+                ));
+            };
+            if &fill.quote_amount * &price.base_amount != &fill.base_amount * &price.quote_amount {
+                return Err(anyhow!("Fill {} is not at the clearing price", index));
+            }
+            let (sold_amount, bought_amount) = if sells_quote {
+                quote_sold[fill.price] += &fill.quote_amount;
+                base_bought[fill.price] += &fill.base_amount;
+                (&fill.quote_amount, &fill.base_amount)
+            } else {
+                base_sold[fill.price] += &fill.base_amount;
+                quote_bought[fill.price] += &fill.quote_amount;
+                (&fill.base_amount, &fill.quote_amount)
+            };
+            if !order.accepts_fill(sold_amount, bought_amount) {
+                return Err(anyhow!(
+                    "Fill {} does not satisfy the constraints of intent {}",
+                    index,
+                    fill.intent
+                ));
+            }
+            intent_sold[fill.intent] += sold_amount;
+            intent_fills[fill.intent] += 1;
+        }
+        for (index, routed_fill) in self.routed_fills.iter().enumerate() {
+            let order = &orders[routed_fill.intent];
+            for route in &routed_fill.routes {
+                if route.amount_in >= max_amount || route.amount_out >= max_amount {
+                    return Err(anyhow!("Routed fill {} overflows an uint256", index));
+                }
+                if route.tokens.first() != Some(&order.sell_token)
+                    || route.tokens.last() != Some(&order.buy_token)
+                {
+                    return Err(anyhow!(
+                        "Routes of routed fill {} do not trade the tokens of intent {}",
+                        index,
+                        routed_fill.intent
+                    ));
+                }
+            }
+            let sold_amount = routed_fill.sold_amount();
+            if !order.accepts_fill(&sold_amount, &routed_fill.bought_amount()) {
+                return Err(anyhow!(
+                    "Routed fill {} does not satisfy the constraints of intent {}",
+                    index,
+                    routed_fill.intent
+                ));
+            }
+            intent_sold[routed_fill.intent] += sold_amount;
+            intent_fills[routed_fill.intent] += 1;
+        }
+
+        for (intent, order) in orders.iter().enumerate() {
+            if intent_fills[intent] > 1 && !order.partially_fillable {
+                return Err(anyhow!(
+                    "Intent {} is not partially fillable, but is filled more than once",
+                    intent
+                ));
+            }
+            if intent_sold[intent] > order.sell_amount {
+                return Err(anyhow!("Fills of intent {} exceed its size", intent));
+            }
+        }
+        for index in 0..self.clearing_prices.len() {
+            if quote_sold[index] != quote_bought[index] || base_sold[index] != base_bought[index] {
+                return Err(anyhow!(
+                    "Token balances are not conserved for the pair of clearing price {}",
+                    index
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Poseidon commitment to the settlement, as exposed by its proof
+    pub fn commitment(&self) -> HashOut<F> {
+        let uint256_limbs = |value: &BigUint| {
+            let mut limbs = value.to_u32_digits();
+            limbs.resize(UINT256_LIMBS, 0);
+            limbs.into_iter().map(F::from_canonical_u32)
+        };
+
+        let mut elements = vec![];
+        for price in &self.clearing_prices {
+            elements.extend(bytes_to_u32_words(price.quote_token.as_bytes()));
+            elements.extend(bytes_to_u32_words(price.base_token.as_bytes()));
+            elements.extend(uint256_limbs(&price.quote_amount));
+            elements.extend(uint256_limbs(&price.base_amount));
+        }
+        for fill in &self.fills {
+            elements.push(F::from_canonical_usize(fill.intent));
+            elements.push(F::from_canonical_usize(fill.price));
+            elements.extend(uint256_limbs(&fill.quote_amount));
+            elements.extend(uint256_limbs(&fill.base_amount));
+        }
+        for routed_fill in &self.routed_fills {
+            elements.push(F::from_canonical_usize(routed_fill.intent));
+            for route in &routed_fill.routes {
+                elements.extend(
+                    route
+                        .pools
+                        .iter()
+                        .map(|pool| F::from_canonical_usize(*pool)),
+                );
+                for token in &route.tokens {
+                    elements.extend(bytes_to_u32_words(token.as_bytes()));
+                }
+                elements.extend(uint256_limbs(&route.amount_in));
+                elements.extend(uint256_limbs(&route.amount_out));
+            }
+        }
+        PoseidonHash::hash_no_pad(&elements)
+    }
+}
+
+/// Proof that a settlement satisfies the constraints of a batch's intents
+pub struct SwapSettlementProofData<C: GenericConfig<D, F = F>> {
+    pub proof_with_pis: ProofWithPublicInputs<F, C, D>,
+    pub common: CommonCircuitData<F, D>,
+    pub verifier_only: VerifierOnlyCircuitData<C, D>,
+}
+
+/// In-circuit [`ClearingPrice`]
+struct ClearingPriceTarget {
+    quote_token: AddressTarget,
+    base_token: AddressTarget,
+    quote_amount: BigUintTarget,
+    base_amount: BigUintTarget,
+}
+
+/// In-circuit [`SwapFill`]
+struct SwapFillTarget {
+    quote_amount: BigUintTarget,
+    base_amount: BigUintTarget,
+}
+
+/// In-circuit [`Route`](crate::amm::Route)
+struct RouteTarget {
+    pools: Vec<Target>,
+    tokens: Vec<AddressTarget>,
+    amount_in: BigUintTarget,
+    amount_out: BigUintTarget,
+}
+
+/// Adds a virtual `uint256`, range checking its limbs
+pub(crate) fn add_virtual_uint256_target(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+) -> BigUintTarget {
+    let value = circuit_builder.add_virtual_biguint_target(UINT256_LIMBS);
+    for limb in &value.limbs {
+        circuit_builder.range_check(limb.0, 32);
+    }
+    value
+}
+
+/// Settlement witnessed in a circuit, and constrained to satisfy the intents the circuit
+/// witnesses, so that any circuit witnessing a batch can prove its settlement
+pub struct SwapSettlementTargets {
+    shape: SettlementShape,
+    price_targets: Vec<ClearingPriceTarget>,
+    fill_targets: Vec<SwapFillTarget>,
+    /// routes of each routed fill
+    route_targets: Vec<Vec<RouteTarget>>,
+    /// Poseidon commitment to the settlement, see [`SwapSettlement::commitment`]
+    pub commitment: HashOutTarget,
+}
+
+impl SwapSettlementTargets {
+    /// Adds the targets of a settlement of the batch of `intent_targets`, with the same
+    /// clearing prices and fills (but not amounts) as `settlement`, and constrains it as
+    /// [`SwapSettlement::check`] checks it
+    pub fn new(
+        circuit_builder: &mut CircuitBuilder<F, D>,
+        intent_targets: &[SwapIntentTarget],
+        settlement: &SwapSettlement,
+    ) -> Result<Self, anyhow::Error> {
+        settlement.check_shape(intent_targets.len())?;
+
+        let one = circuit_builder.constant_biguint(&BigUint::from(1_u8));
+        let price_targets = settlement
+            .clearing_prices
+            .iter()
+            .map(|_| {
+                let price_target = ClearingPriceTarget {
+                    quote_token: circuit_builder.add_virtual_address_target(),
+                    base_token: circuit_builder.add_virtual_address_target(),
+                    quote_amount: add_virtual_uint256_target(circuit_builder),
+                    base_amount: add_virtual_uint256_target(circuit_builder),
+                };
+                // prices are non zero, otherwise any amounts would be at the price
+                for amount in [&price_target.quote_amount, &price_target.base_amount] {
+                    let non_zero = circuit_builder.cmp_biguint(&one, amount);
+                    circuit_builder.assert_one(non_zero.target);
+                }
+                price_target
+            })
+            .collect::<Vec<_>>();
+        // at uniform prices, no two clearing prices are for the same token pair, in either
+        // order
+        if settlement.uniform_prices {
+            for (index, price_target) in price_targets.iter().enumerate() {
+                for other_target in &price_targets[..index] {
+                    let same_quote = circuit_builder
+                        .is_equal_address(&price_target.quote_token, &other_target.quote_token);
+                    let same_base = circuit_builder
+                        .is_equal_address(&price_target.base_token, &other_target.base_token);
+                    let same_order = circuit_builder.and(same_quote, same_base);
+                    let swapped_quote = circuit_builder
+                        .is_equal_address(&price_target.quote_token, &other_target.base_token);
+                    let swapped_base = circuit_builder
+                        .is_equal_address(&price_target.base_token, &other_target.quote_token);
+                    let swapped_order = circuit_builder.and(swapped_quote, swapped_base);
+                    let same_pair = circuit_builder.or(same_order, swapped_order);
+                    circuit_builder.assert_zero(same_pair.target);
+                }
+            }
+        }
+        let order_targets = intent_targets
+            .iter()
+            .map(|intent_target| intent_target.order(circuit_builder))
+            .collect::<Vec<_>>();
+
+        let zero = circuit_builder.zero_biguint();
+        let mut quote_sold = vec![zero.clone(); price_targets.len()];
+        let mut quote_bought = quote_sold.clone();
+        let mut base_sold = quote_sold.clone();
+        let mut base_bought = quote_sold.clone();
+        let mut intent_sold = vec![zero; intent_targets.len()];
+        let mut intent_fills = vec![0_usize; intent_targets.len()];
+        let mut fill_targets = vec![];
+        for fill in &settlement.fills {
+            let order_target = &order_targets[fill.intent];
+            let price_target = &price_targets[fill.price];
+            let fill_target = SwapFillTarget {
+                quote_amount: add_virtual_uint256_target(circuit_builder),
+                base_amount: add_virtual_uint256_target(circuit_builder),
+            };
+
+            // the intent sells either token of the price's pair, for the other one
+            let sells_quote = circuit_builder
+                .is_equal_address(&order_target.sell_token, &price_target.quote_token);
+            let sells_base = circuit_builder.not(sells_quote);
+            let sell_token = circuit_builder.select_address(
+                sells_quote,
+                &price_target.quote_token,
+                &price_target.base_token,
+            );
+            circuit_builder.connect_address(&order_target.sell_token, &sell_token);
+            let buy_token = circuit_builder.select_address(
+                sells_quote,
+                &price_target.base_token,
+                &price_target.quote_token,
+            );
+            circuit_builder.connect_address(&order_target.buy_token, &buy_token);
+
+            let fill_quote_value =
+                circuit_builder.mul_biguint(&fill_target.quote_amount, &price_target.base_amount);
+            let fill_base_value =
+                circuit_builder.mul_biguint(&fill_target.base_amount, &price_target.quote_amount);
+            circuit_builder.connect_biguint(&fill_quote_value, &fill_base_value);
+
+            let sold_amount = circuit_builder.select_biguint(
+                sells_quote,
+                &fill_target.quote_amount,
+                &fill_target.base_amount,
+            );
+            let bought_amount = circuit_builder.select_biguint(
+                sells_quote,
+                &fill_target.base_amount,
+                &fill_target.quote_amount,
+            );
+            circuit_builder.assert_order_accepts_fill(order_target, &sold_amount, &bought_amount);
+            intent_sold[fill.intent] =
+                circuit_builder.add_biguint(&intent_sold[fill.intent], &sold_amount);
+            intent_fills[fill.intent] += 1;
+
+            let sold_quote =
+                circuit_builder.mul_biguint_by_bool(&fill_target.quote_amount, sells_quote);
+            let bought_base =
+                circuit_builder.mul_biguint_by_bool(&fill_target.base_amount, sells_quote);
+            let sold_base =
+                circuit_builder.mul_biguint_by_bool(&fill_target.base_amount, sells_base);
+            let bought_quote =
+                circuit_builder.mul_biguint_by_bool(&fill_target.quote_amount, sells_base);
+            quote_sold[fill.price] =
+                circuit_builder.add_biguint(&quote_sold[fill.price], &sold_quote);
+            base_bought[fill.price] =
+                circuit_builder.add_biguint(&base_bought[fill.price], &bought_base);
+            base_sold[fill.price] = circuit_builder.add_biguint(&base_sold[fill.price], &sold_base);
+            quote_bought[fill.price] =
+                circuit_builder.add_biguint(&quote_bought[fill.price], &bought_quote);
+
+            fill_targets.push(fill_target);
+        }
+
+        // a routed fill sums its routes, each trading the intent's sold token for its bought
+        // token through any pools
+        let mut route_targets = vec![];
+        for routed_fill in &settlement.routed_fills {
+            let order_target = &order_targets[routed_fill.intent];
+            let routes = routed_fill
+                .routes
+                .iter()
+                .map(|route| {
+                    let route_target = RouteTarget {
+                        pools: circuit_builder.add_virtual_targets(route.pools.len()),
+                        tokens: (0..route.tokens.len())
+                            .map(|_| circuit_builder.add_virtual_address_target())
+                            .collect(),
+                        amount_in: add_virtual_uint256_target(circuit_builder),
+                        amount_out: add_virtual_uint256_target(circuit_builder),
+                    };
+                    circuit_builder
+                        .connect_address(&route_target.tokens[0], &order_target.sell_token);
+                    circuit_builder.connect_address(
+                        &route_target.tokens[route_target.tokens.len() - 1],
+                        &order_target.buy_token,
+                    );
+                    route_target
+                })
+                .collect::<Vec<_>>();
+            let mut sold_amount = circuit_builder.zero_biguint();
+            let mut bought_amount = circuit_builder.zero_biguint();
+            for route_target in &routes {
+                sold_amount = circuit_builder.add_biguint(&sold_amount, &route_target.amount_in);
+                bought_amount =
+                    circuit_builder.add_biguint(&bought_amount, &route_target.amount_out);
+            }
+            circuit_builder.assert_order_accepts_fill(order_target, &sold_amount, &bought_amount);
+            intent_sold[routed_fill.intent] =
+                circuit_builder.add_biguint(&intent_sold[routed_fill.intent], &sold_amount);
+            intent_fills[routed_fill.intent] += 1;
+            route_targets.push(routes);
+        }
+
+        // an intent filled more than once is partially fillable, and its fills sell at most
+        // its size
+        for (intent, order_target) in order_targets.iter().enumerate() {
+            if intent_fills[intent] > 1 {
+                circuit_builder.assert_one(order_target.partially_fillable.target);
+                let size_bound =
+                    circuit_builder.cmp_biguint(&intent_sold[intent], &order_target.sell_amount);
+                circuit_builder.assert_one(size_bound.target);
+            }
+        }
+        for index in 0..price_targets.len() {
+            circuit_builder.connect_biguint(&quote_sold[index], &quote_bought[index]);
+            circuit_builder.connect_biguint(&base_sold[index], &base_bought[index]);
+        }
+
+        let mut settlement_elements: Vec<Target> = vec![];
+        for price_target in &price_targets {
+            for token in [&price_target.quote_token, &price_target.base_token] {
+                settlement_elements.extend(circuit_builder.bytes_to_u32_words_be(&token.0));
+            }
+            for amount in [&price_target.quote_amount, &price_target.base_amount] {
+                settlement_elements.extend(amount.limbs.iter().map(|limb| limb.0));
+            }
+        }
+        for (fill, fill_target) in settlement.fills.iter().zip(&fill_targets) {
+            settlement_elements
+                .push(circuit_builder.constant(F::from_canonical_usize(fill.intent)));
+            settlement_elements.push(circuit_builder.constant(F::from_canonical_usize(fill.price)));
+            for amount in [&fill_target.quote_amount, &fill_target.base_amount] {
+                settlement_elements.extend(amount.limbs.iter().map(|limb| limb.0));
+            }
+        }
+        for (routed_fill, routes) in settlement.routed_fills.iter().zip(&route_targets) {
+            settlement_elements
+                .push(circuit_builder.constant(F::from_canonical_usize(routed_fill.intent)));
+            for route_target in routes {
+                settlement_elements.extend(&route_target.pools);
+                for token in &route_target.tokens {
+                    settlement_elements.extend(circuit_builder.bytes_to_u32_words_be(&token.0));
+                }
+                for amount in [&route_target.amount_in, &route_target.amount_out] {
+                    settlement_elements.extend(amount.limbs.iter().map(|limb| limb.0));
+                }
+            }
+        }
+        let commitment = circuit_builder.hash_n_to_hash_no_pad::<PoseidonHash>(settlement_elements);
+
+        Ok(Self {
+            shape: settlement.shape(),
+            price_targets,
+            fill_targets,
+            route_targets,
+            commitment,
+        })
+    }
+
+    /// Witnesses `settlement`, which should have the shape the targets were added for
+    pub fn set_targets(
+        &self,
+        partial_witness: &mut PartialWitness<F>,
+        settlement: &SwapSettlement,
+    ) -> Result<(), anyhow::Error> {
+        if settlement.clearing_prices.len() != self.price_targets.len()
+            || settlement.shape() != self.shape
+        {
+            return Err(anyhow!("Settlement does not have the circuit's shape"));
+        }
+        for (price, price_target) in settlement.clearing_prices.iter().zip(&self.price_targets) {
+            partial_witness
+                .set_bytes_target(&price_target.quote_token.0, price.quote_token.as_bytes());
+            partial_witness
+                .set_bytes_target(&price_target.base_token.0, price.base_token.as_bytes());
+            partial_witness.set_biguint_target(&price_target.quote_amount, &price.quote_amount);
+            partial_witness.set_biguint_target(&price_target.base_amount, &price.base_amount);
+        }
+        for (fill, fill_target) in settlement.fills.iter().zip(&self.fill_targets) {
+            partial_witness.set_biguint_target(&fill_target.quote_amount, &fill.quote_amount);
+            partial_witness.set_biguint_target(&fill_target.base_amount, &fill.base_amount);
+        }
+        for (routed_fill, routes) in settlement.routed_fills.iter().zip(&self.route_targets) {
+            for (route, route_target) in routed_fill.routes.iter().zip(routes) {
+                for (pool, pool_target) in route.pools.iter().zip(&route_target.pools) {
+                    partial_witness.set_target(*pool_target, F::from_canonical_usize(*pool));
+                }
+                for (token, token_target) in route.tokens.iter().zip(&route_target.tokens) {
+                    partial_witness.set_bytes_target(&token_target.0, token.as_bytes());
+                }
+                partial_witness.set_biguint_target(&route_target.amount_in, &route.amount_in);
+                partial_witness.set_biguint_target(&route_target.amount_out, &route.amount_out);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Adds the targets of a batch of `num_intents` intents, with their batch commitment
+pub(crate) fn add_virtual_batch_targets(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    num_intents: usize,
+) -> (Vec<SwapIntentTarget>, HashOutTarget) {
+    let intent_targets = (0..num_intents)
+        .map(|_| SwapIntent::add_virtual_intent_target(circuit_builder))
+        .collect::<Vec<_>>();
+    let intent_commitment_targets = intent_targets
+        .iter()
+        .map(|intent_target| {
+            let structured_hash_target =
+                SwapIntent::structured_hash_target(circuit_builder, intent_target);
+            intent_commitment_target(circuit_builder, &structured_hash_target)
+        })
+        .collect::<Vec<_>>();
+    let batch_commitment_target =
+        intent_batch_commitment_target(circuit_builder, &intent_commitment_targets);
+    (intent_targets, batch_commitment_target)
+}
+
+/// Adds the targets of a batch of `num_intents` intents settled like `settlement`, exposing
+/// the batch commitment then the settlement commitment
+fn add_batch_settlement_targets(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    num_intents: usize,
+    settlement: &SwapSettlement,
+) -> Result<(Vec<SwapIntentTarget>, SwapSettlementTargets), anyhow::Error> {
+    let (intent_targets, batch_commitment_target) =
+        add_virtual_batch_targets(circuit_builder, num_intents);
+    let settlement_targets =
+        SwapSettlementTargets::new(circuit_builder, &intent_targets, settlement)?;
+
+    circuit_builder.register_public_inputs(&batch_commitment_target.elements);
+    circuit_builder.register_public_inputs(&settlement_targets.commitment.elements);
+    Ok((intent_targets, settlement_targets))
+}
+
+fn set_batch_settlement_targets(
+    partial_witness: &mut PartialWitness<F>,
+    intent_targets: &[SwapIntentTarget],
+    settlement_targets: &SwapSettlementTargets,
+    intents: &[SwapIntent],
+    settlement: &SwapSettlement,
+) -> Result<(), anyhow::Error> {
+    for (intent, intent_target) in intents.iter().zip(intent_targets) {
+        intent.set_intent_target(partial_witness, intent_target);
+    }
+    settlement_targets.set_targets(partial_witness, settlement)
+}
+
+/// Constrains `settlement` to satisfy the constraints of the witnessed `intents`, in a circuit
+/// exposing their batch commitment then the settlement commitment, as the settlement circuit
+/// (see [`verify_swap_settlement_proof`])
+pub fn constrain_swap_settlement(
+    circuit_builder: &mut CircuitBuilder<F, D>,
+    partial_witness: &mut PartialWitness<F>,
+    intents: &[SwapIntent],
+    settlement: &SwapSettlement,
+) -> Result<(), anyhow::Error> {
+    if intents.is_empty() {
+        return Err(anyhow!("Batch should not be empty"));
+    }
+    settlement.check(intents)?;
+
+    let (intent_targets, settlement_targets) =
+        add_batch_settlement_targets(circuit_builder, intents.len(), settlement)?;
+    set_batch_settlement_targets(
+        partial_witness,
+        &intent_targets,
+        &settlement_targets,
+        intents,
+        settlement,
+    )
+}
+
+/// Settlement circuit for batches of a given size, settled with a given shape
+pub struct SwapSettlementCircuit<C: GenericConfig<D, F = F>> {
+    data: CircuitData<F, C, D>,
+    intent_targets: Vec<SwapIntentTarget>,
+    settlement_targets: SwapSettlementTargets,
+}
+
+impl<C: GenericConfig<D, F = F>> SwapSettlementCircuit<C> {
+    /// Builds the circuit for a batch of `num_intents` intents, settled with the same
+    /// number of clearing prices and the same fills (but not amounts) as `settlement`
+    pub fn new(num_intents: usize, settlement: &SwapSettlement) -> Result<Self, anyhow::Error> {
+        if num_intents == 0 {
+            return Err(anyhow!("Batch should not be empty"));
+        }
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut circuit_builder = CircuitBuilder::<F, D>::new(config);
+        let (intent_targets, settlement_targets) =
+            add_batch_settlement_targets(&mut circuit_builder, num_intents, settlement)?;
+
+        Ok(Self {
+            data: circuit_builder.build::<C>(),
+            intent_targets,
+            settlement_targets,
+        })
+    }
+
+    /// Proves that `settlement` satisfies the constraints of `intents`
+    pub fn prove(
+        &self,
+        intents: &[SwapIntent],
+        settlement: &SwapSettlement,
+    ) -> Result<SwapSettlementProofData<C>, anyhow::Error> {
+        if intents.len() != self.intent_targets.len() {
+            return Err(anyhow!("Batch does not have the circuit's size"));
+        }
+        settlement.check(intents)?;
+
+        let mut partial_witness = PartialWitness::<F>::new();
+        set_batch_settlement_targets(
+            &mut partial_witness,
+            &self.intent_targets,
+            &self.settlement_targets,
+            intents,
+            settlement,
+        )?;
+
+        let proof_with_pis = self.data.prove(partial_witness)?;
+        Ok(SwapSettlementProofData {
+            proof_with_pis,
+            common: self.data.common.clone(),
+            verifier_only: self.data.verifier_only.clone(),
+        })
+    }
+
+    pub fn verifier_data(&self) -> VerifierCircuitData<F, C, D> {
+        self.data.verifier_data()
+    }
+}
+
+/// Proves that `settlement` satisfies the constraints of `intents`
+pub fn prove_swap_settlement<C: GenericConfig<D, F = F>>(
+    intents: &[SwapIntent],
+    settlement: &SwapSettlement,
+) -> Result<SwapSettlementProofData<C>, anyhow::Error> {
+    SwapSettlementCircuit::<C>::new(intents.len(), settlement)?.prove(intents, settlement)
+}
+
+/// Verifies a settlement proof for the batch of intents with the given commitments, against
+/// the circuit rebuilt from `settlement`
+pub fn verify_swap_settlement_proof<C: GenericConfig<D, F = F>>(
+    settlement_proof: &SwapSettlementProofData<C>,
+    intent_commitments: &[HashOut<F>],
+    settlement: &SwapSettlement,
+) -> Result<(), anyhow::Error> {
+    let expected_public_inputs = [
+        intent_batch_commitment(intent_commitments).elements,
+        settlement.commitment().elements,
+    ]
+    .concat();
+    if settlement_proof.proof_with_pis.public_inputs != expected_public_inputs {
+        return Err(anyhow!(
+            "Settlement proof does not cover the given intents and settlement"
+        ));
+    }
+    let verifier_data =
+        SwapSettlementCircuit::<C>::new(intent_commitments.len(), settlement)?.verifier_data();
+    if settlement_proof.verifier_only.circuit_digest != verifier_data.verifier_only.circuit_digest {
+        return Err(anyhow!(
+            "Settlement proof is not from the settlement circuit"
+        ));
+    }
+    verifier_data.verify(settlement_proof.proof_with_pis.clone())
+}
+
+/// Commitments of a batch's intents, as exposed by their signature proofs
+pub fn swap_intent_commitments(intents: &[SwapIntent]) -> Vec<HashOut<F>> {
+    intents
+        .iter()
+        .map(|intent| intent_commitment(&intent.structured_hash()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use plonky2::plonk::config::PoseidonGoldilocksConfig;
+
+    use crate::{
+        amm::Route,
+        swap_intent::SwapDirection,
+        test_utils::{swap_intent_from, token},
+    };
+
+    use super::*;
+
+    type C = PoseidonGoldilocksConfig;
+
+    fn settlement(buy_base_amount: u64, sell_base_amount: u64) -> SwapSettlement {
+        SwapSettlement::new(
+            vec![ClearingPrice {
+                quote_token: Address::new([125; 20]),
+                base_token: Address::new([64; 20]),
+                quote_amount: BigUint::from(5_u8),
+                base_amount: BigUint::from(1_u8),
+            }],
+            vec![
+                SwapFill {
+                    intent: 0,
+                    price: 0,
+                    quote_amount: BigUint::from(5 * buy_base_amount),
+                    base_amount: BigUint::from(buy_base_amount),
+                },
+                SwapFill {
+                    intent: 1,
+                    price: 0,
+                    quote_amount: BigUint::from(5 * sell_base_amount),
+                    base_amount: BigUint::from(sell_base_amount),
+                },
+            ],
+        )
+    }
+
+    #[test]
+    fn it_works_swap_settlement_check() {
+        let intents = vec![
+            swap_intent_from(token(1), SwapDirection::Buy, 100, 18, false),
+            swap_intent_from(token(2), SwapDirection::Sell, 90, 20, false),
+        ];
+        settlement(20, 20).check(&intents).unwrap();
+
+        // the buyer receives less than its minimum
+        let error = settlement(17, 17).check(&intents).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("does not satisfy the constraints"));
+        // the seller delivers tokens which no one receives
+        let error = settlement(19, 20).check(&intents).unwrap_err();
+        assert!(error.to_string().contains("not conserved"));
+        // the fill is not at the clearing price
+        let mut off_price = settlement(20, 20);
+        off_price.fills[0].quote_amount = BigUint::from(99_u8);
+        let error = off_price.check(&intents).unwrap_err();
+        assert!(error.to_string().contains("not at the clearing price"));
+    }
+
+    #[test]
+    fn it_works_swap_settlement_check_partial_fills() {
+        let mut intents = vec![
+            swap_intent_from(token(1), SwapDirection::Buy, 100, 18, false),
+            swap_intent_from(token(2), SwapDirection::Sell, 90, 20, false),
+        ];
+        // a fraction of an intent which is not partially fillable is out of its limits
+        let error = settlement(10, 10).check(&intents).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("does not satisfy the constraints"));
+
+        for intent in &mut intents {
+            intent.constraints.partially_fillable = true;
+        }
+        settlement(10, 10).check(&intents).unwrap();
+
+        // the clearing price is above the buyer's limit price
+        let mut above_limit = settlement(10, 10);
+        above_limit.clearing_prices[0].quote_amount = BigUint::from(6_u8);
+        for fill in &mut above_limit.fills {
+            fill.quote_amount = BigUint::from(60_u8);
+        }
+        let error = above_limit.check(&intents).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("does not satisfy the constraints of intent 0"));
+    }
+
+    #[test]
+    fn it_works_swap_settlement_check_uniform_prices() {
+        let intents = vec![
+            swap_intent_from(token(1), SwapDirection::Buy, 100, 18, false),
+            swap_intent_from(token(2), SwapDirection::Sell, 90, 20, false),
+        ];
+        // a second price of the pair, in the reversed order, is only accepted per match
+        let mut repeated_pair = settlement(20, 20);
+        let price = repeated_pair.clearing_prices[0].clone();
+        repeated_pair.clearing_prices.push(ClearingPrice {
+            quote_token: price.base_token,
+            base_token: price.quote_token,
+            quote_amount: price.base_amount,
+            base_amount: price.quote_amount,
+        });
+        repeated_pair.check(&intents).unwrap();
+        let error = repeated_pair
+            .with_uniform_prices()
+            .check(&intents)
+            .unwrap_err();
+        assert!(error.to_string().contains("for the same token pair"));
+        settlement(20, 20)
+            .with_uniform_prices()
+            .check(&intents)
+            .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "set twice with different values")]
+    fn it_fails_swap_settlement_circuit_repeated_pairs() {
+        let intents = vec![
+            swap_intent_from(token(1), SwapDirection::Buy, 100, 18, false),
+            swap_intent_from(token(2), SwapDirection::Sell, 90, 20, false),
+        ];
+        let mut repeated_pair = settlement(20, 20).with_uniform_prices();
+        repeated_pair
+            .clearing_prices
+            .push(repeated_pair.clearing_prices[0].clone());
+
+        // witnessed without the native check, which rejects the settlement
+        let mut circuit_builder =
+            CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let mut partial_witness = PartialWitness::new();
+        let (intent_targets, settlement_targets) =
+            add_batch_settlement_targets(&mut circuit_builder, intents.len(), &repeated_pair)
+                .unwrap();
+        set_batch_settlement_targets(
+            &mut partial_witness,
+            &intent_targets,
+            &settlement_targets,
+            &intents,
+            &repeated_pair,
+        )
+        .unwrap();
+        let _ = circuit_builder.build::<C>().prove(partial_witness);
+    }
+
+    /// Intents trading the `[125; 20]`/`[64; 20]` pair in either direction, with the
+    /// `Buy` intent of the reversed pair, and a settlement filling the first one twice
+    fn reversed_pair_settlement() -> (Vec<SwapIntent>, SwapSettlement) {
+        let mut reversed_buy = swap_intent_from(token(3), SwapDirection::Buy, 10, 45, false);
+        reversed_buy.inputs.quote_token = Address::new([64; 20]);
+        reversed_buy.inputs.base_token = Address::new([125; 20]);
+        let mut intents = vec![
+            swap_intent_from(token(1), SwapDirection::Buy, 100, 18, false),
+            swap_intent_from(token(2), SwapDirection::Sell, 45, 10, false),
+            reversed_buy,
+        ];
+        intents[0].constraints.partially_fillable = true;
+
+        let fill = |intent: usize| SwapFill {
+            intent,
+            price: 0,
+            quote_amount: BigUint::from(50_u8),
+            base_amount: BigUint::from(10_u8),
+        };
+        let settlement = SwapSettlement::new(
+            settlement(0, 0).clearing_prices,
+            vec![fill(0), fill(1), fill(0), fill(2)],
+        );
+        (intents, settlement)
+    }
+
+    #[test]
+    fn it_works_swap_settlement_check_reversed_pairs() {
+        let (mut intents, settlement) = reversed_pair_settlement();
+        settlement.check(&intents).unwrap();
+
+        // the fills of the partially fillable intent sell more than its size
+        intents[0].inputs.quote_amount = BigUint::from(90_u8);
+        let error = settlement.check(&intents).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Fills of intent 0 exceed its size"));
+
+        // each fill is within the limits of a whole intent, which is filled once
+        intents[0].inputs.quote_amount = BigUint::from(100_u8);
+        intents[0].constraints.min_base_token_amount = BigUint::from(10_u8);
+        intents[0].constraints.partially_fillable = false;
+        let error = settlement.check(&intents).unwrap_err();
+        assert!(error.to_string().contains("filled more than once"));
+
+        // the reversed intent buys less than its minimum of quote tokens
+        let (mut intents, settlement) = reversed_pair_settlement();
+        intents[2].constraints.min_base_token_amount = BigUint::from(51_u8);
+        let error = settlement.check(&intents).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("does not satisfy the constraints of intent 2"));
+    }
+
+    /// Fill of the first intent of [`reversed_pair_settlement`] through two pools, selling
+    /// `amount_in` of its quote tokens
+    fn routed_fill(amount_in: u64, amount_out: u64) -> RoutedFill {
+        RoutedFill {
+            intent: 0,
+            routes: vec![Route {
+                pools: vec![0, 1],
+                tokens: vec![
+                    Address::new([125; 20]),
+                    Address::new([99; 20]),
+                    Address::new([64; 20]),
+                ],
+                amount_in: BigUint::from(amount_in),
+                amount_out: BigUint::from(amount_out),
+            }],
+        }
+    }
+
+    #[test]
+    fn it_works_swap_settlement_check_routed_fills() {
+        let (mut intents, settlement) = reversed_pair_settlement();
+        intents[0].inputs.quote_amount = BigUint::from(120_u8);
+        let routed = settlement.with_routed_fills(vec![routed_fill(20, 4)]);
+        routed.check(&intents).unwrap();
+
+        // the routed fill is below the intent's limit price
+        let mut below_limit = routed.clone();
+        below_limit.routed_fills[0].routes[0].amount_out = BigUint::from(2_u8);
+        let error = below_limit.check(&intents).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Routed fill 0 does not satisfy the constraints of intent 0"));
+        // the routed fill sells more than the rest of the intent
+        let mut overfilled = routed.clone();
+        overfilled.routed_fills[0].routes[0].amount_in = BigUint::from(25_u8);
+        overfilled.routed_fills[0].routes[0].amount_out = BigUint::from(5_u8);
+        let error = overfilled.check(&intents).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Fills of intent 0 exceed its size"));
+        // the route does not end with the intent's bought token
+        let mut other_token = routed.clone();
+        other_token.routed_fills[0].routes[0].tokens[2] = Address::new([99; 20]);
+        let error = other_token.check(&intents).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("do not trade the tokens of intent 0"));
+        // the route's tokens do not match its pools
+        let mut invalid_route = routed;
+        invalid_route.routed_fills[0].routes[0].pools.pop();
+        let error = invalid_route.check(&intents).unwrap_err();
+        assert!(error.to_string().contains("invalid route"));
+    }
+
+    #[test]
+    fn it_works_swap_settlement_circuit() {
+        // covers intents of both pair orientations, filled once or several times, and through
+        // AMM pools
+        let (mut intents, swap_settlement) = reversed_pair_settlement();
+        intents[0].inputs.quote_amount = BigUint::from(120_u8);
+        let swap_settlement = swap_settlement.with_routed_fills(vec![routed_fill(20, 4)]);
+        let intent_commitments = swap_intent_commitments(&intents);
+
+        let settlement_proof = prove_swap_settlement::<C>(&intents, &swap_settlement).unwrap();
+        verify_swap_settlement_proof(&settlement_proof, &intent_commitments, &swap_settlement)
+            .unwrap();
+
+        // the proof covers neither another batch nor another settlement
+        assert!(verify_swap_settlement_proof(
+            &settlement_proof,
+            &intent_commitments[..2],
+            &swap_settlement
+        )
+        .is_err());
+        let mut other_settlement = swap_settlement.clone();
+        other_settlement.fills[0].quote_amount = BigUint::from(45_u8);
+        other_settlement.fills[0].base_amount = BigUint::from(9_u8);
+        assert!(verify_swap_settlement_proof(
+            &settlement_proof,
+            &intent_commitments,
+            &other_settlement
+        )
+        .is_err());
+        // nor other routes
+        let mut other_routes = swap_settlement.clone();
+        other_routes.routed_fills[0].routes[0].pools = vec![2, 1];
+        assert!(verify_swap_settlement_proof(
+            &settlement_proof,
+            &intent_commitments,
+            &other_routes
+        )
+        .is_err());
+
+        // invalid settlements are not proven
+        let mut overfilled = intents;
+        overfilled[0].inputs.quote_amount = BigUint::from(110_u8);
+        assert!(prove_swap_settlement::<C>(&overfilled, &swap_settlement).is_err());
+    }
+}
@@ -0,0 +1,844 @@
+use std::{
+    collections::HashSet,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::anyhow;
+use num_bigint::BigUint;
+use plonky2::{
+    hash::hash_types::HashOut,
+    iop::witness::PartialWitness,
+    plonk::{circuit_builder::CircuitBuilder, config::GenericConfig},
+};
+use types::types::{Address, PublicKey, RecoverableSignature};
+
+use crate::{
+    cancel::{
+        cancellation_key, prove_cancelled_intents_excluded, CancelIntent, CancelTarget,
+        CancellationExclusionProofData,
+    },
+    error::{FillError, ReplayError},
+    fill::FillTracker,
+    intent::{recover_signer, IntentSignatureVerifier},
+    nonce::NonceTracker,
+    order::match_orders,
+    solver::{verify_signature_proofs, IntentSignature, Solver},
+    structured_hash::{Eip712Domain, StructuredHashInterface},
+    swap_intent::SwapIntent,
+    swap_settlement::{constrain_swap_settlement, ClearingPrice, SwapFill, SwapSettlement},
+    D, F,
+};
+
+/// A match between two opposing [`SwapIntent`]s, in the tokens their orders sell (see
+/// [`SwapIntent::order`]).
+///
+/// The taker sells `taker_sold` of its order's sell token, which the maker buys, and the
+/// maker sells `maker_sold` of the taker's buy token in exchange. Both intents may have any
+/// direction: two `Sell` intents on reversed quote/base pairs oppose each other as much as a
+/// `Buy` and a `Sell` intent on the same pair.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SwapMatch {
+    /// index of the taker intent in the batch
+    pub taker: usize,
+    /// index of the maker intent in the batch
+    pub maker: usize,
+    /// tokens sold by the taker to the maker
+    pub taker_sold: BigUint,
+    /// tokens sold by the maker to the taker
+    pub maker_sold: BigUint,
+}
+
+impl SwapMatch {
+    /// Fills of the match, as the intent, the amount it sells and the amount it buys, for the
+    /// taker then the maker
+    pub fn fills(&self) -> [(usize, &BigUint, &BigUint); 2] {
+        [
+            (self.taker, &self.taker_sold, &self.maker_sold),
+            (self.maker, &self.maker_sold, &self.taker_sold),
+        ]
+    }
+}
+
+/// Output of a [`SwapSolver`] run over a batch of [`SwapIntent`]s.
+///
+/// A partially fillable intent may be part of several matches.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SwapExecution {
+    pub matches: Vec<SwapMatch>,
+    /// `filled[i]` is set if the i-th intent of the batch is part of a match
+    pub filled: Vec<bool>,
+}
+
+impl SwapExecution {
+    /// Returns the indices of the filled intents, in batch order.
+    pub fn filled_intents(&self) -> Vec<usize> {
+        self.filled
+            .iter()
+            .enumerate()
+            .filter_map(|(i, filled)| filled.then_some(i))
+            .collect()
+    }
+
+    /// Settlement of the matches of `intents`, each at its own clearing price: the taker's
+    /// sold and bought tokens are the price's quote and base tokens, and both of its fills
+    /// exchange the match's amounts
+    pub fn settlement(&self, intents: &[SwapIntent]) -> SwapSettlement {
+        let (clearing_prices, fills) = self
+            .matches
+            .iter()
+            .enumerate()
+            .map(|(price, swap_match)| {
+                let taker = intents[swap_match.taker].order();
+                let clearing_price = ClearingPrice::reduced(
+                    taker.sell_token,
+                    taker.buy_token,
+                    swap_match.taker_sold.clone(),
+                    swap_match.maker_sold.clone(),
+                );
+                let fill = |intent| SwapFill {
+                    intent,
+                    price,
+                    quote_amount: swap_match.taker_sold.clone(),
+                    base_amount: swap_match.maker_sold.clone(),
+                };
+                (
+                    clearing_price,
+                    [fill(swap_match.taker), fill(swap_match.maker)],
+                )
+            })
+            .unzip::<_, _, Vec<_>, Vec<_>>();
+        SwapSettlement::new(clearing_prices, fills.concat())
+    }
+}
+
+/// Solver matching opposing [`SwapIntent`]s on the same quote/base token pair.
+#[derive(Clone, Debug, Default)]
+pub struct SwapSolver {
+    /// domain in which intents and cancellations are signed
+    domain: Eip712Domain,
+    nonce_tracker: NonceTracker,
+    fill_tracker: FillTracker,
+    /// structured hash and sender of each intent cancelled by structured hash, a cancellation
+    /// signed by another account than the intent's sender cancelling none of its intents
+    cancelled_intents: HashSet<([u8; 32], Address)>,
+}
+
+impl SwapSolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Solver accepting intents and cancellations signed in `domain`, which should name the
+    /// chain and the contract settling the intents. The default domain has no field.
+    pub fn with_domain(mut self, domain: Eip712Domain) -> Self {
+        self.domain = domain;
+        self
+    }
+
+    pub fn domain(&self) -> &Eip712Domain {
+        &self.domain
+    }
+
+    /// Solver accepting intents from the nonces expected by `nonce_tracker`
+    pub fn with_nonce_tracker(mut self, nonce_tracker: NonceTracker) -> Self {
+        self.nonce_tracker = nonce_tracker;
+        self
+    }
+
+    pub fn nonce_tracker(&self) -> &NonceTracker {
+        &self.nonce_tracker
+    }
+
+    /// Solver filling intents from their sizes remaining in `fill_tracker`
+    pub fn with_fill_tracker(mut self, fill_tracker: FillTracker) -> Self {
+        self.fill_tracker = fill_tracker;
+        self
+    }
+
+    pub fn fill_tracker(&self) -> &FillTracker {
+        &self.fill_tracker
+    }
+
+    /// Records the fills of an execution of `intents`, once it is settled, so that later
+    /// batches do not overfill its intents. Nothing is recorded if a fill is rejected.
+    pub fn record_execution(
+        &mut self,
+        intents: &[SwapIntent],
+        execution: &SwapExecution,
+    ) -> Result<(), FillError> {
+        self.record_fills(execution.matches.iter().flat_map(|swap_match| {
+            swap_match
+                .fills()
+                .map(|(intent, sold_amount, bought_amount)| {
+                    (&intents[intent], sold_amount, bought_amount)
+                })
+        }))
+    }
+
+    /// Records settled fills, each given as an intent with the amounts its order sells and
+    /// buys. Nothing is recorded if a fill is rejected.
+    pub fn record_fills<'a>(
+        &mut self,
+        fills: impl IntoIterator<Item = (&'a SwapIntent, &'a BigUint, &'a BigUint)>,
+    ) -> Result<(), FillError> {
+        let mut fill_tracker = self.fill_tracker.clone();
+        for (intent, sold_amount, bought_amount) in fills {
+            fill_tracker.record_fill(intent, sold_amount, bought_amount)?;
+        }
+        self.fill_tracker = fill_tracker;
+        Ok(())
+    }
+
+    /// Current unix timestamp, in seconds, against which deadlines are checked
+    pub(crate) fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("System time should be after the unix epoch.")
+            .as_secs()
+    }
+
+    /// Records a cancellation signed by its sender, so that the cancelled intent is
+    /// rejected from any later batch
+    pub fn cancel_intent(
+        &mut self,
+        cancel_intent: &CancelIntent,
+        signature: &RecoverableSignature,
+    ) -> Result<(), anyhow::Error> {
+        let signer = recover_signer(cancel_intent.signing_hash(&self.domain), signature)
+            .map_err(|e| anyhow!("Invalid signature for cancellation: {}", e))?;
+        let signer = Address::from_public_key(&signer);
+        if cancel_intent.from() != signer {
+            return Err(anyhow!(
+                "Cancellation is from {} but was signed by {}",
+                cancel_intent.from(),
+                signer
+            ));
+        }
+        match cancel_intent.target() {
+            CancelTarget::IntentHash(intent_hash) => {
+                self.cancelled_intents.insert((intent_hash, signer));
+            }
+            CancelTarget::Nonce(nonce) => self.nonce_tracker.cancel_nonce(&signer, nonce),
+        }
+        Ok(())
+    }
+
+    /// Whether `intent` was cancelled by its sender
+    pub fn is_cancelled(&self, intent: &SwapIntent) -> bool {
+        self.cancelled_intents
+            .contains(&(intent.structured_hash(), intent.inputs.from))
+            || self
+                .nonce_tracker
+                .is_cancelled(&intent.inputs.from, intent.inputs.nonce)
+    }
+
+    /// Keys of the recorded cancellations, by structured hash then by nonce, in a canonical order
+    pub fn cancellation_keys(&self) -> Vec<HashOut<F>> {
+        let mut cancelled_intents = self.cancelled_intents.iter().collect::<Vec<_>>();
+        cancelled_intents.sort();
+        let mut cancelled_nonces = self.nonce_tracker.cancelled_nonces().collect::<Vec<_>>();
+        cancelled_nonces.sort();
+
+        let by_intent_hash = cancelled_intents.into_iter().map(|(intent_hash, from)| {
+            cancellation_key(from, CancelTarget::IntentHash(*intent_hash))
+        });
+        let by_nonce = cancelled_nonces
+            .into_iter()
+            .map(|(from, nonce)| cancellation_key(from, CancelTarget::Nonce(*nonce)));
+        by_intent_hash.chain(by_nonce).collect()
+    }
+
+    /// Proves that `intents` exclude the cancelled intents, whether cancelled by structured
+    /// hash or by nonce, given by [`SwapSolver::cancellation_keys`]
+    pub fn prove_cancelled_intents_excluded<C: GenericConfig<D, F = F>>(
+        &self,
+        intents: &[SwapIntent],
+    ) -> Result<CancellationExclusionProofData<C>, anyhow::Error> {
+        prove_cancelled_intents_excluded(intents, &self.cancellation_keys())
+    }
+}
+
+impl Solver<SwapIntent> for SwapSolver {
+    type Output = SwapExecution;
+
+    fn execute_runtime(&self, intents: Vec<SwapIntent>) -> Self::Output {
+        let mut filled = vec![false; intents.len()];
+        let mut matches = vec![];
+
+        let orders = intents.iter().map(SwapIntent::order).collect::<Vec<_>>();
+        let cancelled = intents
+            .iter()
+            .map(|intent| self.is_cancelled(intent))
+            .collect::<Vec<_>>();
+        // size of each order which can still be filled, given its previous batches
+        let mut remaining = intents
+            .iter()
+            .map(|intent| self.fill_tracker.remaining(intent))
+            .collect::<Vec<_>>();
+        let zero = BigUint::from(0_u8);
+
+        // each order in turn takes the opposing orders, at their limit prices
+        for (taker, taker_order) in orders.iter().enumerate() {
+            for (maker, maker_order) in orders.iter().enumerate() {
+                if remaining[taker] == zero || cancelled[taker] {
+                    break;
+                }
+                if maker == taker || remaining[maker] == zero || cancelled[maker] {
+                    continue;
+                }
+                let Some((taker_sold, maker_sold)) = match_orders(
+                    taker_order,
+                    maker_order,
+                    &remaining[taker],
+                    &remaining[maker],
+                ) else {
+                    continue;
+                };
+
+                // an order which is not partially fillable is filled as a whole
+                for (index, sold) in [(taker, &taker_sold), (maker, &maker_sold)] {
+                    remaining[index] = if orders[index].partially_fillable {
+                        &remaining[index] - sold
+                    } else {
+                        zero.clone()
+                    };
+                    filled[index] = true;
+                }
+                matches.push(SwapMatch {
+                    taker,
+                    maker,
+                    taker_sold,
+                    maker_sold,
+                });
+            }
+        }
+
+        SwapExecution { matches, filled }
+    }
+
+    fn generate_execute_proof<C: GenericConfig<D, F = F>>(
+        &self,
+        circuit_builder: &mut CircuitBuilder<F, D>,
+        partial_witness: &mut PartialWitness<F>,
+        signature_verifier: &IntentSignatureVerifier<C>,
+        intents: Vec<IntentSignature<SwapIntent, C>>,
+    ) -> Result<(), anyhow::Error> {
+        verify_signature_proofs(signature_verifier, &intents)?;
+
+        let intents = intents
+            .into_iter()
+            .map(|intent_signature| intent_signature.intent)
+            .collect::<Vec<_>>();
+        let execution = self.execute_runtime(intents.clone());
+
+        // the witnessed intents constrain each match, so that both counterparties' limits are
+        // enforced on the amounts they exchange
+        constrain_swap_settlement(
+            circuit_builder,
+            partial_witness,
+            &intents,
+            &execution.settlement(&intents),
+        )
+    }
+
+    fn verify_intents_signatures(
+        &mut self,
+        intents: Vec<(SwapIntent, RecoverableSignature)>,
+    ) -> Result<Vec<PublicKey>, anyhow::Error> {
+        let now = Self::now();
+        // nonces are only used once every intent of the batch is accepted
+        let mut nonce_tracker = self.nonce_tracker.clone();
+        let public_keys = intents
+            .iter()
+            .enumerate()
+            .map(|(index, (intent, signature))| {
+                intent.order().check_amounts().map_err(|e| {
+                    anyhow::Error::new(e).context(format!("Rejected intent {}", index))
+                })?;
+                let public_key = recover_signer(intent.signing_hash(&self.domain), signature)
+                    .map_err(|e| anyhow!("Invalid signature for intent {}: {}", index, e))?;
+                let signer = Address::from_public_key(&public_key);
+                if intent.inputs.from != signer {
+                    return Err(anyhow!(
+                        "Intent {} is from {} but was signed by {}",
+                        index,
+                        intent.inputs.from,
+                        signer
+                    ));
+                }
+                if self
+                    .cancelled_intents
+                    .contains(&(intent.structured_hash(), signer))
+                {
+                    let error = ReplayError::Cancelled {
+                        account: signer,
+                        nonce: intent.inputs.nonce,
+                    };
+                    return Err(
+                        anyhow::Error::new(error).context(format!("Rejected intent {}", index))
+                    );
+                }
+                nonce_tracker
+                    .use_nonce(
+                        &intent.inputs.from,
+                        intent.inputs.nonce,
+                        intent.constraints.deadline,
+                        now,
+                    )
+                    .map_err(|e| {
+                        anyhow::Error::new(e).context(format!("Rejected intent {}", index))
+                    })?;
+                Ok(public_key)
+            })
+            .collect::<Result<Vec<_>, anyhow::Error>>()?;
+        self.nonce_tracker = nonce_tracker;
+        Ok(public_keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use types::types::{Message, PrivateKey};
+
+    use plonky2::plonk::{circuit_data::CircuitConfig, config::PoseidonGoldilocksConfig};
+
+    use crate::{
+        aggregation::intent_batch_commitment,
+        cancel::verify_cancelled_intents_excluded,
+        error::IntentError,
+        intent::Intent,
+        swap_intent::SwapDirection,
+        swap_settlement::swap_intent_commitments,
+        test_utils::{
+            domain, proven_intent_signatures, proven_swap_intents, signature_prover,
+            signed_swap_intent, swap_intent, token, ProofConfig,
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn it_works_swap_solver_matches_opposing_intents() {
+        let intents = vec![
+            swap_intent(SwapDirection::Buy, 100, 10, false),
+            swap_intent(SwapDirection::Buy, 50, 10, false),
+            swap_intent(SwapDirection::Sell, 90, 12, false),
+        ];
+
+        let execution = SwapSolver::new().execute_runtime(intents);
+        assert_eq!(
+            execution.matches,
+            vec![SwapMatch {
+                taker: 0,
+                maker: 2,
+                taker_sold: BigUint::from(100_u8),
+                maker_sold: BigUint::from(12_u8),
+            }]
+        );
+        assert_eq!(execution.filled_intents(), vec![0, 2]);
+    }
+
+    #[test]
+    fn it_works_swap_solver_records_reversed_pair_matches() {
+        // sells 10 of token 2 for at least 100 of token 1
+        let mut sell = swap_intent(SwapDirection::Sell, 100, 10, false);
+        sell.inputs.quote_token = Address::new([1; 20]);
+        sell.inputs.base_token = Address::new([2; 20]);
+        // sells 100 of token 1 for at least 10 of token 2
+        let mut reversed_sell = swap_intent(SwapDirection::Sell, 10, 100, false);
+        reversed_sell.inputs.quote_token = Address::new([2; 20]);
+        reversed_sell.inputs.base_token = Address::new([1; 20]);
+        let intents = vec![sell.clone(), reversed_sell.clone()];
+
+        let mut solver = SwapSolver::new();
+        let execution = solver.execute_runtime(intents.clone());
+        assert_eq!(
+            execution.matches,
+            vec![SwapMatch {
+                taker: 0,
+                maker: 1,
+                taker_sold: BigUint::from(10_u8),
+                maker_sold: BigUint::from(100_u8),
+            }]
+        );
+        solver.record_execution(&intents, &execution).unwrap();
+        assert_eq!(solver.fill_tracker().remaining(&sell), BigUint::default());
+        assert_eq!(
+            solver.fill_tracker().remaining(&reversed_sell),
+            BigUint::default()
+        );
+    }
+
+    #[test]
+    fn it_works_swap_solver_skips_incompatible_intents() {
+        let mut other_pair = swap_intent(SwapDirection::Sell, 10, 10, false);
+        other_pair.inputs.base_token = Address::new([65; 20]);
+
+        let intents = vec![
+            swap_intent(SwapDirection::Buy, 100, 10, false),
+            swap_intent(SwapDirection::Sell, 110, 10, false),
+            swap_intent(SwapDirection::Sell, 90, 9, false),
+            other_pair,
+        ];
+
+        let execution = SwapSolver::new().execute_runtime(intents);
+        assert!(execution.matches.is_empty());
+        assert_eq!(execution.filled, vec![false; 4]);
+    }
+
+    #[test]
+    fn it_works_swap_solver_verify_intents_signatures() {
+        let private_key = PrivateKey::new();
+        let public_key = PublicKey::from_private_key(&private_key);
+
+        let mut intent = swap_intent(SwapDirection::Buy, 100, 10, false);
+        intent.inputs.from = Address::from_public_key(&public_key);
+        let signature = intent.sign_intent(private_key, &domain());
+        let mut other_intent = swap_intent(SwapDirection::Sell, 100, 10, false);
+        other_intent.inputs.from = intent.inputs.from;
+
+        // a signature in another domain recovers another signer
+        let other_domain = Eip712Domain {
+            verifying_contract: Some(token(205)),
+            ..domain()
+        };
+        let error = SwapSolver::new()
+            .with_domain(other_domain)
+            .verify_intents_signatures(vec![(intent.clone(), signature.clone())])
+            .unwrap_err();
+        assert!(error.to_string().contains("was signed by"));
+
+        let mut solver = SwapSolver::new().with_domain(domain());
+        // as are amounts which do not fit into an uint256
+        let mut overflowing_intent = intent.clone();
+        overflowing_intent.constraints.min_base_token_amount = BigUint::from(1_u8) << 256_u32;
+        let error = solver
+            .verify_intents_signatures(vec![(overflowing_intent, signature.clone())])
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<IntentError>(),
+            Some(IntentError::AmountOverflow {
+                field: "min_buy_amount",
+                ..
+            })
+        ));
+
+        assert_eq!(
+            solver
+                .verify_intents_signatures(vec![(intent, signature.clone())])
+                .unwrap(),
+            vec![public_key]
+        );
+        // a signature over another intent recovers another signer
+        let other_signer = Address::from_public_key(
+            &recover_signer(other_intent.signing_hash(&domain()), &signature).unwrap(),
+        );
+        assert_ne!(other_signer, other_intent.inputs.from);
+        let error = solver
+            .verify_intents_signatures(vec![(other_intent.clone(), signature)])
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Intent 0 is from {} but was signed by {}",
+                other_intent.inputs.from, other_signer
+            )
+        );
+    }
+
+    #[test]
+    fn it_works_swap_solver_generate_execute_proof() {
+        let proven_intents = proven_swap_intents();
+        let intents = proven_intents
+            .iter()
+            .map(|proven_intent| proven_intent.intent.clone())
+            .collect::<Vec<_>>();
+        let signature_verifier = signature_prover().verifier().unwrap();
+        let solver = SwapSolver::new();
+
+        let mut circuit_builder =
+            CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let mut partial_witness = PartialWitness::new();
+        solver
+            .generate_execute_proof(
+                &mut circuit_builder,
+                &mut partial_witness,
+                &signature_verifier,
+                proven_intent_signatures(),
+            )
+            .unwrap();
+        let circuit_data = circuit_builder.build::<ProofConfig>();
+        let proof_with_pis = circuit_data.prove(partial_witness).unwrap();
+
+        // the proof exposes the batch and the settlement of its matches
+        let settlement = solver.execute_runtime(intents.clone()).settlement(&intents);
+        assert_eq!(settlement.fills.len(), 2);
+        assert_eq!(
+            proof_with_pis.public_inputs,
+            [
+                intent_batch_commitment(&swap_intent_commitments(&intents)).elements,
+                settlement.commitment().elements,
+            ]
+            .concat()
+        );
+        circuit_data.verify(proof_with_pis).unwrap();
+
+        // a valid signature proof of another intent is rejected
+        let mut intent_signatures = proven_intent_signatures();
+        intent_signatures.swap(0, 1);
+        for (intent_signature, intent) in intent_signatures.iter_mut().zip(&intents) {
+            intent_signature.intent = intent.clone();
+        }
+        let mut circuit_builder =
+            CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let error = solver
+            .generate_execute_proof(
+                &mut circuit_builder,
+                &mut PartialWitness::new(),
+                &signature_verifier,
+                intent_signatures,
+            )
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Invalid signature proof for intent 0"));
+    }
+
+    #[test]
+    fn it_works_swap_solver_rejects_intents_from_another_account() {
+        let private_key = PrivateKey::new();
+        // `from` is not the address of the signer
+        let intent = swap_intent(SwapDirection::Buy, 100, 10, false);
+        let signature = intent.sign_intent(private_key, &domain());
+
+        let error = SwapSolver::new()
+            .with_domain(domain())
+            .verify_intents_signatures(vec![(intent, signature)])
+            .unwrap_err();
+        assert!(error.to_string().contains("was signed by"));
+    }
+
+    fn replay_error(error: anyhow::Error) -> ReplayError {
+        error
+            .downcast::<ReplayError>()
+            .expect("Intent should be rejected as a replay")
+    }
+
+    #[test]
+    fn it_fails_swap_solver_replayed_intents() {
+        let private_key = PrivateKey::new();
+        let account = Address::from_public_key(&PublicKey::from_private_key(&private_key));
+        let mut solver = SwapSolver::new().with_domain(domain());
+
+        let batch = vec![
+            signed_swap_intent(&private_key, token(64), 0, u64::MAX),
+            signed_swap_intent(&private_key, token(64), 1, u64::MAX),
+        ];
+        solver.verify_intents_signatures(batch.clone()).unwrap();
+        assert_eq!(solver.nonce_tracker().next_nonce(&account), 2);
+
+        let error = solver.verify_intents_signatures(batch).unwrap_err();
+        assert_eq!(
+            replay_error(error),
+            ReplayError::NonceAlreadyUsed { account, nonce: 0 }
+        );
+
+        let error = solver
+            .verify_intents_signatures(vec![signed_swap_intent(
+                &private_key,
+                token(64),
+                3,
+                u64::MAX,
+            )])
+            .unwrap_err();
+        assert_eq!(
+            replay_error(error),
+            ReplayError::NonceOutOfOrder {
+                account,
+                nonce: 3,
+                expected: 2
+            }
+        );
+
+        let error = solver
+            .verify_intents_signatures(vec![signed_swap_intent(&private_key, token(64), 2, 1)])
+            .unwrap_err();
+        assert!(matches!(
+            replay_error(error),
+            ReplayError::Expired { deadline: 1, .. }
+        ));
+
+        // a rejected batch does not use the nonces of its valid intents
+        let error = solver
+            .verify_intents_signatures(vec![
+                signed_swap_intent(&private_key, token(64), 2, u64::MAX),
+                signed_swap_intent(&private_key, token(64), 2, u64::MAX),
+            ])
+            .unwrap_err();
+        assert!(error.to_string().contains("Rejected intent 1"));
+        assert_eq!(solver.nonce_tracker().next_nonce(&account), 2);
+    }
+
+    #[test]
+    fn it_works_swap_solver_cancel_intent() {
+        let private_key = PrivateKey::new();
+        let account = Address::from_public_key(&PublicKey::from_private_key(&private_key));
+        let (by_hash, by_hash_signature) = signed_swap_intent(&private_key, token(64), 0, u64::MAX);
+        let (by_nonce, by_nonce_signature) =
+            signed_swap_intent(&private_key, token(64), 1, u64::MAX);
+        let (kept, _) = signed_swap_intent(&private_key, token(64), 2, u64::MAX);
+
+        let mut solver = SwapSolver::new().with_domain(domain());
+        let cancel_by_hash = CancelIntent::by_intent_hash(account, by_hash.structured_hash());
+        // only the intent's sender can cancel it
+        let error = solver
+            .cancel_intent(
+                &cancel_by_hash,
+                &cancel_by_hash.sign(PrivateKey::new(), &domain()),
+            )
+            .unwrap_err();
+        assert!(error.to_string().contains("was signed by"));
+        solver
+            .cancel_intent(
+                &cancel_by_hash,
+                &private_key.sign_message_recoverable(&Message::new_message(
+                    cancel_by_hash.signing_hash(&domain()),
+                )),
+            )
+            .unwrap();
+        let cancel_by_nonce = CancelIntent::by_nonce(account, 1);
+        solver
+            .cancel_intent(
+                &cancel_by_nonce,
+                &private_key.sign_message_recoverable(&Message::new_message(
+                    cancel_by_nonce.signing_hash(&domain()),
+                )),
+            )
+            .unwrap();
+        assert!(solver.is_cancelled(&by_hash));
+        assert!(solver.is_cancelled(&by_nonce));
+        assert!(!solver.is_cancelled(&kept));
+
+        let error = solver
+            .verify_intents_signatures(vec![(by_hash.clone(), by_hash_signature)])
+            .unwrap_err();
+        assert_eq!(
+            replay_error(error),
+            ReplayError::Cancelled { account, nonce: 0 }
+        );
+        let error = solver
+            .verify_intents_signatures(vec![(by_nonce.clone(), by_nonce_signature)])
+            .unwrap_err();
+        assert_eq!(
+            replay_error(error),
+            ReplayError::Cancelled { account, nonce: 1 }
+        );
+        // a cancelled buyer is not matched
+        let mut sell = swap_intent(SwapDirection::Sell, 100, 10, false);
+        sell.inputs.nonce = 5;
+        let execution = solver.execute_runtime(vec![by_hash.clone(), sell.clone()]);
+        assert!(execution.matches.is_empty());
+
+        // another account cannot cancel the intent, nor revoke its sender's cancellation
+        let stranger_key = PrivateKey::new();
+        let stranger = Address::from_public_key(&PublicKey::from_private_key(&stranger_key));
+        for intent_hash in [kept.structured_hash(), by_hash.structured_hash()] {
+            let cancel_by_stranger = CancelIntent::by_intent_hash(stranger, intent_hash);
+            solver
+                .cancel_intent(
+                    &cancel_by_stranger,
+                    &stranger_key.sign_message_recoverable(&Message::new_message(
+                        cancel_by_stranger.signing_hash(&domain()),
+                    )),
+                )
+                .unwrap();
+        }
+        assert!(!solver.is_cancelled(&kept));
+        assert!(solver.is_cancelled(&by_hash));
+
+        let batch = vec![kept, sell];
+        let exclusion_proof = solver
+            .prove_cancelled_intents_excluded::<PoseidonGoldilocksConfig>(&batch)
+            .unwrap();
+        verify_cancelled_intents_excluded::<PoseidonGoldilocksConfig, SwapIntent>(
+            &exclusion_proof,
+            &swap_intent_commitments(&batch),
+            &solver.cancellation_keys(),
+        )
+        .unwrap();
+        for cancelled in [by_hash, by_nonce] {
+            assert!(solver
+                .prove_cancelled_intents_excluded::<PoseidonGoldilocksConfig>(&[cancelled])
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn it_works_swap_solver_partial_fills() {
+        // buys with up to 100 quote tokens, at least 2 base tokens per quote token
+        let buy = swap_intent(SwapDirection::Buy, 100, 200, true);
+        let intents = vec![
+            buy.clone(),
+            // sells up to 90 base tokens, at 1 quote token per 3 base tokens
+            swap_intent(SwapDirection::Sell, 30, 90, true),
+            // sells exactly 50 base tokens, for 20 quote tokens
+            swap_intent(SwapDirection::Sell, 20, 50, false),
+        ];
+
+        let mut solver = SwapSolver::new();
+        let execution = solver.execute_runtime(intents.clone());
+        assert_eq!(
+            execution.matches,
+            vec![
+                SwapMatch {
+                    taker: 0,
+                    maker: 1,
+                    taker_sold: BigUint::from(30_u8),
+                    maker_sold: BigUint::from(90_u8),
+                },
+                SwapMatch {
+                    taker: 0,
+                    maker: 2,
+                    taker_sold: BigUint::from(20_u8),
+                    maker_sold: BigUint::from(50_u8),
+                },
+            ]
+        );
+        solver.record_execution(&intents, &execution).unwrap();
+        assert_eq!(solver.fill_tracker().remaining(&buy), BigUint::from(50_u8));
+
+        // the same fills cannot be recorded twice
+        assert!(matches!(
+            solver.record_execution(&intents, &execution),
+            Err(FillError::Overfilled { .. })
+        ));
+        assert_eq!(solver.fill_tracker().remaining(&buy), BigUint::from(50_u8));
+
+        // a later batch only fills the remaining sizes
+        let mut intents = intents;
+        intents.push(swap_intent(SwapDirection::Sell, 100, 300, true));
+        let execution = solver.execute_runtime(intents);
+        assert_eq!(
+            execution.matches,
+            vec![SwapMatch {
+                taker: 0,
+                maker: 3,
+                taker_sold: BigUint::from(50_u8),
+                maker_sold: BigUint::from(150_u8),
+            }]
+        );
+    }
+
+    #[test]
+    fn it_works_swap_solver_respects_partial_limit_prices() {
+        let intents = vec![
+            // at least 4 base tokens per quote token
+            swap_intent(SwapDirection::Buy, 100, 400, true),
+            // at least 1 quote token per 3 base tokens
+            swap_intent(SwapDirection::Sell, 30, 90, true),
+        ];
+
+        let execution = SwapSolver::new().execute_runtime(intents);
+        assert!(execution.matches.is_empty());
+    }
+}
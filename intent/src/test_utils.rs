@@ -0,0 +1,206 @@
+//! Fixtures shared by the crate's tests.
+//!
+//! Building the signature circuit and proving with it take minutes, so that the real
+//! circuit is built once, and the intents proven with it are shared across tests.
+use std::sync::Mutex;
+
+use num_bigint::BigUint;
+use plonky2::plonk::config::PoseidonGoldilocksConfig;
+use types::types::{Address, Message, PrivateKey, PublicKey, RecoverableSignature};
+
+use crate::{
+    intent::{ExecuteRuntime, Intent, SignatureProofData},
+    prover::IntentSignatureProver,
+    public_inputs::PublicInputsOptions,
+    solver::IntentSignature,
+    structured_hash::{Eip712Domain, StructuredHashInterface},
+    swap_intent::{SwapConstraints, SwapDirection, SwapInputs, SwapIntent},
+};
+
+pub(crate) type ProofConfig = PoseidonGoldilocksConfig;
+
+/// Value of `cell`, initialized by the first test to need it, while the others wait for it
+fn shared<T>(cell: &Mutex<Option<&'static T>>, init: impl FnOnce() -> T) -> &'static T {
+    let mut value = cell.lock().expect("Shared fixture initialization failed");
+    match *value {
+        Some(value) => value,
+        None => {
+            let initialized = Box::leak(Box::new(init()));
+            *value = Some(initialized);
+            initialized
+        }
+    }
+}
+
+/// EIP-712 domain in which the test intents and cancellations are signed
+pub(crate) fn domain() -> Eip712Domain {
+    Eip712Domain::new(
+        Some("Intents".to_string()),
+        Some("1".to_string()),
+        Some(BigUint::from(1_u8)),
+        Some(token(204)),
+        None,
+    )
+}
+
+/// Address whose 20 bytes are `byte`
+pub(crate) fn token(byte: u8) -> Address {
+    Address::new([byte; 20])
+}
+
+/// Swap intent of `from`, trading the `[125; 20]` quote token and the `[64; 20]` base
+/// token, with a zero nonce and no deadline
+pub(crate) fn swap_intent_from(
+    from: Address,
+    direction: SwapDirection,
+    quote_amount: u64,
+    min_base_token_amount: u64,
+    partially_fillable: bool,
+) -> SwapIntent {
+    SwapIntent::build_intent(
+        SwapInputs::new(
+            from,
+            token(125),
+            token(64),
+            BigUint::from(quote_amount),
+            direction,
+            0,
+        )
+        .unwrap(),
+        SwapConstraints::new(
+            BigUint::from(min_base_token_amount),
+            u64::MAX,
+            partially_fillable,
+        )
+        .unwrap(),
+        ExecuteRuntime::Swap,
+    )
+}
+
+/// Swap intent of the `[255; 20]` account, as built by [`swap_intent_from`]
+pub(crate) fn swap_intent(
+    direction: SwapDirection,
+    quote_amount: u64,
+    min_base_token_amount: u64,
+    partially_fillable: bool,
+) -> SwapIntent {
+    swap_intent_from(
+        token(255),
+        direction,
+        quote_amount,
+        min_base_token_amount,
+        partially_fillable,
+    )
+}
+
+/// Swap intent of the `[255; 20]` account selling `sell_amount` of the [`token`]
+/// `sell_token` for at least `min_buy_amount` of `buy_token`
+pub(crate) fn token_swap_intent(
+    sell_token: u8,
+    buy_token: u8,
+    sell_amount: u64,
+    min_buy_amount: u64,
+    partially_fillable: bool,
+) -> SwapIntent {
+    let mut intent = swap_intent(
+        SwapDirection::Buy,
+        sell_amount,
+        min_buy_amount,
+        partially_fillable,
+    );
+    intent.inputs.quote_token = token(sell_token);
+    intent.inputs.base_token = token(buy_token);
+    intent
+}
+
+/// Buyer of at least 10 `base_token`s for 100 quote tokens, from the account of
+/// `private_key`, signed by it in [`domain`]
+pub(crate) fn signed_swap_intent(
+    private_key: &PrivateKey,
+    base_token: Address,
+    nonce: u64,
+    deadline: u64,
+) -> (SwapIntent, RecoverableSignature) {
+    let mut intent = swap_intent_from(
+        Address::from_public_key(&PublicKey::from_private_key(private_key)),
+        SwapDirection::Buy,
+        100,
+        10,
+        false,
+    );
+    intent.inputs.base_token = base_token;
+    intent.inputs.nonce = nonce;
+    intent.constraints.deadline = deadline;
+    let signature =
+        private_key.sign_message_recoverable(&Message::new_message(intent.signing_hash(&domain())));
+    (intent, signature)
+}
+
+/// Swap intent with a real proof of its signature by its sender
+pub(crate) struct ProvenSwapIntent {
+    pub intent: SwapIntent,
+    pub public_key: PublicKey,
+    pub signature_proof_data: SignatureProofData<ProofConfig>,
+}
+
+/// Prover of the real signature circuit of swap intents signed in [`domain`], exposing every
+/// public value
+pub(crate) fn signature_prover() -> &'static IntentSignatureProver<ProofConfig, SwapIntent> {
+    static PROVER: Mutex<Option<&IntentSignatureProver<ProofConfig, SwapIntent>>> =
+        Mutex::new(None);
+    shared(&PROVER, || {
+        IntentSignatureProver::new(domain(), PublicInputsOptions::all())
+    })
+}
+
+/// Opposing swap intents, which match, signed by fresh accounts and proven by
+/// [`signature_prover`]: a buyer of at least 10 base tokens for 100 quote tokens,
+/// then a seller of 12 base tokens for at least 90 quote tokens
+pub(crate) fn proven_swap_intents() -> &'static [ProvenSwapIntent] {
+    static INTENTS: Mutex<Option<&Vec<ProvenSwapIntent>>> = Mutex::new(None);
+    let intents = shared(&INTENTS, || {
+        [(SwapDirection::Buy, 100, 10), (SwapDirection::Sell, 90, 12)]
+            .into_iter()
+            .map(|(direction, quote_amount, min_base_token_amount)| {
+                let private_key = PrivateKey::new();
+                let public_key = PublicKey::from_private_key(&private_key);
+                let intent = swap_intent_from(
+                    Address::from_public_key(&public_key),
+                    direction,
+                    quote_amount,
+                    min_base_token_amount,
+                    false,
+                );
+                let signature =
+                    private_key.sign_message(&Message::new_message(intent.signing_hash(&domain())));
+                let signature_proof_data = signature_prover()
+                    .prove(&intent, public_key.clone(), signature)
+                    .expect("Failed to prove the intent's signature");
+                ProvenSwapIntent {
+                    intent,
+                    public_key,
+                    signature_proof_data,
+                }
+            })
+            .collect()
+    });
+    intents
+}
+
+/// [`proven_swap_intents`], each given with a copy of its signature proof
+pub(crate) fn proven_intent_signatures() -> Vec<IntentSignature<SwapIntent, ProofConfig>> {
+    proven_swap_intents()
+        .iter()
+        .map(|proven_intent| {
+            let signature_proof_data = &proven_intent.signature_proof_data;
+            IntentSignature::new(
+                proven_intent.intent.clone(),
+                SignatureProofData {
+                    proof_with_pis: signature_proof_data.proof_with_pis.clone(),
+                    common: signature_proof_data.common.clone(),
+                    verifier_only: signature_proof_data.verifier_only.clone(),
+                },
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,599 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use keccak_hash::keccak;
+use num_bigint::{BigInt, BigUint, Sign};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    error::TypedDataError,
+    structured_hash::{encode_address, encode_string, encode_uint, encode_uint8, EIP712_PREFIX},
+    StructuredHash,
+};
+
+const EIP712_DOMAIN: &str = "EIP712Domain";
+
+/// Member of a struct type, as declared in the `types` section of the payload
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct TypedDataMember {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub member_type: String,
+}
+
+/// An `eth_signTypedData_v4` payload
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypedData {
+    pub types: BTreeMap<String, Vec<TypedDataMember>>,
+    pub primary_type: String,
+    pub domain: Value,
+    pub message: Value,
+}
+
+/// Dynamic typed data value, obtained from its json representation and its EIP-712 type
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TypedValue {
+    Address([u8; 20]),
+    Bool(bool),
+    Uint(BigUint),
+    Int(BigInt),
+    /// `bytes1` up to `bytes32`
+    FixedBytes(Vec<u8>),
+    Bytes(Vec<u8>),
+    String(String),
+    Array(Vec<TypedValue>),
+    /// struct type name, followed by its members' names and values, in declaration order
+    Struct(String, Vec<(String, TypedValue)>),
+}
+
+impl TypedData {
+    pub fn from_json(json: &str) -> Result<Self, TypedDataError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Parses the message, according to the primary type
+    pub fn message_value(&self) -> Result<TypedValue, TypedDataError> {
+        self.parse_value(&self.primary_type, &self.message)
+    }
+
+    /// Parses the domain, according to the `EIP712Domain` type
+    pub fn domain_value(&self) -> Result<TypedValue, TypedDataError> {
+        self.parse_value(EIP712_DOMAIN, &self.domain)
+    }
+
+    /// Parses a json value of the given EIP-712 type
+    pub fn parse_value(
+        &self,
+        value_type: &str,
+        value: &Value,
+    ) -> Result<TypedValue, TypedDataError> {
+        if let Some((element_type, length)) = parse_array_type(value_type)? {
+            let elements = value
+                .as_array()
+                .filter(|elements| length.map_or(true, |length| elements.len() == length))
+                .ok_or_else(|| invalid_value(value_type, value))?;
+            let elements = elements
+                .iter()
+                .map(|element| self.parse_value(element_type, element))
+                .collect::<Result<Vec<_>, _>>()?;
+            return Ok(TypedValue::Array(elements));
+        }
+
+        if let Some(members) = self.types.get(value_type) {
+            let object = value
+                .as_object()
+                .ok_or_else(|| invalid_value(value_type, value))?;
+            let members = members
+                .iter()
+                .map(|member| {
+                    let member_value = object
+                        .get(&member.name)
+                        .ok_or_else(|| TypedDataError::MissingMember(member.name.clone()))?;
+                    Ok((
+                        member.name.clone(),
+                        self.parse_value(&member.member_type, member_value)?,
+                    ))
+                })
+                .collect::<Result<Vec<_>, TypedDataError>>()?;
+            return Ok(TypedValue::Struct(value_type.to_string(), members));
+        }
+
+        parse_atomic_value(value_type, value)
+    }
+
+    /// Computes `encodeType`, the primary type's encoding followed by
+    /// the encodings of the struct types it references, sorted by name
+    pub fn encode_type(&self, primary_type: &str) -> Result<String, TypedDataError> {
+        let mut referenced_types = BTreeSet::new();
+        self.collect_referenced_types(primary_type, &mut referenced_types)?;
+        referenced_types.remove(primary_type);
+
+        let mut type_encoding = self.encode_single_type(primary_type)?;
+        for referenced_type in referenced_types {
+            type_encoding.push_str(&self.encode_single_type(&referenced_type)?);
+        }
+        Ok(type_encoding)
+    }
+
+    pub fn type_hash(&self, primary_type: &str) -> Result<StructuredHash, TypedDataError> {
+        Ok(keccak(self.encode_type(primary_type)?).to_fixed_bytes())
+    }
+
+    /// Computes `hashStruct` of a json value of the given struct type
+    pub fn hash_struct(
+        &self,
+        struct_type: &str,
+        value: &Value,
+    ) -> Result<StructuredHash, TypedDataError> {
+        if !self.types.contains_key(struct_type) {
+            return Err(TypedDataError::UnknownType(struct_type.to_string()));
+        }
+        let value = self.parse_value(struct_type, value)?;
+        self.encode_value(&value)
+    }
+
+    /// Computes `hashStruct(domain)`
+    pub fn domain_separator(&self) -> Result<StructuredHash, TypedDataError> {
+        self.hash_struct(EIP712_DOMAIN, &self.domain)
+    }
+
+    /// Computes `hashStruct(message)`
+    pub fn message_hash(&self) -> Result<StructuredHash, TypedDataError> {
+        self.hash_struct(&self.primary_type, &self.message)
+    }
+
+    /// Computes the digest signed by a wallet, `keccak256("\x19\x01" ‖ domainSeparator ‖ hashStruct(message))`
+    pub fn signing_hash(&self) -> Result<StructuredHash, TypedDataError> {
+        let output = keccak(
+            [
+                EIP712_PREFIX.as_slice(),
+                self.domain_separator()?.as_slice(),
+                self.message_hash()?.as_slice(),
+            ]
+            .concat(),
+        );
+        Ok(output.to_fixed_bytes())
+    }
+
+    /// Encodes a typed value as a 32-byte word. Dynamic values and arrays are hashed,
+    /// and structs are replaced by their `hashStruct`.
+    pub fn encode_value(&self, value: &TypedValue) -> Result<[u8; 32], TypedDataError> {
+        let encoding = match value {
+            TypedValue::Address(address) => encode_address(address),
+            TypedValue::Bool(value) => encode_uint8(*value as u8),
+            TypedValue::Uint(value) => encode_uint(value, 256).ok_or_else(|| {
+                TypedDataError::InvalidValue("uint256".to_string(), value.to_string())
+            })?,
+            TypedValue::Int(value) => encode_int256(value).ok_or_else(|| {
+                TypedDataError::InvalidValue("int256".to_string(), value.to_string())
+            })?,
+            TypedValue::FixedBytes(bytes) => {
+                let mut output = [0u8; 32];
+                output[..bytes.len()].copy_from_slice(bytes);
+                output
+            }
+            TypedValue::Bytes(bytes) => keccak(bytes).to_fixed_bytes(),
+            TypedValue::String(value) => encode_string(value),
+            TypedValue::Array(elements) => {
+                let encodings = elements
+                    .iter()
+                    .map(|element| self.encode_value(element))
+                    .collect::<Result<Vec<_>, _>>()?;
+                keccak(encodings.concat()).to_fixed_bytes()
+            }
+            TypedValue::Struct(struct_type, members) => {
+                let type_hash = self.type_hash(struct_type)?;
+                let encodings = members
+                    .iter()
+                    .map(|(_, member)| self.encode_value(member))
+                    .collect::<Result<Vec<_>, _>>()?;
+                keccak([type_hash.to_vec(), encodings.concat()].concat()).to_fixed_bytes()
+            }
+        };
+        Ok(encoding)
+    }
+
+    fn encode_single_type(&self, struct_type: &str) -> Result<String, TypedDataError> {
+        let members = self
+            .types
+            .get(struct_type)
+            .ok_or_else(|| TypedDataError::UnknownType(struct_type.to_string()))?;
+        let members = members
+            .iter()
+            .map(|member| format!("{} {}", member.member_type, member.name))
+            .collect::<Vec<_>>();
+        Ok(format!("{}({})", struct_type, members.join(",")))
+    }
+
+    fn collect_referenced_types(
+        &self,
+        value_type: &str,
+        referenced_types: &mut BTreeSet<String>,
+    ) -> Result<(), TypedDataError> {
+        let base_type = value_type.split('[').next().unwrap_or(value_type);
+        if referenced_types.contains(base_type) {
+            return Ok(());
+        }
+        if let Some(members) = self.types.get(base_type) {
+            referenced_types.insert(base_type.to_string());
+            for member in members {
+                self.collect_referenced_types(&member.member_type, referenced_types)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits an array type `T[]` or `T[n]` into `T` and its optional length
+fn parse_array_type(value_type: &str) -> Result<Option<(&str, Option<usize>)>, TypedDataError> {
+    if !value_type.ends_with(']') {
+        return Ok(None);
+    }
+    let start = value_type
+        .rfind('[')
+        .ok_or_else(|| TypedDataError::UnknownType(value_type.to_string()))?;
+    let length = &value_type[start + 1..value_type.len() - 1];
+    let length = if length.is_empty() {
+        None
+    } else {
+        Some(
+            length
+                .parse::<usize>()
+                .map_err(|_| TypedDataError::UnknownType(value_type.to_string()))?,
+        )
+    };
+    Ok(Some((&value_type[..start], length)))
+}
+
+fn parse_atomic_value(value_type: &str, value: &Value) -> Result<TypedValue, TypedDataError> {
+    let invalid = || invalid_value(value_type, value);
+    match value_type {
+        "address" => {
+            let bytes = value.as_str().and_then(parse_hex).ok_or_else(invalid)?;
+            let address = <[u8; 20]>::try_from(bytes).map_err(|_| invalid())?;
+            Ok(TypedValue::Address(address))
+        }
+        "bool" => Ok(TypedValue::Bool(value.as_bool().ok_or_else(invalid)?)),
+        "string" => Ok(TypedValue::String(
+            value.as_str().ok_or_else(invalid)?.to_string(),
+        )),
+        "bytes" => Ok(TypedValue::Bytes(
+            value.as_str().and_then(parse_hex).ok_or_else(invalid)?,
+        )),
+        _ => {
+            if let Some(size) = parse_type_size(value_type, "bytes", 1, 32) {
+                let bytes = value.as_str().and_then(parse_hex).ok_or_else(invalid)?;
+                if bytes.len() != size {
+                    return Err(invalid());
+                }
+                Ok(TypedValue::FixedBytes(bytes))
+            } else if let Some(bits) = parse_type_size(value_type, "uint", 8, 256) {
+                let value = parse_integer(value).ok_or_else(invalid)?;
+                let value = value.to_biguint().ok_or_else(invalid)?;
+                if value.bits() > bits as u64 {
+                    return Err(invalid());
+                }
+                Ok(TypedValue::Uint(value))
+            } else if let Some(bits) = parse_type_size(value_type, "int", 8, 256) {
+                let value = parse_integer(value).ok_or_else(invalid)?;
+                // two's complement range check, i.e. -2^(bits - 1) <= value < 2^(bits - 1)
+                let bound = BigInt::from(1) << (bits - 1);
+                if value < -bound.clone() || value >= bound {
+                    return Err(invalid());
+                }
+                Ok(TypedValue::Int(value))
+            } else {
+                Err(TypedDataError::UnknownType(value_type.to_string()))
+            }
+        }
+    }
+}
+
+/// Parses sizes of types such as `uint64` or `bytes4`, with `uint` and `int` defaulting to 256 bits
+fn parse_type_size(value_type: &str, prefix: &str, min: usize, max: usize) -> Option<usize> {
+    let size = value_type.strip_prefix(prefix)?;
+    if size.is_empty() && prefix != "bytes" {
+        return Some(256);
+    }
+    let size = size.parse::<usize>().ok()?;
+    let is_valid_size = (min..=max).contains(&size) && (prefix == "bytes" || size % 8 == 0);
+    is_valid_size.then_some(size)
+}
+
+/// Parses json numbers, and decimal or `0x` prefixed hexadecimal strings
+fn parse_integer(value: &Value) -> Option<BigInt> {
+    match value {
+        Value::Number(number) => number
+            .as_u64()
+            .map(BigInt::from)
+            .or_else(|| number.as_i64().map(BigInt::from)),
+        Value::String(value) => {
+            let (sign, digits) = match value.strip_prefix('-') {
+                Some(digits) => (Sign::Minus, digits),
+                None => (Sign::Plus, value.as_str()),
+            };
+            let magnitude = match digits.strip_prefix("0x") {
+                Some(hex_digits) => BigUint::parse_bytes(hex_digits.as_bytes(), 16)?,
+                None => BigUint::parse_bytes(digits.as_bytes(), 10)?,
+            };
+            Some(BigInt::from_biguint(sign, magnitude))
+        }
+        _ => None,
+    }
+}
+
+fn parse_hex(value: &str) -> Option<Vec<u8>> {
+    hex::decode(value.strip_prefix("0x")?).ok()
+}
+
+/// Encodes an `int256` value, as a 32-byte two's complement big endian word,
+/// or returns `None` if `value` does not fit into 256 bits
+fn encode_int256(value: &BigInt) -> Option<[u8; 32]> {
+    let bound = BigInt::from(1) << 255_u32;
+    if *value >= bound || *value < -&bound {
+        return None;
+    }
+    let value = if value.sign() == Sign::Minus {
+        ((BigInt::from(1) << 256_u32) + value).magnitude().clone()
+    } else {
+        value.magnitude().clone()
+    };
+    encode_uint(&value, 256)
+}
+
+fn invalid_value(value_type: &str, value: &Value) -> TypedDataError {
+    TypedDataError::InvalidValue(value_type.to_string(), value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+    use types::types::Address;
+
+    use crate::{
+        structured_hash::{encode_uint256, StructuredHashInterface},
+        swap_intent::{SwapConstraints, SwapDirection, SwapInputs, SwapIntent},
+        test_utils::domain,
+    };
+
+    use super::*;
+
+    const MAIL_TYPED_DATA: &str = r#"{
+        "types": {
+            "EIP712Domain": [
+                { "name": "name", "type": "string" },
+                { "name": "version", "type": "string" },
+                { "name": "chainId", "type": "uint256" },
+                { "name": "verifyingContract", "type": "address" }
+            ],
+            "Person": [
+                { "name": "name", "type": "string" },
+                { "name": "wallet", "type": "address" }
+            ],
+            "Mail": [
+                { "name": "from", "type": "Person" },
+                { "name": "to", "type": "Person" },
+                { "name": "contents", "type": "string" }
+            ]
+        },
+        "primaryType": "Mail",
+        "domain": {
+            "name": "Ether Mail",
+            "version": "1",
+            "chainId": 1,
+            "verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"
+        },
+        "message": {
+            "from": {
+                "name": "Cow",
+                "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826"
+            },
+            "to": {
+                "name": "Bob",
+                "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB"
+            },
+            "contents": "Hello, Bob!"
+        }
+    }"#;
+
+    #[test]
+    fn it_works_typed_data_mail_example() {
+        let typed_data = TypedData::from_json(MAIL_TYPED_DATA).unwrap();
+        assert_eq!(
+            typed_data.encode_type("Mail").unwrap(),
+            "Mail(Person from,Person to,string contents)Person(string name,address wallet)"
+        );
+        assert_eq!(
+            typed_data.domain_separator().unwrap(),
+            hex!("f2cee375fa42b42143804025fc449deafd50cc031ca257e0b194a650a912090f")
+        );
+        assert_eq!(
+            typed_data.message_hash().unwrap(),
+            hex!("c52c0ee5d84264471806290a3f2c4cecfc5490626bf912d01f240d7a274b371e")
+        );
+        assert_eq!(
+            typed_data.signing_hash().unwrap(),
+            hex!("be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2")
+        );
+    }
+
+    #[test]
+    fn it_works_typed_data_arrays_and_dynamic_types() {
+        let typed_data = TypedData::from_json(
+            r#"{
+                "types": {
+                    "EIP712Domain": [],
+                    "Tag": [{ "name": "label", "type": "string" }],
+                    "Batch": [
+                        { "name": "delta", "type": "int8" },
+                        { "name": "data", "type": "bytes" },
+                        { "name": "selector", "type": "bytes4" },
+                        { "name": "tags", "type": "Tag[2]" },
+                        { "name": "amounts", "type": "uint64[]" }
+                    ]
+                },
+                "primaryType": "Batch",
+                "domain": {},
+                "message": {
+                    "delta": -1,
+                    "data": "0xdeadbeef",
+                    "selector": "0x01020304",
+                    "tags": [{ "label": "a" }, { "label": "b" }],
+                    "amounts": [1, "0x02", "3"]
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            typed_data.encode_type("Batch").unwrap(),
+            "Batch(int8 delta,bytes data,bytes4 selector,Tag[2] tags,uint64[] amounts)Tag(string label)"
+        );
+
+        let tag_type_hash = keccak("Tag(string label)").to_fixed_bytes();
+        let tag_hash =
+            |label: &str| keccak([tag_type_hash, encode_string(label)].concat()).to_fixed_bytes();
+        let mut selector = [0u8; 32];
+        selector[..4].copy_from_slice(&[1, 2, 3, 4]);
+        let amounts = [1_u8, 2, 3].map(|amount| encode_uint256(&BigUint::from(amount)));
+
+        let expected = keccak(
+            [
+                typed_data.type_hash("Batch").unwrap(),
+                [0xff; 32],
+                keccak(hex!("deadbeef")).to_fixed_bytes(),
+                selector,
+                keccak([tag_hash("a"), tag_hash("b")].concat()).to_fixed_bytes(),
+                keccak(amounts.concat()).to_fixed_bytes(),
+            ]
+            .concat(),
+        )
+        .to_fixed_bytes();
+        assert_eq!(typed_data.message_hash().unwrap(), expected);
+    }
+
+    #[test]
+    fn it_works_typed_data_matches_swap_intent_structured_hash() {
+        let intent = SwapIntent {
+            inputs: SwapInputs::new(
+                Address::new([255; 20]),
+                Address::new([125; 20]),
+                Address::new([64; 20]),
+                BigUint::from(1_000_000_000_000_u64),
+                SwapDirection::Sell,
+                7,
+            )
+            .unwrap(),
+            constraints: SwapConstraints::new(BigUint::from(64_u8), 1_700_000_000, true).unwrap(),
+        };
+
+        let typed_data = TypedData::from_json(&format!(
+            r#"{{
+                "types": {{
+                    "EIP712Domain": [
+                        {{ "name": "name", "type": "string" }},
+                        {{ "name": "version", "type": "string" }},
+                        {{ "name": "chainId", "type": "uint256" }},
+                        {{ "name": "verifyingContract", "type": "address" }}
+                    ],
+                    "SwapIntent": [
+                        {{ "name": "inputs", "type": "SwapInputs" }},
+                        {{ "name": "constraints", "type": "SwapConstraints" }}
+                    ],
+                    "SwapInputs": [
+                        {{ "name": "from", "type": "address" }},
+                        {{ "name": "quote_token", "type": "address" }},
+                        {{ "name": "base_token", "type": "address" }},
+                        {{ "name": "quote_amount", "type": "uint256" }},
+                        {{ "name": "direction", "type": "uint8" }},
+                        {{ "name": "nonce", "type": "uint64" }}
+                    ],
+                    "SwapConstraints": [
+                        {{ "name": "min_base_token_amount", "type": "uint256" }},
+                        {{ "name": "deadline", "type": "uint64" }},
+                        {{ "name": "partially_fillable", "type": "bool" }}
+                    ]
+                }},
+                "primaryType": "SwapIntent",
+                "domain": {{
+                    "name": "Intents",
+                    "version": "1",
+                    "chainId": 1,
+                    "verifyingContract": "0x{}"
+                }},
+                "message": {{
+                    "inputs": {{
+                        "from": "0x{}",
+                        "quote_token": "0x{}",
+                        "base_token": "0x{}",
+                        "quote_amount": "1000000000000",
+                        "direction": 1,
+                        "nonce": 7
+                    }},
+                    "constraints": {{
+                        "min_base_token_amount": 64,
+                        "deadline": 1700000000,
+                        "partially_fillable": true
+                    }}
+                }}
+            }}"#,
+            hex::encode([204; 20]),
+            hex::encode([255; 20]),
+            hex::encode([125; 20]),
+            hex::encode([64; 20]),
+        ))
+        .unwrap();
+
+        assert_eq!(
+            SwapIntent::type_encode(),
+            typed_data.encode_type("SwapIntent").unwrap()
+        );
+        assert_eq!(intent.structured_hash(), typed_data.message_hash().unwrap());
+        // the digest signed by a wallet is the one intents are signed and recovered over
+        assert_eq!(
+            intent.signing_hash(&domain()),
+            typed_data.signing_hash().unwrap()
+        );
+    }
+
+    #[test]
+    fn it_fails_typed_data_invalid_values() {
+        let typed_data = TypedData::from_json(
+            r#"{
+                "types": { "Value": [{ "name": "value", "type": "uint8" }] },
+                "primaryType": "Value",
+                "domain": {},
+                "message": { "value": 256 }
+            }"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            typed_data.message_hash(),
+            Err(TypedDataError::InvalidValue(..))
+        ));
+        assert!(matches!(
+            typed_data.hash_struct("Value", &serde_json::json!({})),
+            Err(TypedDataError::MissingMember(..))
+        ));
+        assert!(matches!(
+            typed_data.parse_value("uint7", &serde_json::json!(1)),
+            Err(TypedDataError::UnknownType(..))
+        ));
+        // the payload has no `EIP712Domain` type
+        assert!(matches!(
+            typed_data.domain_separator(),
+            Err(TypedDataError::UnknownType(..))
+        ));
+        // values built directly are not range checked by parsing
+        let value = BigUint::from(1_u8) << 256_u32;
+        assert!(matches!(
+            typed_data.encode_value(&TypedValue::Uint(value.clone())),
+            Err(TypedDataError::InvalidValue(..))
+        ));
+        assert!(matches!(
+            typed_data.encode_value(&TypedValue::Int(-BigInt::from(value))),
+            Err(TypedDataError::InvalidValue(..))
+        ));
+    }
+}
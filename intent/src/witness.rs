@@ -4,12 +4,64 @@ use plonky2::{
 use plonky2_ecdsa::{
     curve::ecdsa::{ECDSAPublicKey, ECDSASignature},
     gadgets::{
-        biguint::WitnessBigUint, curve::AffinePointTarget, ecdsa::ECDSASignatureTarget,
+        biguint::{BigUintTarget, WitnessBigUint},
+        curve::AffinePointTarget,
+        ecdsa::ECDSASignatureTarget,
         nonnative::CircuitBuilderNonNative,
     },
 };
 
-use crate::{circuit::ECDSASignatureCircuitTargets, C, D, F, FF};
+use types::types::Address;
+
+use crate::{
+    circuit::{AddressTarget, ECDSASignatureCircuitTargets},
+    keccak::WitnessKeccak,
+    C, D, F, FF,
+};
+
+/// Targets of [`ECDSASignatureCircuitTargets`] which are set by the witness. Unlike the
+/// circuit targets, they are set without the circuit builder, so once the circuit is built
+#[derive(Clone, Debug)]
+pub struct ECDSASignatureWitnessTargets {
+    message: BigUintTarget,
+    public_key_x: BigUintTarget,
+    public_key_y: BigUintTarget,
+    signature_r: BigUintTarget,
+    signature_s: BigUintTarget,
+    from_address: AddressTarget,
+}
+
+impl ECDSASignatureWitnessTargets {
+    pub fn new(
+        circuit_builder: &mut CircuitBuilder<F, D>,
+        targets: ECDSASignatureCircuitTargets,
+    ) -> Self {
+        let ECDSASignatureCircuitTargets {
+            message_nonnative_target,
+            public_key_ecdsa_target,
+            signature_ecdsa_signature_target,
+            from_address_target,
+        } = targets;
+
+        let AffinePointTarget {
+            x: x_public_key_target,
+            y: y_public_key_target,
+        } = public_key_ecdsa_target.0;
+        let ECDSASignatureTarget {
+            r: r_signature_target,
+            s: s_signature_target,
+        } = signature_ecdsa_signature_target;
+
+        Self {
+            message: circuit_builder.nonnative_to_canonical_biguint(&message_nonnative_target),
+            public_key_x: circuit_builder.nonnative_to_canonical_biguint(&x_public_key_target),
+            public_key_y: circuit_builder.nonnative_to_canonical_biguint(&y_public_key_target),
+            signature_r: circuit_builder.nonnative_to_canonical_biguint(&r_signature_target),
+            signature_s: circuit_builder.nonnative_to_canonical_biguint(&s_signature_target),
+            from_address: from_address_target,
+        }
+    }
+}
 
 pub trait ECDSAIntentWitness {
     fn verify_signed_intent(
@@ -18,8 +70,19 @@ pub trait ECDSAIntentWitness {
         message: FF,
         public_key: ECDSAPublicKey<C>,
         signature: ECDSASignature<C>,
+        from: Address,
         targets: ECDSASignatureCircuitTargets,
     );
+    /// Sets the signature's targets, as [`ECDSAIntentWitness::verify_signed_intent`]
+    /// does, once the circuit is built
+    fn set_signed_intent(
+        &mut self,
+        message: FF,
+        public_key: ECDSAPublicKey<C>,
+        signature: ECDSASignature<C>,
+        from: Address,
+        targets: &ECDSASignatureWitnessTargets,
+    );
 }
 
 impl ECDSAIntentWitness for PartialWitness<F> {
@@ -29,39 +92,33 @@ impl ECDSAIntentWitness for PartialWitness<F> {
         message: FF,
         public_key: ECDSAPublicKey<C>,
         signature: ECDSASignature<C>,
+        from: Address,
         targets: ECDSASignatureCircuitTargets,
     ) {
-        let ECDSASignatureCircuitTargets {
-            message_nonnative_target,
-            public_key_ecdsa_target,
-            signature_ecdsa_signature_target,
-        } = targets;
-
-        let message_biguint_target =
-            circuit_builder.nonnative_to_canonical_biguint(&message_nonnative_target);
-        self.set_biguint_target(&message_biguint_target, &message.to_canonical_biguint());
-
-        let AffinePointTarget {
-            x: x_public_key_target,
-            y: y_public_key_target,
-        } = public_key_ecdsa_target.0;
-        let x_public_key_target =
-            circuit_builder.nonnative_to_canonical_biguint(&x_public_key_target);
-        let y_public_key_target =
-            circuit_builder.nonnative_to_canonical_biguint(&y_public_key_target);
-        self.set_biguint_target(&x_public_key_target, &public_key.0.x.to_canonical_biguint());
-        self.set_biguint_target(&y_public_key_target, &public_key.0.y.to_canonical_biguint());
+        let targets = ECDSASignatureWitnessTargets::new(circuit_builder, targets);
+        self.set_signed_intent(message, public_key, signature, from, &targets);
+    }
 
-        let ECDSASignatureTarget {
-            r: r_signature_target,
-            s: s_signature_target,
-        } = signature_ecdsa_signature_target;
-        let r_signature_target =
-            circuit_builder.nonnative_to_canonical_biguint(&r_signature_target);
-        let s_signature_target =
-            circuit_builder.nonnative_to_canonical_biguint(&s_signature_target);
-        self.set_biguint_target(&r_signature_target, &signature.r.to_canonical_biguint());
-        self.set_biguint_target(&s_signature_target, &signature.s.to_canonical_biguint());
+    fn set_signed_intent(
+        &mut self,
+        message: FF,
+        public_key: ECDSAPublicKey<C>,
+        signature: ECDSASignature<C>,
+        from: Address,
+        targets: &ECDSASignatureWitnessTargets,
+    ) {
+        self.set_biguint_target(&targets.message, &message.to_canonical_biguint());
+        self.set_biguint_target(
+            &targets.public_key_x,
+            &public_key.0.x.to_canonical_biguint(),
+        );
+        self.set_biguint_target(
+            &targets.public_key_y,
+            &public_key.0.y.to_canonical_biguint(),
+        );
+        self.set_biguint_target(&targets.signature_r, &signature.r.to_canonical_biguint());
+        self.set_biguint_target(&targets.signature_s, &signature.s.to_canonical_biguint());
+        self.set_bytes_target(&targets.from_address.0, from.as_bytes());
     }
 }
 
@@ -80,6 +137,8 @@ mod tests {
         secp256k1::Secp256K1,
     };
 
+    use types::types::PublicKey;
+
     use crate::circuit::ECDSAIntentCircuit;
 
     use super::*;
@@ -104,7 +163,8 @@ mod tests {
         let sig = sign_message(msg, sk);
 
         let ecdsa_targets = builder.verify_intent_signature();
-        pw.verify_signed_intent(&mut builder, msg, pk, sig, ecdsa_targets);
+        let from = Address::from_public_key(&PublicKey::from_plonky2_public_key(pk).unwrap());
+        pw.verify_signed_intent(&mut builder, msg, pk, sig, from, ecdsa_targets);
 
         dbg!(builder.num_gates());
         let data = builder.build::<C>();
@@ -0,0 +1,203 @@
+//! `#[derive(StructuredHash)]`, generating EIP-712 `StructuredHashInterface` implementations.
+//!
+//! Every field of the struct becomes a member of the EIP-712 type, in declaration order.
+//! The member type is taken from the field's `Eip712Member` implementation, and can be
+//! overridden by a field attribute with any `uint<N>` type, for integer fields implementing
+//! `Eip712Uint`, whose values are then checked to fit into `N` bits when encoded. Other
+//! overrides, and overrides of other fields, are rejected at compile time.
+//!
+//! The implementations refer to the `intent` crate, by default as `::intent`, which another
+//! path can replace with a struct attribute, e.g. `crate` from within the `intent` crate:
+//!
+//! ```ignore
+//! #[derive(StructuredHash)]
+//! #[eip712(name = "Order", crate = "crate")]
+//! struct SwapOrder {
+//!     #[eip712(rename = "sellAmount", type = "uint128")]
+//!     sell_amount: BigUint,
+//! }
+//! ```
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, parse_quote, Attribute, Data, DeriveInput, Error, Fields, LitStr, Path, Type,
+};
+
+#[proc_macro_derive(StructuredHash, attributes(eip712))]
+pub fn derive_structured_hash(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_structured_hash(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Options set through `#[eip712(...)]` attributes
+#[derive(Default)]
+struct Eip712Attributes {
+    /// struct name, or member name
+    name: Option<String>,
+    /// member type
+    member_type: Option<LitStr>,
+    /// path to the `intent` crate
+    crate_path: Option<Path>,
+}
+
+fn parse_attributes(attributes: &[Attribute], is_field: bool) -> Result<Eip712Attributes, Error> {
+    let mut output = Eip712Attributes::default();
+    for attribute in attributes
+        .iter()
+        .filter(|attribute| attribute.path().is_ident("eip712"))
+    {
+        attribute.parse_nested_meta(|meta| {
+            let name_attribute = if is_field { "rename" } else { "name" };
+            if meta.path.is_ident(name_attribute) {
+                output.name = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if is_field && meta.path.is_ident("type") {
+                let member_type = meta.value()?.parse::<LitStr>()?;
+                if uint_bits(&member_type.value()).is_none() {
+                    return Err(Error::new_spanned(
+                        member_type,
+                        "unsupported eip712 member type, expected uint<N> with N a multiple of 8 \
+                         between 8 and 256",
+                    ));
+                }
+                output.member_type = Some(member_type);
+            } else if !is_field && meta.path.is_ident("crate") {
+                output.crate_path = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+            } else {
+                return Err(meta.error("unsupported eip712 attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(output)
+}
+
+/// Width of an `uint<bits>` type, with `bits` a multiple of 8 between 8 and 256
+fn uint_bits(member_type: &str) -> Option<u64> {
+    let bits = member_type.strip_prefix("uint")?.parse::<u64>().ok()?;
+    (bits % 8 == 0 && (8..=256).contains(&bits)).then_some(bits)
+}
+
+struct Member {
+    ident: syn::Ident,
+    ty: Type,
+    name: String,
+    member_type: Option<String>,
+}
+
+fn expand_structured_hash(input: DeriveInput) -> Result<TokenStream2, Error> {
+    let ident = &input.ident;
+    let struct_attributes = parse_attributes(&input.attrs, false)?;
+    let type_name = struct_attributes.name.unwrap_or_else(|| ident.to_string());
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    ident,
+                    "StructuredHash can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                ident,
+                "StructuredHash can only be derived for structs",
+            ))
+        }
+    };
+
+    let members = fields
+        .iter()
+        .map(|field| {
+            let attributes = parse_attributes(&field.attrs, true)?;
+            let ident = field.ident.clone().expect("named field");
+            Ok(Member {
+                name: attributes.name.unwrap_or_else(|| ident.to_string()),
+                ident,
+                ty: field.ty.clone(),
+                member_type: attributes.member_type.as_ref().map(LitStr::value),
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let crate_path = struct_attributes
+        .crate_path
+        .unwrap_or_else(|| parse_quote!(::intent));
+    let structured_hash = quote! { #crate_path::structured_hash };
+    let member_declarations = members.iter().map(|member| {
+        let name = &member.name;
+        let ty = &member.ty;
+        match &member.member_type {
+            Some(member_type) => quote! { format!("{} {}", #member_type, #name) },
+            None => quote! {
+                format!("{} {}", <#ty as #structured_hash::Eip712Member>::member_type(), #name)
+            },
+        }
+    });
+    let member_types = members.iter().map(|member| &member.ty);
+    let member_encodings = members.iter().map(|member| {
+        let ident = &member.ident;
+        let name = &member.name;
+        match &member.member_type {
+            Some(member_type) => quote! {
+                #structured_hash::Eip712Member::encode_member_as(&self.#ident, #member_type)
+                    .unwrap_or_else(|| {
+                        panic!("member `{}` of {} does not fit into {}", #name, #type_name, #member_type)
+                    })
+            },
+            None => quote! { #structured_hash::Eip712Member::encode_member(&self.#ident) },
+        }
+    });
+
+    // integer overrides are only accepted for fields implementing `Eip712Uint`
+    let mut generics = input.generics.clone();
+    let where_clause = generics.make_where_clause();
+    for member in members.iter().filter(|member| member.member_type.is_some()) {
+        let ty = &member.ty;
+        where_clause
+            .predicates
+            .push(parse_quote!(#ty: #structured_hash::Eip712Uint));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics #structured_hash::Eip712Member for #ident #ty_generics #where_clause {
+            fn member_type() -> String {
+                #type_name.to_string()
+            }
+
+            fn referenced_types() -> Vec<(String, String)> {
+                let members: Vec<String> = vec![#(#member_declarations),*];
+                let mut referenced_types =
+                    vec![(#type_name.to_string(), format!("{}({})", #type_name, members.join(",")))];
+                #(
+                    referenced_types.extend(
+                        <#member_types as #structured_hash::Eip712Member>::referenced_types()
+                    );
+                )*
+                referenced_types
+            }
+
+            fn encode_member(&self) -> [u8; 32] {
+                <Self as #structured_hash::StructuredHashInterface>::structured_hash(self)
+            }
+        }
+
+        impl #impl_generics #structured_hash::StructuredHashInterface for #ident #ty_generics #where_clause {
+            fn type_encode() -> String {
+                #structured_hash::encode_type(
+                    <Self as #structured_hash::Eip712Member>::referenced_types(),
+                )
+            }
+
+            fn data_encode(&self) -> Vec<u8> {
+                let members: Vec<[u8; 32]> = vec![#(#member_encodings),*];
+                members.concat()
+            }
+        }
+    })
+}
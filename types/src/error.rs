@@ -7,4 +7,12 @@ pub enum TypeConversionError {
     InvalidLibSecp256K1TypeConversion(#[from] Error),
     #[error("Invalid slice length, current length is {0}, but it should be {1}")]
     InvalidSliceLength(usize, usize),
+    #[error("Invalid signature recovery id {0}, it should be one of 0, 1, 27 or 28")]
+    InvalidRecoveryId(u8),
+    #[error("Invalid hex address {0}, it should be 0x followed by 40 hex digits")]
+    InvalidAddressHex(String),
+    #[error("Invalid EIP-55 checksum for address {0}")]
+    InvalidAddressChecksum(String),
+    #[error("Invalid EIP-712 address encoding, the first 12 bytes should be zero")]
+    InvalidAddressEncoding,
 }
@@ -1,3 +1,9 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+use keccak_hash::keccak;
 use libsecp256k1::{PublicKeyFormat, SecretKey};
 use num_bigint::BigUint;
 use plonky2::field::{
@@ -14,6 +20,12 @@ use crate::{error::TypeConversionError, utils::convert_u64_le_to_u8_be};
 
 pub struct PrivateKey(pub(crate) SecretKey);
 
+impl Default for PrivateKey {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PrivateKey {
     /// Initializes a [`PrivateKey`] randomly
     pub fn new() -> Self {
@@ -48,13 +60,19 @@ impl PrivateKey {
         let mut secret_key_big_end_byte_array = [0u8; 32];
         secret_key_big_end_byte_array.copy_from_slice(&big_end_bytes_secret_key);
         let libsecp256k1_sk = SecretKey::parse(&secret_key_big_end_byte_array)
-            .map_err(|e| TypeConversionError::InvalidLibSecp256K1TypeConversion(e))?;
+            .map_err(TypeConversionError::InvalidLibSecp256K1TypeConversion)?;
         Ok(Self(libsecp256k1_sk))
     }
 
     pub fn sign_message(&self, message: &Message) -> Signature {
         Signature(libsecp256k1::sign(&message.0, &self.0).0)
     }
+
+    /// Signs a message, keeping the recovery id of the signature.
+    pub fn sign_message_recoverable(&self, message: &Message) -> RecoverableSignature {
+        let (signature, recovery_id) = libsecp256k1::sign(&message.0, &self.0);
+        RecoverableSignature::new(signature, recovery_id)
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -76,7 +94,7 @@ impl PublicKey {
         Ok(Self::from_private_key(&private_key))
     }
 
-    pub fn as_libsecp256k1_public_key<'a>(&'a self) -> &'a libsecp256k1::PublicKey {
+    pub fn as_libsecp256k1_public_key(&self) -> &libsecp256k1::PublicKey {
         &self.0
     }
 
@@ -100,15 +118,11 @@ impl PublicKey {
         let serialized_public_key_be_bytes = self.0.serialize();
         let public_key_be_bytes_x = &serialized_public_key_be_bytes[1..33];
         let public_key_be_bytes_y = &serialized_public_key_be_bytes[33..65];
-        let plonky2_public_key = ECDSAPublicKey(AffinePoint::nonzero(
-            Secp256K1Base::from_noncanonical_biguint(BigUint::from_bytes_be(
-                &public_key_be_bytes_x,
-            )),
-            Secp256K1Base::from_noncanonical_biguint(BigUint::from_bytes_be(
-                &public_key_be_bytes_y,
-            )),
-        ));
-        plonky2_public_key
+
+        ECDSAPublicKey(AffinePoint::nonzero(
+            Secp256K1Base::from_noncanonical_biguint(BigUint::from_bytes_be(public_key_be_bytes_x)),
+            Secp256K1Base::from_noncanonical_biguint(BigUint::from_bytes_be(public_key_be_bytes_y)),
+        ))
     }
 }
 
@@ -121,7 +135,7 @@ impl Message {
 
     pub fn from_slice(slice: &[u8]) -> Result<Self, TypeConversionError> {
         Ok(Self(libsecp256k1::Message::parse_slice(slice).map_err(
-            |e| TypeConversionError::InvalidLibSecp256K1TypeConversion(e),
+            TypeConversionError::InvalidLibSecp256K1TypeConversion,
         )?))
     }
 
@@ -129,7 +143,7 @@ impl Message {
         self.0.serialize()
     }
 
-    pub fn as_libsecp256k1_message<'a>(&'a self) -> &'a libsecp256k1::Message {
+    pub fn as_libsecp256k1_message(&self) -> &libsecp256k1::Message {
         &self.0
     }
 
@@ -152,7 +166,7 @@ impl Signature {
         Self(signature)
     }
 
-    pub fn as_libsecp256k1_signature<'a>(&'a self) -> &'a libsecp256k1::Signature {
+    pub fn as_libsecp256k1_signature(&self) -> &libsecp256k1::Signature {
         &self.0
     }
 
@@ -171,6 +185,176 @@ impl Signature {
     }
 }
 
+/// Signature together with its recovery id, which allows to recover the signer's [`PublicKey`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecoverableSignature {
+    pub(crate) signature: libsecp256k1::Signature,
+    pub(crate) recovery_id: libsecp256k1::RecoveryId,
+}
+
+impl RecoverableSignature {
+    /// Byte length of Ethereum's `r || s || v` signature format
+    pub const SERIALIZED_LENGTH: usize = 65;
+
+    pub fn new(signature: libsecp256k1::Signature, recovery_id: libsecp256k1::RecoveryId) -> Self {
+        Self {
+            signature,
+            recovery_id,
+        }
+    }
+
+    /// Returns the `r` component, as big endian bytes
+    pub fn r(&self) -> [u8; 32] {
+        self.signature.r.b32()
+    }
+
+    /// Returns the `s` component, as big endian bytes
+    pub fn s(&self) -> [u8; 32] {
+        self.signature.s.b32()
+    }
+
+    /// Returns the `v` component, in Ethereum's 27/28 form
+    pub fn v(&self) -> u8 {
+        27 + self.recovery_id.serialize()
+    }
+
+    /// Serializes the signature as Ethereum's 65-byte `r || s || v`, with `v` in {27, 28}
+    pub fn serialize(&self) -> [u8; Self::SERIALIZED_LENGTH] {
+        let mut output = [0u8; Self::SERIALIZED_LENGTH];
+        output[..64].copy_from_slice(&self.signature.serialize());
+        output[64] = self.v();
+        output
+    }
+
+    /// Parses a 65-byte `r || s || v` signature, where `v` is either in {27, 28} or in {0, 1}
+    pub fn parse_slice(slice: &[u8]) -> Result<Self, TypeConversionError> {
+        if slice.len() != Self::SERIALIZED_LENGTH {
+            return Err(TypeConversionError::InvalidSliceLength(
+                slice.len(),
+                Self::SERIALIZED_LENGTH,
+            ));
+        }
+        let recovery_id = match slice[64] {
+            v @ (0 | 1) => v,
+            v @ (27 | 28) => v - 27,
+            v => return Err(TypeConversionError::InvalidRecoveryId(v)),
+        };
+        let signature = libsecp256k1::Signature::parse_standard_slice(&slice[..64])?;
+        let recovery_id = libsecp256k1::RecoveryId::parse(recovery_id)?;
+        Ok(Self::new(signature, recovery_id))
+    }
+
+    /// Recovers the public key of the signer of `message`
+    pub fn recover_public_key(&self, message: &Message) -> Result<PublicKey, TypeConversionError> {
+        let public_key = libsecp256k1::recover(&message.0, &self.signature, &self.recovery_id)?;
+        Ok(PublicKey(public_key))
+    }
+
+    /// Drops the recovery id
+    pub fn to_signature(&self) -> Signature {
+        Signature(self.signature)
+    }
+}
+
+impl From<RecoverableSignature> for Signature {
+    fn from(value: RecoverableSignature) -> Self {
+        value.to_signature()
+    }
+}
+
+/// Ethereum account address, the last 20 bytes of the keccak256 hash of an uncompressed public key
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Address(pub(crate) [u8; 20]);
+
+impl Address {
+    pub fn new(bytes: [u8; 20]) -> Self {
+        Self(bytes)
+    }
+
+    /// Derives the address of a [`PublicKey`]
+    pub fn from_public_key(public_key: &PublicKey) -> Self {
+        // serialized uncompressed keys are prefixed by the `0x04` tag
+        let public_key_hash = keccak(&public_key.0.serialize()[1..]).to_fixed_bytes();
+        let mut address = [0u8; 20];
+        address.copy_from_slice(&public_key_hash[12..]);
+        Self(address)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 20] {
+        &self.0
+    }
+
+    /// EIP-712 encoding of the address, a 32-byte word left padded with zeros
+    pub fn to_eip712_word(&self) -> [u8; 32] {
+        let mut output = [0u8; 32];
+        output[12..].copy_from_slice(&self.0);
+        output
+    }
+
+    /// Parses an EIP-712 encoded address, whose first 12 bytes should be zero
+    pub fn from_eip712_word(word: &[u8; 32]) -> Result<Self, TypeConversionError> {
+        if word[..12].iter().any(|byte| *byte != 0) {
+            return Err(TypeConversionError::InvalidAddressEncoding);
+        }
+        let mut address = [0u8; 20];
+        address.copy_from_slice(&word[12..]);
+        Ok(Self(address))
+    }
+
+    /// EIP-55 mixed-case checksum encoding, `0x` prefixed
+    pub fn to_checksum(&self) -> String {
+        let lowercase_hex = hex::encode(self.0);
+        let hex_hash = hex::encode(keccak(lowercase_hex.as_bytes()).to_fixed_bytes());
+        let checksummed_hex = lowercase_hex
+            .chars()
+            .zip(hex_hash.chars())
+            .map(|(character, hash_character)| {
+                // letters are uppercased if the corresponding nibble of the hash is at least 8
+                if hash_character.to_digit(16).unwrap_or(0) >= 8 {
+                    character.to_ascii_uppercase()
+                } else {
+                    character
+                }
+            })
+            .collect::<String>();
+        format!("0x{}", checksummed_hex)
+    }
+}
+
+impl Display for Address {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_checksum())
+    }
+}
+
+impl FromStr for Address {
+    type Err = TypeConversionError;
+
+    /// Parses a `0x` prefixed hex address. All lowercase and all uppercase addresses are
+    /// accepted as is, mixed-case addresses should have a valid EIP-55 checksum.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid_hex = || TypeConversionError::InvalidAddressHex(value.to_string());
+        let hex_address = value.strip_prefix("0x").ok_or_else(invalid_hex)?;
+        let bytes = hex::decode(hex_address).map_err(|_| invalid_hex())?;
+        let address = Self(bytes.try_into().map_err(|_| invalid_hex())?);
+
+        let is_single_case = hex_address == hex_address.to_ascii_lowercase()
+            || hex_address == hex_address.to_ascii_uppercase();
+        if !is_single_case && address.to_checksum() != value {
+            return Err(TypeConversionError::InvalidAddressChecksum(
+                value.to_string(),
+            ));
+        }
+        Ok(address)
+    }
+}
+
+impl From<&PublicKey> for Address {
+    fn from(public_key: &PublicKey) -> Self {
+        Self::from_public_key(public_key)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use plonky2::field::{
@@ -180,6 +364,121 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn it_works_address_from_public_key() {
+        let secret_key = SecretKey::parse(&hex_literal::hex!(
+            "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318"
+        ))
+        .unwrap();
+        let public_key = PublicKey::from_private_key(&PrivateKey::from_secret_key(secret_key));
+        assert_eq!(
+            Address::from_public_key(&public_key).to_string(),
+            "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23"
+        );
+    }
+
+    #[test]
+    fn it_works_address_eip55_checksum() {
+        // test vectors from https://eips.ethereum.org/EIPS/eip-55
+        for checksummed_address in [
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+            "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+            "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+        ] {
+            let address = Address::from_str(checksummed_address).unwrap();
+            assert_eq!(address.to_string(), checksummed_address);
+            assert_eq!(
+                Address::from_str(&checksummed_address.to_ascii_lowercase()).unwrap(),
+                address
+            );
+        }
+
+        assert!(matches!(
+            Address::from_str("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD"),
+            Err(TypeConversionError::InvalidAddressChecksum(_))
+        ));
+        assert!(matches!(
+            Address::from_str("5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"),
+            Err(TypeConversionError::InvalidAddressHex(_))
+        ));
+        assert!(matches!(
+            Address::from_str("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeA"),
+            Err(TypeConversionError::InvalidAddressHex(_))
+        ));
+    }
+
+    #[test]
+    fn it_works_address_eip712_word() {
+        let address = Address::new([42u8; 20]);
+        let word = address.to_eip712_word();
+        assert_eq!(word[..12], [0u8; 12]);
+        assert_eq!(Address::from_eip712_word(&word).unwrap(), address);
+
+        let mut invalid_word = word;
+        invalid_word[0] = 1;
+        assert!(matches!(
+            Address::from_eip712_word(&invalid_word),
+            Err(TypeConversionError::InvalidAddressEncoding)
+        ));
+    }
+
+    #[test]
+    fn it_works_recoverable_signature_serialization() {
+        let private_key = PrivateKey::new();
+        let message = Message::new_message([7u8; 32]);
+        let signature = private_key.sign_message_recoverable(&message);
+
+        let serialized = signature.serialize();
+        assert_eq!(serialized[..32], signature.r());
+        assert_eq!(serialized[32..64], signature.s());
+        assert!(serialized[64] == 27 || serialized[64] == 28);
+        assert_eq!(
+            RecoverableSignature::parse_slice(&serialized).unwrap(),
+            signature
+        );
+
+        // `v` in {0, 1} is accepted as well
+        let mut serialized_zero_based = serialized;
+        serialized_zero_based[64] -= 27;
+        assert_eq!(
+            RecoverableSignature::parse_slice(&serialized_zero_based).unwrap(),
+            signature
+        );
+
+        let mut invalid_v = serialized;
+        invalid_v[64] = 29;
+        assert!(matches!(
+            RecoverableSignature::parse_slice(&invalid_v),
+            Err(TypeConversionError::InvalidRecoveryId(29))
+        ));
+        assert!(matches!(
+            RecoverableSignature::parse_slice(&serialized[..64]),
+            Err(TypeConversionError::InvalidSliceLength(64, 65))
+        ));
+    }
+
+    #[test]
+    fn it_works_recoverable_signature_recover_public_key() {
+        let private_key = PrivateKey::new();
+        let public_key = PublicKey::from_private_key(&private_key);
+        let message = Message::new_message([7u8; 32]);
+        let signature = private_key.sign_message_recoverable(&message);
+
+        assert_eq!(signature.recover_public_key(&message).unwrap(), public_key);
+        assert!(libsecp256k1::verify(
+            &message.0,
+            signature.to_signature().as_libsecp256k1_signature(),
+            &public_key.0
+        ));
+
+        let other_message = Message::new_message([8u8; 32]);
+        assert_ne!(
+            signature.recover_public_key(&other_message).ok(),
+            Some(public_key)
+        );
+    }
+
     #[test]
     fn it_works_private_key_from_plonky2_sk() {
         let plonky2_sk = ECDSASecretKey::<Secp256K1>(Secp256K1Scalar::rand());